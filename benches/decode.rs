@@ -0,0 +1,109 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ethabi::Token;
+use ethereum_types::{H160, U256};
+
+fn address(i: u64) -> Token {
+    Token::Address(H160::from_low_u64_be(0xabcdef0123 + i))
+}
+
+fn uint(i: u64) -> Token {
+    Token::Uint(U256::from(i))
+}
+
+// Resembles the orders passed to Seaport's fulfillAvailableAdvancedOrders
+fn seaport_order(i: u64) -> Token {
+    let offer = Token::Tuple(vec![
+        uint(2),
+        address(i + 2),
+        uint(1000 + i),
+        uint(1),
+        uint(1),
+    ]);
+    let consideration = (0..3)
+        .map(|j| {
+            Token::Tuple(vec![
+                uint(0),
+                address(0),
+                uint(0),
+                uint(10000 + j),
+                uint(10000 + j),
+                address(i + j + 3),
+            ])
+        })
+        .collect();
+    let parameters = Token::Tuple(vec![
+        address(i),
+        address(i + 1),
+        Token::Array(vec![offer]),
+        Token::Array(consideration),
+        uint(0),
+        uint(1670000000),
+        uint(1680000000),
+        Token::FixedBytes(vec![0; 32]),
+        uint(123456789 + i),
+        Token::FixedBytes(vec![0x0a; 32]),
+        uint(3),
+    ]);
+    Token::Tuple(vec![
+        parameters,
+        uint(1),
+        uint(1),
+        Token::Bytes(vec![0xab; 65]),
+        Token::Bytes(vec![]),
+    ])
+}
+
+fn seaport_orders(count: u64) -> String {
+    hex::encode(ethabi::encode(&[
+        Token::Array((0..count).map(seaport_order).collect()),
+        Token::Array(vec![]),
+        Token::FixedBytes(vec![0; 32]),
+        uint(count),
+    ]))
+}
+
+fn decode_seaport_orders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seaport_orders");
+    group.sample_size(10);
+    for count in [1, 5, 10] {
+        let encoded_arguments = seaport_orders(count);
+        let words = encoded_arguments.len() / 64;
+        group.bench_with_input(
+            BenchmarkId::from_parameter(words),
+            &encoded_arguments,
+            |b, encoded_arguments| {
+                b.iter(|| abi_decoder::decode_arguments(encoded_arguments).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+// Every element could also be a static tuple, which makes the decoder backtrack a lot
+fn single_element_arrays(count: u64) -> String {
+    hex::encode(ethabi::encode(&[Token::Array(
+        (0..count)
+            .map(|i| Token::Array(vec![Token::Tuple(vec![uint(i / 3), uint(i % 3)])]))
+            .collect(),
+    )]))
+}
+
+fn decode_single_element_arrays(c: &mut Criterion) {
+    let mut group = c.benchmark_group("single_element_arrays");
+    group.sample_size(10);
+    for count in [6, 12, 24] {
+        let encoded_arguments = single_element_arrays(count);
+        let words = encoded_arguments.len() / 64;
+        group.bench_with_input(
+            BenchmarkId::from_parameter(words),
+            &encoded_arguments,
+            |b, encoded_arguments| {
+                b.iter(|| abi_decoder::decode_arguments(encoded_arguments).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode_seaport_orders, decode_single_element_arrays);
+criterion_main!(benches);
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ethabi::token::Token;
+use ethereum_types::H256;
+use ethers::providers::{Http, Provider};
+
+use crate::decoder::heuristic::AddressBook;
+use crate::decoder::signatures::Signatures;
+use crate::decoder::type_hints::{TypeHint, TypeHints};
+use crate::decoder::word::Word;
+use crate::transaction_data::{get_provider, get_transaction_input};
+use crate::{
+    decode_arguments_with_engine, DecodingError, DecodingOptions, DecodingResult, GreedySearch,
+    LayoutEngine,
+};
+
+/// Decoder built once with everything that is shared between decodings: the options, known
+/// signatures and addresses, the provider transactions are fetched from and the calldata of the
+/// transactions fetched so far
+///
+/// Clones share all of it, so a decoder can be cloned cheaply into every task or thread that
+/// decodes.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::{Decoder, DecodingOptions};
+/// use ethabi::Token;
+///
+/// let decoder = Decoder::new(DecodingOptions::default())
+///     .with_signatures("transfer(address,uint256)".parse().unwrap());
+/// let calldata = "a9059cbb\
+///                 000000000000000000000000000000000000000000000000000000000000dead\
+///                 0000000000000000000000000000000000000000000000000000000000000064";
+/// let result = decoder.decode_calldata(calldata).unwrap();
+/// assert_eq!(result.signature.as_deref(), Some("transfer(address,uint256)"));
+/// // Without the signature the small address would be decoded as a number
+/// assert_eq!(result.tokens[0], Token::Address(ethabi::Address::from_low_u64_be(0xdead)));
+/// ```
+#[derive(Clone)]
+pub struct Decoder {
+    options: Arc<DecodingOptions>,
+    engine: Arc<dyn LayoutEngine + Send + Sync>,
+    signatures: Arc<Signatures>,
+    address_book: AddressBook,
+    // Connects to the url in the `RPC_URL` environment variable if not set
+    provider: Option<Arc<Provider<Http>>>,
+    // Calldata of the fetched transactions by their hash
+    transactions: Arc<Mutex<HashMap<String, String>>>,
+}
+
+/// Decoded event log
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodedLog {
+    /// Signature of the event the first topic is the hash of, if the decoder knows it
+    pub event: Option<String>,
+    /// Indexed values (all topics after the event hash, or all topics if the event is not known
+    /// as it might be anonymous), decoded as static words
+    pub topics: Vec<Token>,
+    /// Decoded values that are not indexed
+    pub data: DecodingResult,
+}
+
+impl Decoder {
+    /// Decoder with the options and the greedy search, which does not know any signatures or
+    /// addresses
+    pub fn new(options: DecodingOptions) -> Self {
+        Decoder {
+            options: Arc::new(options),
+            engine: Arc::new(GreedySearch),
+            signatures: Arc::default(),
+            address_book: AddressBook::default(),
+            provider: None,
+            transactions: Arc::default(),
+        }
+    }
+
+    /// Decodes with the layout engine instead of the greedy search
+    pub fn with_engine(mut self, engine: impl LayoutEngine + Send + Sync + 'static) -> Self {
+        self.engine = Arc::new(engine);
+        self
+    }
+
+    /// Decodes the arguments of calls with a known selector as the types of the signature, as far
+    /// as the data is an encoding of them
+    pub fn with_signatures(mut self, signatures: Signatures) -> Self {
+        self.signatures = Arc::new(signatures);
+        self
+    }
+
+    /// Decodes the known addresses as addresses
+    pub fn with_address_book(mut self, address_book: AddressBook) -> Self {
+        Arc::make_mut(&mut self.options)
+            .heuristics
+            .push(address_book.clone());
+        self.address_book = address_book;
+        self
+    }
+
+    /// Fetches transactions from the node at the url
+    pub fn with_provider(mut self, rpc_url: &str) -> Self {
+        self.provider = Some(Arc::new(get_provider(Some(rpc_url))));
+        self
+    }
+
+    /// Options the decoder decodes with
+    pub fn options(&self) -> &DecodingOptions {
+        &self.options
+    }
+
+    /// Signatures the decoder knows
+    pub fn signatures(&self) -> &Signatures {
+        &self.signatures
+    }
+
+    /// Addresses the decoder knows
+    pub fn address_book(&self) -> &AddressBook {
+        &self.address_book
+    }
+
+    /// Decodes abi encoded arguments without selector
+    pub fn decode_arguments(
+        &self,
+        encoded_arguments: &str,
+    ) -> Result<DecodingResult, DecodingError> {
+        decode_arguments_with_engine(encoded_arguments, &self.options, self.engine.as_ref())
+    }
+
+    /// Decodes the arguments of a call, as the types of the called function if its signature is
+    /// known and the arguments are an encoding of its types (trying all signatures with the
+    /// selector in order) and without types otherwise. Type hints in the options take precedence
+    /// over the signatures.
+    pub fn decode_calldata(&self, calldata: &str) -> Result<DecodingResult, DecodingError> {
+        let calldata = calldata.trim_start_matches("0x");
+        let Some(selector) = calldata
+            .get(..8)
+            .and_then(|selector| hex::decode(selector).ok())
+        else {
+            // Not a function call
+            return Ok(DecodingResult::default());
+        };
+        let arguments = &calldata[8..];
+        if self.options.type_hints.is_some() {
+            return self.decode_arguments(arguments);
+        }
+        let selector = selector.try_into().expect("Selectors are 4 bytes");
+        for (signature, param_types) in self.signatures.function_types(selector) {
+            let options = DecodingOptions {
+                type_hints: Some(TypeHints(
+                    param_types.iter().cloned().map(TypeHint::Type).collect(),
+                )),
+                ..(*self.options).clone()
+            };
+            match decode_arguments_with_engine(arguments, &options, self.engine.as_ref()) {
+                Ok(mut result) => {
+                    result.signature = Some(signature.to_string());
+                    return Ok(result);
+                }
+                // Selectors of different signatures can collide
+                Err(DecodingError::NoMatchingLayout) => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        self.decode_arguments(arguments)
+    }
+
+    /// Fetches the calldata of the transaction (once for the decoder and all of its clones) and
+    /// decodes its arguments
+    pub async fn decode_transaction(&self, tx_hash: &str) -> Result<DecodingResult, DecodingError> {
+        let tx_hash = tx_hash.trim_start_matches("0x").to_lowercase();
+        let cached = self
+            .transactions
+            .lock()
+            .expect("Transaction cache is poisoned")
+            .get(&tx_hash)
+            .cloned();
+        let calldata = match cached {
+            Some(calldata) => calldata,
+            None => {
+                let calldata = match &self.provider {
+                    Some(provider) => get_transaction_input(provider, &tx_hash).await,
+                    None => get_transaction_input(&get_provider(None), &tx_hash).await,
+                };
+                self.transactions
+                    .lock()
+                    .expect("Transaction cache is poisoned")
+                    .insert(tx_hash, calldata.clone());
+                calldata
+            }
+        };
+        self.decode_calldata(&calldata)
+    }
+
+    /// Decodes an event log from its topics and data
+    pub fn decode_log(&self, topics: &[H256], data: &str) -> Result<DecodedLog, DecodingError> {
+        let event = topics.first().and_then(|topic| {
+            let events = self.signatures.events(*topic);
+            events.first().map(|event| event.to_string())
+        });
+        let indexed = if event.is_some() {
+            &topics[1..]
+        } else {
+            topics
+        };
+        Ok(DecodedLog {
+            topics: indexed
+                .iter()
+                .map(|topic| self.options.heuristics.tokenize_word(&Word::new(topic.0)))
+                .collect(),
+            data: self.decode_arguments(data)?,
+            event,
+        })
+    }
+
+    /// Decodes the data returned by a call. Data that is 4 bytes longer than a number of words
+    /// is taken to be a revert with an error selector (i.e. `Error(string)`) followed by its
+    /// arguments.
+    pub fn decode_return_data(&self, data: &str) -> Result<DecodingResult, DecodingError> {
+        let data = data.trim_start_matches("0x");
+        if data.len() % 64 == 8 {
+            return self.decode_calldata(data);
+        }
+        self.decode_arguments(data)
+    }
+}
@@ -1,41 +1,357 @@
 use ethabi::token::Token;
-use std::collections::HashMap;
 
+pub mod annotation;
+pub mod constraint_solver;
+pub mod decoded_value;
+pub mod disallowed_markers;
+pub mod empty_values;
+pub mod error;
+pub mod explain;
+pub mod fixed_arrays;
+pub mod heuristic;
+pub mod layout_engine;
+pub mod options;
+pub mod parse_context;
 pub mod parse_marker;
 pub mod parse_token;
+pub mod pins;
+pub mod pointer_graph;
 pub mod preprocessing;
+pub mod priors;
+pub mod query;
+pub mod signatures;
 #[cfg(test)]
 mod tests;
+pub mod trailing_data;
+pub mod type_hints;
+pub mod validation;
+pub mod visit;
+pub mod word;
 
+use disallowed_markers::DisallowedMarkers;
+use error::DecodingError;
+use ethabi::ParamType;
+use explain::Explanation;
+use layout_engine::{GreedySearch, LayoutEngine};
+use options::DecodingOptions;
+use parse_context::{Budget, ParseContext};
 use parse_marker::ParseMarker;
-use preprocessing::{add_padding, chunk_data};
+use preprocessing::add_padding;
+use trailing_data::TrailingData;
+use type_hints::TypeHints;
+use validation::{validate_encoding, DeviationKind};
+use word::{to_words, words_to_bytes, Word};
 // TODO: Add check that ensures arrays have elements of the same type
 
-pub fn chunk_and_decode_data(encoded_arguments: &str) -> Vec<Token> {
+/// Decoded arguments together with any data that was appended after them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodingResult {
+    /// Decoded arguments, including tuples with a single element that are part of the encoding
+    /// (see [`unwrapped_tokens`](Self::unwrapped_tokens) for the arguments without them)
+    pub tokens: Vec<Token>,
+    /// Decoded arguments of the other layouts the engine found for the same data, best first,
+    /// after the decoded arguments with groups of values that repeat the same types read as fixed
+    /// size arrays (if there are any)
+    pub alternatives: Vec<Vec<Token>>,
+    /// Data following the abi encoded arguments that is not part of the encoding
+    pub trailing_data: Option<TrailingData>,
+    /// The arguments are an encoding of the tokens other than the canonical one (e.g. with out of
+    /// order or shared tails or with gaps between them). Unlike [`round_trip`](Self::round_trip)
+    /// this is not set for layouts that do not decode to the tokens at all.
+    pub non_canonical: bool,
+    /// Re-encoding the tokens gives back exactly the decoded data (without trailing data and with
+    /// an incomplete last word padded with zeros). Layouts that do not re-encode to the data are
+    /// only returned if none of the layouts the engine found does, or if they are non canonical
+    /// encodings and [`allow_non_canonical`](DecodingOptions::allow_non_canonical) is set.
+    pub round_trip: bool,
+    /// Why each word was interpreted the way it was, if requested in the options
+    pub explanation: Option<Explanation>,
+    /// Decoding stopped early because a limit in the options was reached. Values that were not
+    /// decoded are returned as raw bytes containing their part of the data.
+    pub partial: bool,
+    /// The arguments are a single tuple behind an offset (i.e. the one struct a function takes).
+    /// The offset to it shows the tuple is part of the encoding rather than a grouping of the
+    /// arguments.
+    pub wrapper_tuple: bool,
+    /// Signature of the function or error whose selector the data starts with, if the
+    /// [`Decoder`](crate::Decoder) that decoded it knows the signature and the arguments are an
+    /// encoding of its types
+    pub signature: Option<String>,
+}
+
+impl DecodingResult {
+    /// Types of the decoded arguments, in which empty values are empty arrays
+    pub fn param_types(&self) -> Vec<ParamType> {
+        self.tokens
+            .iter()
+            .map(parse_token::get_param_type)
+            .collect()
+    }
+
+    /// Types of the decoded arguments, in which `?` marks empty values that could be an empty
+    /// array, bytes or string (i.e. that have no non empty siblings in an array)
+    pub fn types(&self) -> TypeHints {
+        TypeHints(
+            self.tokens
+                .iter()
+                .map(empty_values::get_type_hint)
+                .collect(),
+        )
+    }
+
+    /// Decoded arguments with the fields of a wrapper tuple (see
+    /// [`wrapper_tuple`](Self::wrapper_tuple)) as arguments of their own and every tuple with a
+    /// single element replaced by the element
+    ///
+    /// This is how decoders that know the abi often present the arguments, but unlike the
+    /// decoded tokens it does not encode to the same data if it removed any tuple.
+    pub fn unwrapped_tokens(&self) -> Vec<Token> {
+        let tokens = match self.tokens.as_slice() {
+            [Token::Tuple(fields)] if self.wrapper_tuple => fields.clone(),
+            tokens => tokens.to_vec(),
+        };
+        tokens
+            .into_iter()
+            .map(parse_token::remove_single_element_tuples)
+            .collect()
+    }
+}
+
+pub fn decode_arguments(
+    encoded_arguments: &str,
+    options: &DecodingOptions,
+) -> Result<DecodingResult, DecodingError> {
+    decode_arguments_with_engine(encoded_arguments, options, &GreedySearch)
+}
+
+pub fn decode_arguments_with_engine(
+    encoded_arguments: &str,
+    options: &DecodingOptions,
+    engine: &dyn LayoutEngine,
+) -> Result<DecodingResult, DecodingError> {
+    pins::check_pins(&options.pins)?;
     if encoded_arguments.is_empty() {
-        return Vec::new();
+        return Ok(DecodingResult {
+            round_trip: true,
+            ..Default::default()
+        });
+    }
+
+    let words = to_words(&decode_hex(encoded_arguments)?);
+    let mut budget = Budget::default();
+    if options.explain {
+        budget.start_trace();
+    }
+    // Incomplete words at the end can only be trailing data
+    let complete_words = &words[..encoded_arguments.len() / 64];
+    let data = words_to_bytes(&words);
+    let structured_layouts =
+        trailing_data::decode_without_trailing_data(complete_words, options, engine, &mut budget)
+            .and_then(|(length, mut layouts)| {
+                apply_constraints(&mut layouts, &data, options, |tokens| tokens);
+                (!layouts.is_empty()).then_some((length, layouts))
+            });
+    let (mut result, consumed_arguments) = if let Some((length, mut layouts)) = structured_layouts {
+        options
+            .heuristics
+            .rank(&mut layouts, &data[..length * 32], |tokens| tokens);
+        let round_trip =
+            prefer_round_trip(&mut layouts, &words[..length], options, |tokens| tokens);
+        let mut layouts = layouts.into_iter();
+        let tokens = layouts
+            .next()
+            .expect("Decoding contains at least one layout");
+        let result = DecodingResult {
+            non_canonical: !round_trip && is_non_canonical_encoding(&tokens, &words[..length]),
+            round_trip,
+            explanation: None,
+            tokens,
+            alternatives: layouts.collect(),
+            trailing_data: TrailingData::from_hex(&encoded_arguments[length * 64..]),
+            partial: false,
+            wrapper_tuple: false,
+            signature: None,
+        };
+        (result, &encoded_arguments[..length * 64])
+    } else {
+        // Without a structured decoding there is nothing that tells us where the arguments end
+        let mut layouts = engine.decode_layouts(&words, options, &mut budget);
+        apply_constraints(&mut layouts, &data, options, |(tokens, _)| tokens);
+        if layouts.is_empty() && (options.type_hints.is_some() || !options.pins.is_empty()) {
+            return Err(DecodingError::NoMatchingLayout);
+        }
+        options
+            .heuristics
+            .rank(&mut layouts, &data, |(tokens, _)| tokens);
+        let round_trip = prefer_round_trip(&mut layouts, &words, options, |(tokens, _)| tokens);
+        let mut layouts = layouts.into_iter();
+        let (tokens, partial) = layouts.next().expect("Failed to parse arguments");
+        let result = DecodingResult {
+            non_canonical: !partial && !round_trip && is_non_canonical_encoding(&tokens, &words),
+            round_trip,
+            explanation: None,
+            tokens,
+            alternatives: layouts.map(|(tokens, _)| tokens).collect(),
+            trailing_data: None,
+            partial,
+            wrapper_tuple: false,
+            signature: None,
+        };
+        (result, encoded_arguments)
+    };
+
+    result.tokens = empty_values::resolve_empty_values(result.tokens);
+    let alternatives = std::mem::take(&mut result.alternatives);
+    for alternative in alternatives {
+        let alternative = empty_values::resolve_empty_values(alternative);
+        // Layouts that only differed in the type of empty values are the same now
+        if alternative != result.tokens && !result.alternatives.contains(&alternative) {
+            result.alternatives.push(alternative);
+        }
+    }
+    // Groups of values that repeat the same types encode the same as fixed size arrays, which are
+    // the first alternative reading of the layout unless the type hints already chose between them
+    if options.type_hints.is_none() {
+        let fixed_arrays = fixed_arrays::infer_fixed_arrays(result.tokens.clone());
+        if fixed_arrays != result.tokens && !result.alternatives.contains(&fixed_arrays) {
+            result.alternatives.insert(0, fixed_arrays);
+        }
+    }
+    result.wrapper_tuple = matches!(
+        result.tokens.as_slice(),
+        [token @ Token::Tuple(_)] if token.is_dynamic()
+    );
+
+    if options.explain {
+        result.explanation = Some(Explanation::new(
+            &words,
+            budget.take_trace(),
+            &result.tokens,
+        ));
     }
 
-    let encoded_arguments = add_padding(encoded_arguments);
-    let chunks = chunk_data(&encoded_arguments);
-    println!("#### Encoded calldata (without function selector) ####");
-    for (i, chunk) in chunks.iter().enumerate() {
-        println!(
-            "{}: {} - {}",
-            i,
-            chunk,
-            u64::from_str_radix(chunk.trim_start_matches('0'), 16).unwrap_or(0)
-        );
+    if options.strict {
+        // Validate the unpadded data so that incomplete words are reported as well
+        let data = decode_hex(consumed_arguments)?
+            .into_iter()
+            .take(consumed_arguments.len() / 2)
+            .collect::<Vec<u8>>();
+        let deviations = validate_encoding(&result.param_types(), &data);
+        if !deviations.is_empty() {
+            return Err(DecodingError::NonCanonical(deviations));
+        }
     }
-    println!("\n");
-    decode_chunks(chunks)
+    Ok(result)
+}
+
+// Bytes of the hex encoded data padded to complete words
+fn decode_hex(encoded_data: &str) -> Result<Vec<u8>, DecodingError> {
+    hex::decode(add_padding(encoded_data)).map_err(|error| match error {
+        hex::FromHexError::InvalidHexCharacter { index, .. } => DecodingError::InvalidHex(index),
+        // The padded data always has an even length
+        _ => DecodingError::InvalidHex(
+            encoded_data
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or_default(),
+        ),
+    })
 }
 
-pub fn decode_chunks(chunks: Vec<&str>) -> Vec<Token> {
-    let result = parse_token::parse_token(&ParseMarker::TopLevel, &chunks, &HashMap::new(), true);
+// The canonical encoding of a set of tokens is unique so any other data decoding to the same
+// tokens has to be non canonical
+pub fn is_canonical_encoding(tokens: &[Token], words: &[Word]) -> bool {
+    ethabi::encode(tokens) == words_to_bytes(words)
+}
+
+// Whether the words encode the tokens in a way other than the canonical one, which is the case if
+// decoding them as the types of the tokens gives back the tokens or if only the positions of the
+// tails deviate from the canonical encoding. Layouts that misread the data are neither.
+fn is_non_canonical_encoding(tokens: &[Token], words: &[Word]) -> bool {
+    // Empty values get their type from their siblings, which ethabi::decode does as well
+    let tokens = empty_values::resolve_empty_values(tokens.to_vec());
+    let param_types: Vec<ParamType> = tokens.iter().map(parse_token::get_param_type).collect();
+    let data = words_to_bytes(words);
+    if ethabi::decode(&param_types, &data).is_ok_and(|decoded_tokens| decoded_tokens == tokens) {
+        return true;
+    }
+    validate_encoding(&param_types, &data)
+        .iter()
+        .any(|deviation| {
+            matches!(
+                deviation.kind,
+                DeviationKind::NonMinimalOffset { .. } | DeviationKind::UnreferencedGap { .. }
+            )
+        })
+}
+
+// Keeps the layouts consistent with the type hints and pins in the options, with their values
+// converted to the hinted types
+fn apply_constraints<T>(
+    layouts: &mut Vec<T>,
+    data: &[u8],
+    options: &DecodingOptions,
+    tokens: impl Fn(&mut T) -> &mut Vec<Token>,
+) {
+    if let Some(type_hints) = &options.type_hints {
+        layouts.retain_mut(|layout| match type_hints.apply(tokens(layout)) {
+            Some(hinted_tokens) => {
+                *tokens(layout) = hinted_tokens;
+                true
+            }
+            None => false,
+        });
+    }
+    layouts.retain_mut(|layout| pins::respects_pins(&options.pins, tokens(layout), data));
+}
+
+// Moves the best layout that re-encodes to the words to the front and returns whether there is
+// one, otherwise the layouts are left as they are. If non canonical encodings are allowed, a best
+// layout that is a non canonical encoding of its tokens stays in front, as the words re-encoding
+// differently is expected for those.
+fn prefer_round_trip<T>(
+    layouts: &mut Vec<T>,
+    words: &[Word],
+    options: &DecodingOptions,
+    tokens: impl Fn(&T) -> &[Token],
+) -> bool {
+    match layouts
+        .iter()
+        .position(|layout| is_canonical_encoding(tokens(layout), words))
+    {
+        Some(0) => true,
+        Some(_)
+            if options.allow_non_canonical
+                && is_non_canonical_encoding(tokens(&layouts[0]), words) =>
+        {
+            false
+        }
+        Some(position) => {
+            let layout = layouts.remove(position);
+            layouts.insert(0, layout);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the decoded tokens of the words with the given markers disallowed, in which every value
+/// behind an offset is wrapped in a tuple of its own
+pub fn parse_arguments(
+    words: &[Word],
+    disallowed_markers: &DisallowedMarkers,
+    context: &mut ParseContext,
+) -> Option<Vec<Token>> {
+    let result = parse_token::parse_token(
+        &ParseMarker::TopLevel,
+        words,
+        0,
+        disallowed_markers,
+        true,
+        context,
+    );
     if let Some(parse_token::TokenOrTopLevel::TopLevel(tokens)) = result {
-        tokens
+        Some(tokens)
     } else {
-        panic!("Failed to parse arguments");
+        None
     }
 }
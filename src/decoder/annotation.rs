@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+use ethabi::token::Token;
+
+use super::word::{to_words, Word};
+
+/// What a word of the encoded data is part of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordRole {
+    /// Static value (or part of a static tuple / fixed size array) in the head of a tuple or array
+    HeadValue,
+    /// Offset pointing to the tail of a dynamic value
+    Offset {
+        /// Index of the word the tail starts at
+        target: usize,
+    },
+    /// Length of an array or byte string
+    Length {
+        /// Words of the elements or the content
+        region: Range<usize>,
+    },
+    /// Content of a byte string
+    BytesData {
+        /// Index of the word containing the length of the byte string
+        length_word: usize,
+        /// Number of zero bytes at the end of the word that only pad the content
+        padding: usize,
+    },
+    /// Word between the tails of a non canonical encoding that is not part of any value
+    Padding,
+    /// Data following the encoded arguments
+    Trailing,
+}
+
+/// Role of a word together with the token it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordAnnotation {
+    /// What the word is part of
+    pub role: WordRole,
+    /// Indices of the token the word belongs to, first among the arguments and then among the
+    /// elements of each tuple or array (see [`get_token`]). Offsets and lengths belong to the
+    /// value they point to or measure, padding and trailing data to no token (an empty path).
+    pub path: Vec<usize>,
+}
+
+/// Annotates every word of the encoded arguments (without function selector) with its role in
+/// the encoding of the tokens. An incomplete word at the end is annotated as well.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::WordRole;
+///
+/// // An array of the two numbers 3 and 4
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000002\
+///                0000000000000000000000000000000000000000000000000000000000000003\
+///                0000000000000000000000000000000000000000000000000000000000000004";
+/// let result = abi_decoder::decode_arguments(encoded).unwrap();
+/// let annotations = abi_decoder::annotate_words(&result.tokens, &hex::decode(encoded).unwrap());
+/// assert_eq!(annotations[0].role, WordRole::Offset { target: 1 });
+/// assert_eq!(annotations[1].role, WordRole::Length { region: 2..4 });
+/// assert_eq!(annotations[3].role, WordRole::HeadValue);
+/// assert_eq!(annotations[3].path, vec![0, 1]);
+/// ```
+pub fn annotate_words(tokens: &[Token], data: &[u8]) -> Vec<WordAnnotation> {
+    let mut padded_data = data.to_vec();
+    padded_data.resize(data.len().div_ceil(32) * 32, 0);
+    let words = to_words(&padded_data);
+    let mut annotator = Annotator {
+        words: &words,
+        annotations: vec![None; words.len()],
+    };
+    let end = annotator
+        .annotate_tuple(tokens, 0, &mut Vec::new())
+        .unwrap_or(0);
+    annotator
+        .annotations
+        .into_iter()
+        .enumerate()
+        .map(|(index, annotation)| {
+            annotation.unwrap_or_else(|| WordAnnotation {
+                role: if index < end {
+                    WordRole::Padding
+                } else {
+                    WordRole::Trailing
+                },
+                path: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Token at the path of indices, first among the arguments and then among the elements of each
+/// tuple or array
+pub fn get_token<'a>(tokens: &'a [Token], path: &[usize]) -> Option<&'a Token> {
+    let (first, rest) = path.split_first()?;
+    let mut token = tokens.get(*first)?;
+    for index in rest {
+        token = match token {
+            Token::Tuple(tokens) | Token::Array(tokens) | Token::FixedArray(tokens) => {
+                tokens.get(*index)?
+            }
+            _ => return None,
+        };
+    }
+    Some(token)
+}
+
+struct Annotator<'a> {
+    words: &'a [Word],
+    annotations: Vec<Option<WordAnnotation>>,
+}
+
+impl<'a> Annotator<'a> {
+    // Annotates the head of a tuple starting at the base and all of its tails. Returns the index
+    // of the word after the last word belonging to the tuple.
+    fn annotate_tuple(
+        &mut self,
+        tokens: &[Token],
+        base: usize,
+        path: &mut Vec<usize>,
+    ) -> Option<usize> {
+        let mut head = base;
+        let mut end = base;
+        for (index, token) in tokens.iter().enumerate() {
+            path.push(index);
+            if token.is_dynamic() {
+                let offset = self.words.get(head)?.as_usize()?;
+                let target = base + offset / 32;
+                self.set(head, WordRole::Offset { target }, path)?;
+                end = end.max(self.annotate_dynamic(token, target, path)?);
+                head += 1;
+            } else {
+                head = self.annotate_static(token, head, path)?;
+            }
+            path.pop();
+        }
+        Some(end.max(head))
+    }
+
+    // Returns the index of the word after the last word belonging to the value
+    fn annotate_dynamic(
+        &mut self,
+        token: &Token,
+        start: usize,
+        path: &mut Vec<usize>,
+    ) -> Option<usize> {
+        match token {
+            Token::Bytes(_) | Token::String(_) => {
+                let length = match token {
+                    Token::String(string) => string.len(),
+                    Token::Bytes(bytes) => bytes.len(),
+                    _ => unreachable!(),
+                };
+                let length_words = length.div_ceil(32);
+                let region = start + 1..start + 1 + length_words;
+                for index in region.clone() {
+                    let padding = if index + 1 == region.end {
+                        length_words * 32 - length
+                    } else {
+                        0
+                    };
+                    let role = WordRole::BytesData {
+                        length_word: start,
+                        padding,
+                    };
+                    self.set(index, role, path)?;
+                }
+                self.set(start, WordRole::Length { region }, path)?;
+                Some(start + 1 + length_words)
+            }
+            Token::Array(tokens) => {
+                let end = self.annotate_tuple(tokens, start + 1, path)?;
+                self.set(
+                    start,
+                    WordRole::Length {
+                        region: start + 1..end,
+                    },
+                    path,
+                )?;
+                Some(end)
+            }
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                self.annotate_tuple(tokens, start, path)
+            }
+            _ => panic!("Static token passed to annotate_dynamic"),
+        }
+    }
+
+    // Returns the index of the word after the value
+    fn annotate_static(
+        &mut self,
+        token: &Token,
+        index: usize,
+        path: &mut Vec<usize>,
+    ) -> Option<usize> {
+        match token {
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                let mut index = index;
+                for (element_index, token) in tokens.iter().enumerate() {
+                    path.push(element_index);
+                    index = self.annotate_static(token, index, path)?;
+                    path.pop();
+                }
+                Some(index)
+            }
+            _ => {
+                self.set(index, WordRole::HeadValue, path)?;
+                Some(index + 1)
+            }
+        }
+    }
+
+    fn set(&mut self, index: usize, role: WordRole, path: &[usize]) -> Option<()> {
+        *self.annotations.get_mut(index)? = Some(WordAnnotation {
+            role,
+            path: path.to_vec(),
+        });
+        Some(())
+    }
+}
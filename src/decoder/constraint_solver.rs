@@ -0,0 +1,589 @@
+use std::collections::HashMap;
+
+use ethabi::token::Token;
+
+use super::layout_engine::LayoutEngine;
+use super::options::DecodingOptions;
+use super::parse_context::Budget;
+use super::parse_token::tokenize_argument;
+use super::word::{words_to_bytes, Word};
+
+/// Layout engine that finds every layout of the data satisfying the rules of the abi encoding
+///
+/// The rules are expressed as constraints on the words of each region (the arguments, or a tail
+/// that an offset points to):
+/// - Offsets in the head of a tuple or array point into its tail, in the order of the offsets
+///   unless non canonical encodings are allowed
+/// - The tails tile the rest of the region without gaps or overlap, each one ending where the
+///   next one starts
+/// - Lengths consume exactly their region, either as the content of a byte string (with zero
+///   padding) or as the elements of an array. For non canonical encodings the content of a byte
+///   string may end before its region does as a last resort.
+/// - A tuple behind an offset contains a dynamic value, static tuples are encoded in place
+/// - The elements of an array have the same shape (bytes, arrays and tuples are not mixed)
+///
+/// The solver searches the heads of each region depth first, trying an offset before a static
+/// value at every word and in a tail bytes before arrays, so the layouts come out ranked by the
+/// same preference for dynamic types as the greedy search. Unlike the greedy search it returns
+/// the layouts exactly as encoded (e.g. without removing single element tuples). Words are only
+/// tried as offsets if a tail can start at their target, and in canonical encodings a head is
+/// abandoned as soon as it ends a tail that is known to have no layout.
+///
+/// The number of layouts can grow exponentially with the size of the data, so only the best
+/// `max_layouts` layouts of each region are kept and the limits in the options apply to the
+/// tails and heads the solver tries.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstraintSolver {
+    /// Maximum number of layouts returned (and kept for each region of the data)
+    pub max_layouts: usize,
+}
+
+impl Default for ConstraintSolver {
+    fn default() -> Self {
+        ConstraintSolver { max_layouts: 16 }
+    }
+}
+
+impl LayoutEngine for ConstraintSolver {
+    fn decode_layouts(
+        &self,
+        words: &[Word],
+        options: &DecodingOptions,
+        budget: &mut Budget,
+    ) -> Vec<(Vec<Token>, bool)> {
+        let mut solver = Solver {
+            words,
+            options,
+            budget,
+            max_layouts: self.max_layouts.max(1),
+            solved: HashMap::new(),
+            partial: false,
+        };
+        let arguments = Region {
+            kind: RegionKind::Arguments,
+            start: 0,
+            end: words.len(),
+            depth: 0,
+        };
+        let mut layouts: Vec<Vec<Token>> = solver
+            .solve_all(arguments)
+            .into_iter()
+            .map(|token| match token {
+                Token::Tuple(tokens) => tokens,
+                _ => panic!("Arguments solved to a non tuple token"),
+            })
+            .collect();
+        // Treating every word as a static value always satisfies the rules, so there can only be
+        // no layout if the limits stopped the search before reaching it
+        if layouts.is_empty() {
+            solver.partial = true;
+            layouts.push(
+                words
+                    .iter()
+                    .map(|word| tokenize_argument(word, options))
+                    .collect(),
+            );
+        }
+        let partial = solver.partial;
+        layouts
+            .into_iter()
+            .map(|tokens| (tokens, partial))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RegionKind {
+    Arguments,
+    Tail,
+}
+
+// Words between start (inclusive) and end (exclusive) that are decoded as one value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Region {
+    kind: RegionKind,
+    start: usize,
+    end: usize,
+    // Number of offsets between the arguments and the region
+    depth: usize,
+}
+
+// Choice for one word of a head, the offsets are relative to the start of the head
+#[derive(Debug, Clone, Copy)]
+struct Choice {
+    target: Option<usize>,
+    // Smallest offset chosen so far (where the tail starts) or the end of the region
+    head_length: usize,
+    last_target: Option<usize>,
+    static_allowed: bool,
+}
+
+struct Solver<'a> {
+    words: &'a [Word],
+    options: &'a DecodingOptions,
+    budget: &'a mut Budget,
+    max_layouts: usize,
+    // Ranked alternatives for every solved region, arguments are solved to tuples
+    solved: HashMap<Region, Vec<Token>>,
+    partial: bool,
+}
+
+impl Solver<'_> {
+    // Solves the region and all regions it depends on. Instead of recursing into the tails, a
+    // region whose tails are not solved yet is tried again once they are, so the nesting of the
+    // data is only bounded by memory.
+    fn solve_all(&mut self, region: Region) -> Vec<Token> {
+        let mut pending = vec![region];
+        while let Some(region) = pending.last().copied() {
+            if self.solved.contains_key(&region) {
+                pending.pop();
+                continue;
+            }
+            match self.solve(region) {
+                Ok(alternatives) => {
+                    self.solved.insert(region, alternatives);
+                    pending.pop();
+                }
+                Err(missing) => {
+                    if !self.enter(missing) {
+                        let raw_bytes = words_to_bytes(&self.words[missing.start..missing.end]);
+                        self.solved.insert(missing, vec![Token::Bytes(raw_bytes)]);
+                    } else {
+                        pending.push(missing);
+                    }
+                }
+            }
+        }
+        self.solved.remove(&region).unwrap_or_default()
+    }
+
+    // Counts a tail towards the limits and returns false if it has to be left undecoded
+    fn enter(&mut self, region: Region) -> bool {
+        let depth_exceeded = self
+            .options
+            .max_depth
+            .is_some_and(|max_depth| region.depth > max_depth);
+        if depth_exceeded {
+            self.budget.reach_limit();
+        }
+        if depth_exceeded || !self.budget.step(self.options) {
+            self.partial = true;
+            return false;
+        }
+        true
+    }
+
+    // Returns the ranked alternatives of the region or a tail that has to be solved first
+    fn solve(&mut self, region: Region) -> Result<Vec<Token>, Region> {
+        let mut alternatives = Vec::new();
+        match region.kind {
+            RegionKind::Arguments => {
+                for tokens in self.solve_tuple(region, None, false)? {
+                    alternatives.push(Token::Tuple(tokens));
+                }
+            }
+            RegionKind::Tail => {
+                for tokens in self.solve_tuple(region, Some(true), true)? {
+                    alternatives.push(Token::Tuple(tokens));
+                }
+                alternatives.extend(self.solve_length_prefixed(region)?);
+                for tokens in self.solve_tuple(region, Some(false), true)? {
+                    alternatives.push(Token::Tuple(tokens));
+                }
+            }
+        }
+        alternatives.truncate(self.max_layouts);
+        Ok(alternatives)
+    }
+
+    // Searches all heads of the region depth first, where the first word can be forced to be an
+    // offset or a static value
+    fn solve_tuple(
+        &mut self,
+        region: Region,
+        first_is_offset: Option<bool>,
+        needs_offset: bool,
+    ) -> Result<Vec<Vec<Token>>, Region> {
+        let length = region.end - region.start;
+        let tail_starts = self.get_tail_starts(region);
+        let mut layouts = Vec::new();
+        let mut head: Vec<Choice> = Vec::new();
+        let mut descend = true;
+        loop {
+            if descend {
+                let i = head.len();
+                let (head_length, last_target) = head
+                    .last()
+                    .map(|choice| (choice.head_length, choice.last_target))
+                    .unwrap_or((length, None));
+                if i == head_length {
+                    descend = false;
+                    if needs_offset && last_target.is_none() {
+                        continue;
+                    }
+                    if !self.budget.check(self.options) {
+                        self.partial = true;
+                        break;
+                    }
+                    let targets: Vec<Option<usize>> =
+                        head.iter().map(|choice| choice.target).collect();
+                    let remaining = self.max_layouts - layouts.len();
+                    layouts.extend(self.compose_head(
+                        region.start,
+                        region,
+                        &targets,
+                        false,
+                        remaining,
+                    )?);
+                    if layouts.len() >= self.max_layouts {
+                        break;
+                    }
+                    continue;
+                }
+                let offset_allowed = i > 0 || first_is_offset != Some(false);
+                let static_allowed = i > 0 || first_is_offset != Some(true);
+                let target = self
+                    .get_offset(region.start, i, length, last_target)
+                    .filter(|target| offset_allowed && tail_starts[*target])
+                    .filter(|target| !self.ends_unsolvable_tail(region, last_target, *target));
+                match target {
+                    Some(target) => head.push(Choice {
+                        target: Some(target),
+                        head_length: head_length.min(target),
+                        last_target: Some(last_target.map_or(target, |last| last.max(target))),
+                        static_allowed,
+                    }),
+                    None if static_allowed => head.push(Choice {
+                        target: None,
+                        head_length,
+                        last_target,
+                        static_allowed,
+                    }),
+                    None => descend = false,
+                }
+            } else {
+                let Some(choice) = head.pop() else {
+                    break;
+                };
+                if choice.target.is_some() && choice.static_allowed {
+                    let (head_length, last_target) = head
+                        .last()
+                        .map(|choice| (choice.head_length, choice.last_target))
+                        .unwrap_or((length, None));
+                    head.push(Choice {
+                        target: None,
+                        head_length,
+                        last_target,
+                        static_allowed: true,
+                    });
+                    descend = true;
+                }
+            }
+        }
+        Ok(layouts)
+    }
+
+    // Whether a tail can start at each word of the region (relative to its start), which requires
+    // a length whose value fits before the end or an offset pointing inside the tail. The tails of
+    // a head end at or before the end of the region, so words failing this for the whole region
+    // are never the target of an offset and the heads pointing to them are not enumerated.
+    fn get_tail_starts(&self, region: Region) -> Vec<bool> {
+        let length = region.end - region.start;
+        let mut tail_starts = vec![false; length];
+        // Number of offsets (minus the ones that ended) covering each tail start
+        let mut covering = vec![0isize; length + 1];
+        for (j, word) in self.words[region.start..region.end].iter().enumerate() {
+            let Some(value) = word.as_usize() else {
+                continue;
+            };
+            if j + 1 + value.div_ceil(32) <= length {
+                tail_starts[j] = true;
+            }
+            // An offset at j points inside a tail starting at t if j < t + value / 32 < length
+            let distance = value / 32;
+            if value % 32 != 0 || distance == 0 || distance >= length {
+                continue;
+            }
+            let first = (j + 1).saturating_sub(distance);
+            let last = j.min(length - distance - 1);
+            if first <= last {
+                covering[first] += 1;
+                covering[last + 1] -= 1;
+            }
+        }
+        let mut count = 0;
+        for (tail_start, covered) in tail_starts.iter_mut().zip(&covering) {
+            count += covered;
+            *tail_start |= count > 0;
+        }
+        tail_starts
+    }
+
+    // Whether the offset with the target ends the tail of the last offset (in canonical encodings,
+    // where the tails are in the order of their offsets) and that tail is known to have no layout
+    fn ends_unsolvable_tail(
+        &self,
+        region: Region,
+        last_target: Option<usize>,
+        target: usize,
+    ) -> bool {
+        let Some(last_target) = last_target.filter(|_| !self.options.allow_non_canonical) else {
+            return false;
+        };
+        let tail = Region {
+            kind: RegionKind::Tail,
+            start: region.start + last_target,
+            end: region.start + target,
+            depth: region.depth + 1,
+        };
+        self.solved.get(&tail).is_some_and(Vec::is_empty)
+    }
+
+    // Target (relative to the head) of the word at the given position of the head if it can be an
+    // offset into the tail
+    fn get_offset(
+        &self,
+        head_start: usize,
+        i: usize,
+        end: usize,
+        last_target: Option<usize>,
+    ) -> Option<usize> {
+        let value = self.words[head_start + i].as_usize()?;
+        if value % 32 != 0 {
+            return None;
+        }
+        let target = value / 32;
+        if target <= i || target >= end {
+            return None;
+        }
+        if !self.options.allow_non_canonical && last_target.is_some_and(|last| target <= last) {
+            return None;
+        }
+        Some(target)
+    }
+
+    // All combinations of the alternatives of the tails of a complete head in ranked order
+    fn compose_head(
+        &self,
+        head_start: usize,
+        region: Region,
+        targets: &[Option<usize>],
+        same_shape: bool,
+        max_layouts: usize,
+    ) -> Result<Vec<Vec<Token>>, Region> {
+        let mut tail_starts: Vec<usize> = targets.iter().flatten().copied().collect();
+        tail_starts.sort_unstable();
+        tail_starts.dedup();
+        // Tails are ranked in the order their offsets appear in the head
+        let mut tails: Vec<usize> = Vec::new();
+        for target in targets.iter().flatten() {
+            if !tails.contains(target) {
+                tails.push(*target);
+            }
+        }
+        let mut tail_alternatives = Vec::new();
+        for target in &tails {
+            let position = tail_starts
+                .binary_search(target)
+                .expect("Tail start exists");
+            let end = tail_starts
+                .get(position + 1)
+                .map_or(region.end, |next| head_start + next);
+            let tail = Region {
+                kind: RegionKind::Tail,
+                start: head_start + target,
+                end,
+                depth: region.depth + 1,
+            };
+            tail_alternatives.push(self.solved.get(&tail).ok_or(tail)?);
+        }
+        let combinations = get_combinations(&tail_alternatives, same_shape, max_layouts);
+        Ok(combinations
+            .into_iter()
+            .map(|combination| {
+                targets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, target)| match target {
+                        Some(target) => {
+                            let tail = tails.iter().position(|t| t == target).unwrap();
+                            tail_alternatives[tail][combination[tail]].clone()
+                        }
+                        None => tokenize_argument(&self.words[head_start + i], self.options),
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    // Alternatives of a tail starting with a length, for a length of zero the empty array is
+    // preferred over empty bytes. Bytes followed by a gap (in non canonical encodings) come last,
+    // and the heuristics rank the layouts they are part of after those without gaps.
+    fn solve_length_prefixed(&mut self, region: Region) -> Result<Vec<Token>, Region> {
+        let Some(length) = self.words[region.start].as_usize() else {
+            return Ok(Vec::new());
+        };
+        let mut alternatives = Vec::new();
+        if length == 0 {
+            alternatives.extend(self.get_static_array(region, length));
+            alternatives.extend(self.get_bytes(region, length, true));
+        } else {
+            alternatives.extend(self.get_bytes(region, length, true));
+            alternatives.extend(self.get_dynamic_array(region, length)?);
+            alternatives.extend(self.get_static_array(region, length));
+            if self.options.allow_non_canonical {
+                alternatives.extend(self.get_bytes(region, length, false));
+            }
+        }
+        Ok(alternatives)
+    }
+
+    // Bytes whose content fills the region exactly, or (if not exact) ends before the region
+    // does, leaving the words after it as a gap
+    fn get_bytes(&self, region: Region, length: usize, exact: bool) -> Option<Token> {
+        let content_end = region.start + 1 + length.div_ceil(32);
+        let fits = if exact {
+            content_end == region.end
+        } else {
+            content_end < region.end
+        };
+        if !fits {
+            return None;
+        }
+        let content = &self.words[region.start + 1..content_end];
+        let mut bytes = words_to_bytes(content);
+        if bytes[length..].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        bytes.truncate(length);
+        Some(Token::Bytes(bytes))
+    }
+
+    // Array whose elements are static values of the same number of words
+    fn get_static_array(&self, region: Region, length: usize) -> Option<Token> {
+        let elements = &self.words[region.start + 1..region.end];
+        if length == 0 {
+            return elements.is_empty().then(|| Token::Array(Vec::new()));
+        }
+        if elements.is_empty() || !elements.len().is_multiple_of(length) {
+            return None;
+        }
+        let element_size = elements.len() / length;
+        let tokens = if element_size == 1 {
+            elements
+                .iter()
+                .map(|word| tokenize_argument(word, self.options))
+                .collect()
+        } else {
+            elements
+                .chunks(element_size)
+                .map(|element| {
+                    Token::Tuple(
+                        element
+                            .iter()
+                            .map(|word| tokenize_argument(word, self.options))
+                            .collect(),
+                    )
+                })
+                .collect()
+        };
+        Some(Token::Array(tokens))
+    }
+
+    // Array whose elements are dynamic values behind one offset each
+    fn get_dynamic_array(&self, region: Region, length: usize) -> Result<Vec<Token>, Region> {
+        let head_start = region.start + 1;
+        // Every element needs an offset and at least one word of tail
+        if length >= region.end - head_start {
+            return Ok(Vec::new());
+        }
+        let mut targets = Vec::new();
+        let mut last_target = None;
+        for i in 0..length {
+            let Some(target) = self.get_offset(head_start, i, region.end - head_start, last_target)
+            else {
+                return Ok(Vec::new());
+            };
+            if target < length {
+                return Ok(Vec::new());
+            }
+            last_target = Some(last_target.map_or(target, |last: usize| last.max(target)));
+            targets.push(Some(target));
+        }
+        if targets.iter().flatten().min() != Some(&length) {
+            return Ok(Vec::new());
+        }
+        let layouts = self.compose_head(head_start, region, &targets, true, self.max_layouts)?;
+        Ok(layouts.into_iter().map(Token::Array).collect())
+    }
+}
+
+// Index combinations of the alternatives in lexicographic order, the best max_layouts
+// combinations only ever use the first max_layouts alternatives of every tail. The elements of an
+// array all have the same type, so for those only alternatives of the same shape as the chosen
+// alternative of the first element are combined.
+fn get_combinations(
+    alternatives: &[&Vec<Token>],
+    same_shape: bool,
+    max_layouts: usize,
+) -> Vec<Vec<usize>> {
+    if !same_shape || alternatives.is_empty() {
+        let indices: Vec<Vec<usize>> = alternatives
+            .iter()
+            .map(|tail_alternatives| (0..tail_alternatives.len()).collect())
+            .collect();
+        return get_product(&indices, max_layouts);
+    }
+    let mut combinations = Vec::new();
+    for (first_index, first) in alternatives[0].iter().enumerate() {
+        let mut indices = vec![vec![first_index]];
+        for tail_alternatives in &alternatives[1..] {
+            indices.push(
+                (0..tail_alternatives.len())
+                    .filter(|index| have_same_shape(first, &tail_alternatives[*index]))
+                    .collect(),
+            );
+        }
+        combinations.extend(get_product(&indices, max_layouts - combinations.len()));
+        if combinations.len() >= max_layouts {
+            break;
+        }
+    }
+    combinations
+}
+
+fn get_product(indices: &[Vec<usize>], max_layouts: usize) -> Vec<Vec<usize>> {
+    let mut combinations = vec![Vec::new()];
+    for tail_indices in indices {
+        let mut extended = Vec::new();
+        'outer: for combination in &combinations {
+            for index in tail_indices {
+                if extended.len() >= max_layouts {
+                    break 'outer;
+                }
+                let mut combination = combination.clone();
+                combination.push(*index);
+                extended.push(combination);
+            }
+        }
+        combinations = extended;
+    }
+    combinations
+}
+
+// Whether the tokens can be values of the same type. Which type a static word has is only guessed
+// from its value, so all static words have the same shape, as do empty arrays and any other array.
+fn have_same_shape(a: &Token, b: &Token) -> bool {
+    match (a, b) {
+        (Token::Array(a), Token::Array(b)) => {
+            a.is_empty() || b.is_empty() || have_same_shape(&a[0], &b[0])
+        }
+        (Token::Tuple(a), Token::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| have_same_shape(a, b))
+        }
+        (Token::Bytes(_), Token::Bytes(_)) => true,
+        (Token::Array(_) | Token::Tuple(_) | Token::Bytes(_), _)
+        | (_, Token::Array(_) | Token::Tuple(_) | Token::Bytes(_)) => false,
+        _ => true,
+    }
+}
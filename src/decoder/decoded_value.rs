@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use ethabi::token::Token;
+use ethabi::Address;
+use ethereum_types::U256;
+
+use super::annotation::{annotate_words, WordRole};
+
+/// Type of value a static word can be decoded as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WordType {
+    /// Unsigned number
+    Uint,
+    /// Signed number, a candidate only for negative values (others are the same number as uint)
+    Int,
+    /// Address, a candidate if the word fits into 20 bytes
+    Address,
+    /// Boolean, a candidate for zero and one
+    Bool,
+    /// Left aligned bytes of the given size
+    FixedBytes(usize),
+}
+
+/// Value decoded from the encoded arguments, which keeps the ambiguity of static words and the
+/// words each value was decoded from
+///
+/// Spans are the indices of the words the encoding of the value occupies (the tail for values
+/// behind an offset, without the offset itself). Values that could not be located in the data
+/// (i.e. regions left undecoded because of a limit) have an empty span.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::{DecodedValue, Resolution, WordType};
+/// use ethabi::Token;
+///
+/// // The number 1, which could just as well be `true` or the address 0x00..01
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000001";
+/// let result = abi_decoder::decode_arguments(encoded).unwrap();
+/// let values = DecodedValue::from_tokens(&result.tokens, &hex::decode(encoded).unwrap());
+/// let DecodedValue::Word { candidates, span, .. } = &values[0] else { panic!() };
+/// assert_eq!(candidates[..3], [WordType::Uint, WordType::Address, WordType::Bool]);
+/// assert_eq!(*span, 0..1);
+///
+/// // Converting back gives the decoded tokens unless another type is preferred
+/// assert_eq!(values[0].to_token(&Resolution::default()), result.tokens[0]);
+/// let resolution = Resolution::prefer(vec![WordType::Bool]);
+/// assert_eq!(values[0].to_token(&resolution), Token::Bool(true));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedValue {
+    /// Static word
+    Word {
+        /// Raw content of the word
+        word: [u8; 32],
+        /// Types the word is a valid encoding of, starting with the type the decoder chose
+        candidates: Vec<WordType>,
+        /// Index of the word
+        span: Range<usize>,
+    },
+    /// Byte string
+    Bytes {
+        /// Content of the byte string
+        bytes: Vec<u8>,
+        /// Words of the length and the content
+        span: Range<usize>,
+    },
+    /// String
+    String {
+        /// Content of the string
+        string: String,
+        /// Words of the length and the content
+        span: Range<usize>,
+    },
+    /// Dynamic array
+    Array {
+        /// Decoded elements
+        elements: Vec<DecodedValue>,
+        /// Words of the length and the elements
+        span: Range<usize>,
+    },
+    /// Fixed size array
+    FixedArray {
+        /// Decoded elements
+        elements: Vec<DecodedValue>,
+        /// Words of the elements
+        span: Range<usize>,
+    },
+    /// Tuple
+    Tuple {
+        /// Decoded fields
+        fields: Vec<DecodedValue>,
+        /// Words of the fields
+        span: Range<usize>,
+    },
+}
+
+/// Chooses the type of words that are valid encodings of several types when converting decoded
+/// values to tokens
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolution {
+    /// Types in order of preference (ignoring the size of fixed bytes), words that are no valid
+    /// encoding of any of them keep the type the decoder chose
+    pub preferred_types: Vec<WordType>,
+}
+
+impl Resolution {
+    /// Prefers the types in the given order
+    pub fn prefer(preferred_types: Vec<WordType>) -> Self {
+        Resolution { preferred_types }
+    }
+
+    fn choose(&self, candidates: &[WordType]) -> Option<WordType> {
+        self.preferred_types
+            .iter()
+            .find_map(|preferred| {
+                candidates.iter().find(|candidate| {
+                    std::mem::discriminant(*candidate) == std::mem::discriminant(preferred)
+                })
+            })
+            .or(candidates.first())
+            .copied()
+    }
+}
+
+impl DecodedValue {
+    /// Decoded values of the tokens decoded from the data (without function selector)
+    pub fn from_tokens(tokens: &[Token], data: &[u8]) -> Vec<DecodedValue> {
+        let spans = get_spans(tokens, data);
+        let mut path = Vec::new();
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(index, token)| {
+                path.push(index);
+                let value = from_token(token, &spans, &mut path);
+                path.pop();
+                value
+            })
+            .collect()
+    }
+
+    /// Words the encoding of the value occupies
+    pub fn span(&self) -> &Range<usize> {
+        match self {
+            DecodedValue::Word { span, .. }
+            | DecodedValue::Bytes { span, .. }
+            | DecodedValue::String { span, .. }
+            | DecodedValue::Array { span, .. }
+            | DecodedValue::FixedArray { span, .. }
+            | DecodedValue::Tuple { span, .. } => span,
+        }
+    }
+
+    /// Converts the value to a token, choosing the types of static words with the resolution.
+    /// The default resolution gives back the tokens the values were created from.
+    pub fn to_token(&self, resolution: &Resolution) -> Token {
+        let to_tokens = |values: &[DecodedValue]| {
+            values
+                .iter()
+                .map(|value| value.to_token(resolution))
+                .collect()
+        };
+        match self {
+            DecodedValue::Word {
+                word, candidates, ..
+            } => {
+                let word_type = resolution.choose(candidates).unwrap_or(WordType::Uint);
+                word_to_token(word, word_type)
+            }
+            DecodedValue::Bytes { bytes, .. } => Token::Bytes(bytes.clone()),
+            DecodedValue::String { string, .. } => Token::String(string.clone()),
+            DecodedValue::Array { elements, .. } => Token::Array(to_tokens(elements)),
+            DecodedValue::FixedArray { elements, .. } => Token::FixedArray(to_tokens(elements)),
+            DecodedValue::Tuple { fields, .. } => Token::Tuple(to_tokens(fields)),
+        }
+    }
+}
+
+fn from_token(
+    token: &Token,
+    spans: &HashMap<Vec<usize>, Range<usize>>,
+    path: &mut Vec<usize>,
+) -> DecodedValue {
+    let span = spans.get(path.as_slice()).cloned().unwrap_or(0..0);
+    let mut from_tokens = |tokens: &[Token]| -> Vec<DecodedValue> {
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(index, token)| {
+                path.push(index);
+                let value = from_token(token, spans, path);
+                path.pop();
+                value
+            })
+            .collect()
+    };
+    match token {
+        Token::Bytes(bytes) => DecodedValue::Bytes {
+            bytes: bytes.clone(),
+            span,
+        },
+        Token::String(string) => DecodedValue::String {
+            string: string.clone(),
+            span,
+        },
+        Token::Array(tokens) => DecodedValue::Array {
+            elements: from_tokens(tokens),
+            span,
+        },
+        Token::FixedArray(tokens) => DecodedValue::FixedArray {
+            elements: from_tokens(tokens),
+            span,
+        },
+        Token::Tuple(tokens) => DecodedValue::Tuple {
+            fields: from_tokens(tokens),
+            span,
+        },
+        _ => {
+            let mut word = [0; 32];
+            word.copy_from_slice(&ethabi::encode(std::slice::from_ref(token)));
+            DecodedValue::Word {
+                candidates: get_candidates(&word, get_word_type(token)),
+                word,
+                span,
+            }
+        }
+    }
+}
+
+// Words of every value by its path, offsets are not part of the value they point to
+fn get_spans(tokens: &[Token], data: &[u8]) -> HashMap<Vec<usize>, Range<usize>> {
+    let mut spans: HashMap<Vec<usize>, Range<usize>> = HashMap::new();
+    for (index, annotation) in annotate_words(tokens, data).into_iter().enumerate() {
+        if matches!(annotation.role, WordRole::Offset { .. }) {
+            continue;
+        }
+        for length in 1..=annotation.path.len() {
+            spans
+                .entry(annotation.path[..length].to_vec())
+                .and_modify(|span| {
+                    span.start = span.start.min(index);
+                    span.end = span.end.max(index + 1);
+                })
+                .or_insert(index..index + 1);
+        }
+    }
+    spans
+}
+
+fn get_word_type(token: &Token) -> WordType {
+    match token {
+        Token::Int(_) => WordType::Int,
+        Token::Address(_) => WordType::Address,
+        Token::Bool(_) => WordType::Bool,
+        Token::FixedBytes(bytes) => WordType::FixedBytes(bytes.len()),
+        _ => WordType::Uint,
+    }
+}
+
+// The chosen type followed by every other type the word is a valid encoding of
+fn get_candidates(word: &[u8; 32], chosen: WordType) -> Vec<WordType> {
+    let is_zero = |bytes: &[u8]| bytes.iter().all(|byte| *byte == 0);
+    let bytes_length = word.iter().rposition(|byte| *byte != 0).unwrap_or(0) + 1;
+    let others = [
+        Some(WordType::Uint),
+        (word[0] >= 0x80).then_some(WordType::Int),
+        is_zero(&word[..12]).then_some(WordType::Address),
+        (is_zero(&word[..31]) && word[31] <= 1).then_some(WordType::Bool),
+        Some(WordType::FixedBytes(bytes_length)),
+    ];
+    let mut candidates = vec![chosen];
+    for candidate in others.into_iter().flatten() {
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+fn word_to_token(word: &[u8; 32], word_type: WordType) -> Token {
+    match word_type {
+        WordType::Uint => Token::Uint(U256::from_big_endian(word)),
+        WordType::Int => Token::Int(U256::from_big_endian(word)),
+        WordType::Address => Token::Address(Address::from_slice(&word[12..])),
+        WordType::Bool => Token::Bool(word[31] == 1),
+        WordType::FixedBytes(length) => Token::FixedBytes(word[..length].to_vec()),
+    }
+}
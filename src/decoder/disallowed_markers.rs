@@ -0,0 +1,159 @@
+use std::cell::OnceCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use super::parse_marker::MarkerType;
+
+/// Persistent set of marker types that must not be generated at the given word indices
+///
+/// The entries form a linked list in insertion order (latest first) whose nodes are shared
+/// between all sets derived from each other, so adding an entry takes constant time. Lookups,
+/// comparisons and hashing go through a view of the entries sorted by index, which a set builds
+/// the first time it needs it (starting from the view of the set it was derived from, if that
+/// one is built already) and shares with its clones. The view makes sets with the same entries
+/// equal however they were built, and the hashes of its prefixes make the entries below any index
+/// cheap to use as (part of) a memoization key.
+#[derive(Debug, Clone, Default)]
+pub struct DisallowedMarkers(Option<Rc<Entry>>);
+
+#[derive(Debug)]
+struct Entry {
+    index: usize,
+    marker_type: MarkerType,
+    next: DisallowedMarkers,
+    sorted: OnceCell<Rc<SortedMarkers>>,
+}
+
+// Entries sorted by index (the latest marker type of every index) and the hash of every prefix of
+// them, the first hash being the one of the empty prefix
+#[derive(Debug)]
+struct SortedMarkers {
+    entries: Vec<(usize, MarkerType)>,
+    prefix_hashes: Vec<u64>,
+}
+
+impl SortedMarkers {
+    fn new(entries: Vec<(usize, MarkerType)>) -> Self {
+        let mut prefix_hashes = Vec::with_capacity(entries.len() + 1);
+        prefix_hashes.push(0);
+        for entry in &entries {
+            let mut hasher = DefaultHasher::new();
+            prefix_hashes.last().hash(&mut hasher);
+            entry.hash(&mut hasher);
+            prefix_hashes.push(hasher.finish());
+        }
+        SortedMarkers {
+            entries,
+            prefix_hashes,
+        }
+    }
+}
+
+/// Entries of a set with an index below a given one
+#[derive(Debug, Clone)]
+pub struct MarkersBelow {
+    sorted: Rc<SortedMarkers>,
+    len: usize,
+}
+
+impl MarkersBelow {
+    fn entries(&self) -> &[(usize, MarkerType)] {
+        &self.sorted.entries[..self.len]
+    }
+}
+
+impl PartialEq for MarkersBelow {
+    fn eq(&self, other: &Self) -> bool {
+        self.sorted.prefix_hashes[self.len] == other.sorted.prefix_hashes[other.len]
+            && self.entries() == other.entries()
+    }
+}
+
+impl Eq for MarkersBelow {}
+
+impl Hash for MarkersBelow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sorted.prefix_hashes[self.len].hash(state);
+    }
+}
+
+impl PartialEq for DisallowedMarkers {
+    fn eq(&self, other: &Self) -> bool {
+        self.below(usize::MAX) == other.below(usize::MAX)
+    }
+}
+
+impl Eq for DisallowedMarkers {}
+
+impl Hash for DisallowedMarkers {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.below(usize::MAX).hash(state);
+    }
+}
+
+impl DisallowedMarkers {
+    pub fn get(&self, index: usize) -> Option<&MarkerType> {
+        let entry = self.0.as_ref()?;
+        let entries = &entry.sorted().entries;
+        entries
+            .binary_search_by_key(&index, |(entry_index, _)| *entry_index)
+            .ok()
+            .map(|position| &entries[position].1)
+    }
+
+    pub fn contains(&self, index: usize, marker_type: &MarkerType) -> bool {
+        self.get(index) == Some(marker_type)
+    }
+
+    /// Returns a set that additionally disallows the marker type at the index (replacing any
+    /// marker type previously disallowed there)
+    pub fn insert(&self, index: usize, marker_type: MarkerType) -> Self {
+        DisallowedMarkers(Some(Rc::new(Entry {
+            index,
+            marker_type,
+            next: self.clone(),
+            sorted: OnceCell::new(),
+        })))
+    }
+
+    /// Entries with an index below the given one
+    pub fn below(&self, index: usize) -> MarkersBelow {
+        let sorted = match &self.0 {
+            Some(entry) => entry.sorted().clone(),
+            None => Rc::new(SortedMarkers::new(Vec::new())),
+        };
+        let len = sorted.entries.partition_point(|(i, _)| *i < index);
+        MarkersBelow { sorted, len }
+    }
+}
+
+impl Entry {
+    fn sorted(&self) -> &Rc<SortedMarkers> {
+        self.sorted.get_or_init(|| {
+            // Entries added since the last set whose view is built, which are applied to a copy
+            // of that view from the oldest on
+            let mut pending = vec![(self.index, &self.marker_type)];
+            let mut current = &self.next;
+            let mut entries = loop {
+                match &current.0 {
+                    Some(entry) => match entry.sorted.get() {
+                        Some(sorted) => break sorted.entries.clone(),
+                        None => {
+                            pending.push((entry.index, &entry.marker_type));
+                            current = &entry.next;
+                        }
+                    },
+                    None => break Vec::new(),
+                }
+            };
+            for (index, marker_type) in pending.into_iter().rev() {
+                match entries.binary_search_by_key(&index, |(entry_index, _)| *entry_index) {
+                    Ok(position) => entries[position].1 = marker_type.clone(),
+                    Err(position) => entries.insert(position, (index, marker_type.clone())),
+                }
+            }
+            Rc::new(SortedMarkers::new(entries))
+        })
+    }
+}
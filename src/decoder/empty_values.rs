@@ -0,0 +1,116 @@
+use ethabi::token::Token;
+
+use super::parse_token::get_param_type;
+use super::type_hints::TypeHint;
+
+// Zero length values encode the same as empty bytes, strings and arrays of any type, so the data
+// alone cannot tell them apart. Elements of the same array have the same type though, which
+// settles the type of an empty value whenever a sibling has a non empty value in its place.
+
+/// Gives empty values inside arrays the type of the values of their siblings
+pub(crate) fn resolve_empty_values(tokens: Vec<Token>) -> Vec<Token> {
+    tokens.into_iter().map(resolve_token).collect()
+}
+
+/// Type of the token, unknown for empty values whose type is not settled by their siblings
+pub(crate) fn get_type_hint(token: &Token) -> TypeHint {
+    match token {
+        _ if is_empty_value(token) => TypeHint::Unknown,
+        Token::Array(elements) => TypeHint::array(get_type_hint(&merge_elements(elements))),
+        Token::FixedArray(elements) => {
+            TypeHint::fixed_array(get_type_hint(&merge_elements(elements)), elements.len())
+        }
+        Token::Tuple(tokens) => TypeHint::tuple(tokens.iter().map(get_type_hint).collect()),
+        _ => TypeHint::Type(get_param_type(token)),
+    }
+}
+
+/// Whether the token is an empty array, bytes or string
+pub(crate) fn is_empty_value(token: &Token) -> bool {
+    match token {
+        Token::Array(tokens) => tokens.is_empty(),
+        Token::Bytes(bytes) => bytes.is_empty(),
+        Token::String(string) => string.is_empty(),
+        _ => false,
+    }
+}
+
+fn resolve_token(token: Token) -> Token {
+    match token {
+        Token::Array(elements) => Token::Array(resolve_elements(elements)),
+        Token::FixedArray(elements) => Token::FixedArray(resolve_elements(elements)),
+        Token::Tuple(tokens) => Token::Tuple(tokens.into_iter().map(resolve_token).collect()),
+        _ => token,
+    }
+}
+
+fn resolve_elements(elements: Vec<Token>) -> Vec<Token> {
+    let elements: Vec<Token> = elements.into_iter().map(resolve_token).collect();
+    let template = merge_elements(&elements);
+    elements
+        .into_iter()
+        .map(|element| resolve_like(element, &template))
+        .collect()
+}
+
+// Gives the empty values of the token the type of the non empty values in the same place of the
+// template
+fn resolve_like(token: Token, template: &Token) -> Token {
+    match (token, template) {
+        (token, Token::Bytes(bytes)) if is_empty_value(&token) && !bytes.is_empty() => {
+            Token::Bytes(Vec::new())
+        }
+        (token, Token::String(string)) if is_empty_value(&token) && !string.is_empty() => {
+            Token::String(String::new())
+        }
+        (token, Token::Array(elements)) if is_empty_value(&token) && !elements.is_empty() => {
+            Token::Array(Vec::new())
+        }
+        (Token::Tuple(tokens), Token::Tuple(templates)) if tokens.len() == templates.len() => {
+            Token::Tuple(
+                tokens
+                    .into_iter()
+                    .zip(templates)
+                    .map(|(token, template)| resolve_like(token, template))
+                    .collect(),
+            )
+        }
+        (Token::Array(elements), Token::Array(templates)) => match templates.first() {
+            Some(template) => Token::Array(
+                elements
+                    .into_iter()
+                    .map(|element| resolve_like(element, template))
+                    .collect(),
+            ),
+            None => Token::Array(elements),
+        },
+        (token, _) => token,
+    }
+}
+
+// Single element combining the non empty values of all elements, empty only where all elements
+// are empty
+fn merge_elements(elements: &[Token]) -> Token {
+    elements
+        .iter()
+        .cloned()
+        .reduce(merge)
+        .unwrap_or(Token::Array(Vec::new()))
+}
+
+fn merge(a: Token, b: Token) -> Token {
+    match (a, b) {
+        (a, b) if is_empty_value(&a) => {
+            if is_empty_value(&b) {
+                a
+            } else {
+                b
+            }
+        }
+        (Token::Tuple(a), Token::Tuple(b)) if a.len() == b.len() => {
+            Token::Tuple(a.into_iter().zip(b).map(|(a, b)| merge(a, b)).collect())
+        }
+        (Token::Array(a), Token::Array(b)) => Token::Array(vec![merge_elements(&[a, b].concat())]),
+        (a, _) => a,
+    }
+}
@@ -0,0 +1,150 @@
+use super::validation::EncodingDeviation;
+
+/// Reasons why calldata could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodingError {
+    /// Strict mode is enabled and the data is not the canonical encoding of the decoded values
+    NonCanonical(Vec<EncodingDeviation>),
+    /// None of the layouts found for the data is consistent with the type hints and pins in the
+    /// options
+    NoMatchingLayout,
+    /// Pins in the options for the word (counting from 0) contradict each other
+    ConflictingPins(usize),
+    /// Position (counting from 0) of the first character of the arguments that is not a hex digit
+    InvalidHex(usize),
+}
+
+impl std::fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodingError::NonCanonical(deviations) => {
+                write!(f, "Data is not canonically encoded:")?;
+                for deviation in deviations {
+                    write!(f, "\n{}", deviation)?;
+                }
+                Ok(())
+            }
+            DecodingError::NoMatchingLayout => {
+                write!(f, "No decoding is consistent with the type hints and pins")
+            }
+            DecodingError::ConflictingPins(word) => {
+                write!(f, "Conflicting pins for word {}", word)
+            }
+            DecodingError::InvalidHex(position) => {
+                write!(f, "Invalid hex character at position {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+/// Reasons why a corpus or a priors file could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriorsError {
+    /// Line (counting from 1) that is not in the expected format
+    InvalidLine(usize),
+    /// Line (counting from 1) whose calldata is not an encoding of the arguments of its signature
+    InvalidSample(usize),
+}
+
+impl std::fmt::Display for PriorsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriorsError::InvalidLine(line) => write!(f, "Line {}: invalid format", line),
+            PriorsError::InvalidSample(line) => {
+                write!(f, "Line {}: calldata does not match the signature", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PriorsError {}
+
+/// Reasons why a type hint template could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeHintsError {
+    /// Position (in the template without whitespace) of a character that was not expected
+    Syntax(usize),
+    /// Name that is not a type
+    UnknownType(String),
+}
+
+impl std::fmt::Display for TypeHintsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeHintsError::Syntax(position) => {
+                write!(f, "Unexpected character at position {}", position)
+            }
+            TypeHintsError::UnknownType(name) => write!(f, "Unknown type {}", name),
+        }
+    }
+}
+
+impl std::error::Error for TypeHintsError {}
+
+/// Reasons why a pin could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinError {
+    /// Pin that is not of the form `<word>=<role>` or `<word>!=<role>`
+    Syntax(String),
+    /// Name that is not a role
+    UnknownRole(String),
+}
+
+impl std::fmt::Display for PinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PinError::Syntax(pin) => write!(f, "Invalid pin {}", pin),
+            PinError::UnknownRole(role) => write!(
+                f,
+                "Unknown role {} (expected value, offset, bytes-length or array-length)",
+                role
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PinError {}
+
+/// Reasons why a query could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// Position (in the query without whitespace) of a character that was not expected
+    Syntax(usize),
+    /// Type filter that is not a single type
+    InvalidType(String),
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Syntax(position) => {
+                write!(f, "Unexpected character at position {}", position)
+            }
+            QueryError::InvalidType(name) => write!(f, "Invalid type {}", name),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Reasons why a signature could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    /// Signature that is not of the form `<name>(<types>)`
+    Syntax(String),
+    /// Name that is not a type
+    UnknownType(String),
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Syntax(signature) => write!(f, "Invalid signature {}", signature),
+            SignatureError::UnknownType(name) => write!(f, "Unknown type {}", name),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
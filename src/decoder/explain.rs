@@ -0,0 +1,362 @@
+use std::fmt;
+
+use ethabi::token::Token;
+use ethabi::ParamType;
+use serde_json::{json, Value};
+
+use super::annotation::{annotate_words, get_token, WordAnnotation, WordRole};
+use super::parse_marker::{MarkerType, ParseMarker};
+use super::parse_token::get_param_type;
+use super::word::{words_to_bytes, Word};
+
+/// Rule that classified a word
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /// Offset pointing to the start of a tail (`get_dynamic_offset_marker`)
+    Offset {
+        /// Index of the word the offset points to
+        target: usize,
+    },
+    /// Length of a byte string (`get_dynamic_bytes_marker`)
+    BytesLength {
+        /// Number of bytes
+        bytes: usize,
+    },
+    /// Content of a byte string
+    BytesContent {
+        /// Index of the word containing the length of the byte string
+        length_word: usize,
+    },
+    /// Length of an array of static elements (`get_array_marker_static`)
+    StaticArrayLength {
+        /// Number of elements
+        elements: usize,
+        /// Number of words of each element
+        element_size: usize,
+    },
+    /// Length of an array of values behind one offset each (`get_array_marker_dynamic`)
+    DynamicArrayLength {
+        /// Number of elements
+        elements: usize,
+    },
+    /// Static value of the given type (`tokenize_argument`)
+    Value(ParamType),
+    /// Word between the tails of a non canonical encoding that is not part of any value
+    Padding,
+}
+
+/// Kind of interpretation of a word that was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    /// Offset pointing to a tail
+    Offset,
+    /// Length of a byte string
+    BytesLength,
+    /// Length of an array of static elements
+    StaticArrayLength,
+    /// Length of an array of values behind offsets
+    DynamicArrayLength,
+}
+
+/// Why an interpretation of a word was rejected (and disallowed for the rest of the search)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The value the interpretation implies could not be parsed
+    Unparsable,
+    /// The offset points to a tuple without dynamic values, which would be encoded in place
+    StaticTuple,
+}
+
+/// Interpretation of a word that was tried and rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rejection {
+    /// What the word was interpreted as
+    pub kind: RuleKind,
+    /// Why the interpretation was rejected
+    pub reason: RejectionReason,
+}
+
+/// Everything the decoder tried for one word
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WordTrace {
+    /// Rule of the returned decoding, `None` for trailing data after the encoded arguments
+    pub rule: Option<Rule>,
+    /// Every rule that classified the word in the order the search first tried them
+    pub classifications: Vec<Rule>,
+    /// Every interpretation that was rejected in the order the search first rejected them
+    pub rejections: Vec<Rejection>,
+}
+
+/// Trace of why each word was interpreted the way it was
+///
+/// Only the greedy search records the classifications and rejections, for other engines only the
+/// rules of the returned decoding are known.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Explanation {
+    /// Trace of every word of the data
+    pub words: Vec<WordTrace>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum TraceEvent {
+    Classified(usize, Rule),
+    Rejected(usize, Rejection),
+}
+
+impl Explanation {
+    pub(crate) fn new(words: &[Word], events: Vec<TraceEvent>, tokens: &[Token]) -> Self {
+        let mut traces = vec![WordTrace::default(); words.len()];
+        for event in events {
+            match event {
+                // Markers are generated again after every rejection and retries of the same
+                // region reject the same interpretations, so each one is only listed once
+                TraceEvent::Classified(index, rule) => {
+                    let classifications = &mut traces[index].classifications;
+                    if !classifications.contains(&rule) {
+                        classifications.push(rule);
+                    }
+                }
+                TraceEvent::Rejected(index, rejection) => {
+                    let rejections = &mut traces[index].rejections;
+                    if !rejections.contains(&rejection) {
+                        rejections.push(rejection);
+                    }
+                }
+            }
+        }
+        let annotations = annotate_words(tokens, &words_to_bytes(words));
+        for (trace, annotation) in traces.iter_mut().zip(&annotations) {
+            trace.rule = get_rule(annotation, tokens);
+        }
+        Explanation { words: traces }
+    }
+
+    /// Renders the explanation as JSON
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.words
+                .iter()
+                .enumerate()
+                .map(|(index, trace)| {
+                    json!({
+                        "word": index,
+                        "rule": trace.rule.as_ref().map(rule_to_json),
+                        "classifications": trace.classifications.iter().map(rule_to_json).collect::<Vec<_>>(),
+                        "rejections": trace.rejections.iter().map(|rejection| json!({
+                            "kind": format!("{:?}", rejection.kind),
+                            "reason": format!("{:?}", rejection.reason),
+                        })).collect::<Vec<_>>(),
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Offset { target } => write!(f, "offset to word {}", target),
+            Rule::BytesLength { bytes } => write!(f, "length of {} bytes", bytes),
+            Rule::BytesContent { length_word } => {
+                write!(
+                    f,
+                    "content of the bytes with length in word {}",
+                    length_word
+                )
+            }
+            Rule::StaticArrayLength {
+                elements,
+                element_size,
+            } => write!(
+                f,
+                "length of an array of {} static elements of {} words",
+                elements, element_size
+            ),
+            Rule::DynamicArrayLength { elements } => {
+                write!(f, "length of an array of {} dynamic elements", elements)
+            }
+            Rule::Value(param_type) => write!(f, "{} value", param_type),
+            Rule::Padding => write!(f, "padding between tails"),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            RuleKind::Offset => "offset",
+            RuleKind::BytesLength => "bytes length",
+            RuleKind::StaticArrayLength => "static array length",
+            RuleKind::DynamicArrayLength => "dynamic array length",
+        };
+        let reason = match self.reason {
+            RejectionReason::Unparsable => "the value it implies could not be parsed",
+            RejectionReason::StaticTuple => "it points to a tuple without dynamic values",
+        };
+        write!(f, "rejected {} because {}", kind, reason)
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, trace) in self.words.iter().enumerate() {
+            match &trace.rule {
+                Some(rule) => writeln!(f, "Word {}: {}", index, rule)?,
+                None => writeln!(f, "Word {}: trailing data", index)?,
+            }
+            for rule in &trace.classifications {
+                if Some(rule) != trace.rule.as_ref() {
+                    writeln!(f, "    tried {}", rule)?;
+                }
+            }
+            for rejection in &trace.rejections {
+                writeln!(f, "    {}", rejection)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rule_to_json(rule: &Rule) -> Value {
+    match rule {
+        Rule::Offset { target } => json!({ "offset": { "target": target } }),
+        Rule::BytesLength { bytes } => json!({ "bytes_length": { "bytes": bytes } }),
+        Rule::BytesContent { length_word } => {
+            json!({ "bytes_content": { "length_word": length_word } })
+        }
+        Rule::StaticArrayLength {
+            elements,
+            element_size,
+        } => json!({
+            "static_array_length": { "elements": elements, "element_size": element_size }
+        }),
+        Rule::DynamicArrayLength { elements } => {
+            json!({ "dynamic_array_length": { "elements": elements } })
+        }
+        Rule::Value(param_type) => json!({ "value": param_type.to_string() }),
+        Rule::Padding => json!({ "padding": {} }),
+    }
+}
+
+/// Rules of the words classified by the marker, whose indices are relative to the words starting
+/// at the base
+pub(crate) fn classify_marker(
+    parse_marker: &ParseMarker,
+    base: usize,
+    static_value: impl Fn(usize) -> Token,
+) -> Vec<(usize, Rule)> {
+    match parse_marker {
+        ParseMarker::Word(i) => vec![(
+            base + i,
+            Rule::Value(get_param_type(&static_value(base + i))),
+        )],
+        ParseMarker::DynamicOffset(i, location) => vec![(
+            base + i,
+            Rule::Offset {
+                target: base + location.start,
+            },
+        )],
+        ParseMarker::DynamicBytes(padding, location) => {
+            let length_word = base + location.start - 1;
+            let mut rules = vec![(
+                length_word,
+                Rule::BytesLength {
+                    bytes: (location.end - location.start) * 32 - padding,
+                },
+            )];
+            for index in location.start..location.end {
+                rules.push((base + index, Rule::BytesContent { length_word }));
+            }
+            rules
+        }
+        ParseMarker::StaticArray(element_size, location) => vec![(
+            base + location.start - 1,
+            Rule::StaticArrayLength {
+                elements: (location.end - location.start)
+                    .checked_div(*element_size)
+                    .unwrap_or(0),
+                element_size: *element_size,
+            },
+        )],
+        // Arrays are only generated for the first word of a tail
+        ParseMarker::DynamicArray(_, locations) => vec![(
+            base,
+            Rule::DynamicArrayLength {
+                elements: locations.len(),
+            },
+        )],
+        ParseMarker::Tuple(..) | ParseMarker::TopLevel => Vec::new(),
+    }
+}
+
+/// Index of the word a rejected marker classified
+pub(crate) fn get_rejected_word(parse_marker: &ParseMarker) -> usize {
+    match parse_marker {
+        ParseMarker::DynamicArray(..) => 0,
+        ParseMarker::DynamicOffset(i, _) => *i,
+        ParseMarker::DynamicBytes(_, location)
+        | ParseMarker::StaticArray(_, location)
+        | ParseMarker::Tuple(location) => location.start - 1,
+        ParseMarker::Word(i) => *i,
+        ParseMarker::TopLevel => panic!("TopLevel marker cannot be rejected"),
+    }
+}
+
+pub(crate) fn get_marker_kind(parse_marker: &ParseMarker) -> RuleKind {
+    match parse_marker {
+        ParseMarker::DynamicOffset(..) | ParseMarker::Tuple(..) => RuleKind::Offset,
+        ParseMarker::DynamicBytes(..) => RuleKind::BytesLength,
+        ParseMarker::StaticArray(..) => RuleKind::StaticArrayLength,
+        ParseMarker::DynamicArray(..) => RuleKind::DynamicArrayLength,
+        _ => panic!("Cannot reject {:?}", parse_marker),
+    }
+}
+
+pub(crate) fn get_rule_kind(marker_type: &MarkerType) -> RuleKind {
+    match marker_type {
+        MarkerType::Tuple => RuleKind::Offset,
+        MarkerType::DynamicBytes => RuleKind::BytesLength,
+        MarkerType::Array => RuleKind::StaticArrayLength,
+        MarkerType::DynamicArray => RuleKind::DynamicArrayLength,
+    }
+}
+
+// Rule of a word of the returned decoding
+fn get_rule(annotation: &WordAnnotation, tokens: &[Token]) -> Option<Rule> {
+    let token = get_token(tokens, &annotation.path);
+    match (&annotation.role, token) {
+        (WordRole::HeadValue, Some(token)) => Some(Rule::Value(get_param_type(token))),
+        (WordRole::Offset { target }, _) => Some(Rule::Offset { target: *target }),
+        (WordRole::Length { .. }, Some(Token::Bytes(bytes))) => {
+            Some(Rule::BytesLength { bytes: bytes.len() })
+        }
+        (WordRole::Length { .. }, Some(Token::String(string))) => Some(Rule::BytesLength {
+            bytes: string.len(),
+        }),
+        (WordRole::Length { .. }, Some(Token::Array(elements))) => match elements.first() {
+            Some(element) if !element.is_dynamic() => Some(Rule::StaticArrayLength {
+                elements: elements.len(),
+                element_size: get_static_size(element),
+            }),
+            _ => Some(Rule::DynamicArrayLength {
+                elements: elements.len(),
+            }),
+        },
+        (WordRole::BytesData { length_word, .. }, _) => Some(Rule::BytesContent {
+            length_word: *length_word,
+        }),
+        (WordRole::Padding, _) => Some(Rule::Padding),
+        _ => None,
+    }
+}
+
+// Number of words a static value occupies
+pub(crate) fn get_static_size(token: &Token) -> usize {
+    match token {
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            tokens.iter().map(get_static_size).sum()
+        }
+        _ => 1,
+    }
+}
@@ -0,0 +1,202 @@
+use ethabi::param_type::ParamType;
+use ethabi::token::Token;
+
+use super::empty_values::get_type_hint;
+use super::type_hints::TypeHint;
+
+// Fixed size arrays have no length word, so they encode the same as tuples of their elements: a
+// static one in place and one of dynamic elements as a head of offsets followed by their tails.
+// The decoder only groups words into tuples where the data requires a group (the elements of
+// arrays and the values behind offsets), so a group that consists of repetitions of the same
+// types may as well be a fixed size array. Structs with fields of the same type are as common
+// though, so this is offered as another reading of the decoded tokens rather than replacing
+// their tuples. The elements of an array have the same type, so their tuples are either all
+// fixed size arrays of the same shape or all stay tuples.
+
+/// Replaces the tuples whose fields repeat the same types with fixed size arrays
+pub(crate) fn infer_fixed_arrays(tokens: Vec<Token>) -> Vec<Token> {
+    tokens
+        .into_iter()
+        .flat_map(|token| infer_siblings(vec![token]))
+        .collect()
+}
+
+// Infers the fixed size arrays of values that have to have the same type
+fn infer_siblings(tokens: Vec<Token>) -> Vec<Token> {
+    if tokens.is_empty() {
+        return tokens;
+    }
+    if let Some(Token::Tuple(first)) = tokens.first() {
+        let length = first.len();
+        if tokens
+            .iter()
+            .all(|token| matches!(token, Token::Tuple(fields) if fields.len() == length))
+        {
+            let tuples = tokens
+                .into_iter()
+                .map(|token| match token {
+                    Token::Tuple(fields) => fields,
+                    _ => unreachable!(),
+                })
+                .collect();
+            return infer_tuples(tuples, length);
+        }
+    }
+    if tokens
+        .iter()
+        .all(|token| matches!(token, Token::Array(_) | Token::FixedArray(_)))
+    {
+        return infer_arrays(tokens);
+    }
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            Token::Tuple(_) | Token::Array(_) | Token::FixedArray(_) => infer_siblings(vec![token]),
+            _ => vec![token],
+        })
+        .collect()
+}
+
+// The fields at the same position of all tuples are siblings
+fn infer_tuples(tuples: Vec<Vec<Token>>, length: usize) -> Vec<Token> {
+    let count = tuples.len();
+    let mut columns: Vec<Vec<Token>> = vec![Vec::with_capacity(count); length];
+    for fields in tuples {
+        for (column, field) in columns.iter_mut().zip(fields) {
+            column.push(field);
+        }
+    }
+    let mut columns: Vec<_> = columns
+        .into_iter()
+        .map(|column| infer_siblings(column).into_iter())
+        .collect();
+    let tuples: Vec<Vec<Token>> = (0..count)
+        .map(|_| {
+            columns
+                .iter_mut()
+                .map(|column| column.next().expect("Columns have a field of every tuple"))
+                .collect()
+        })
+        .collect();
+    match get_element_size(&tuples) {
+        Some(element_size) => tuples
+            .into_iter()
+            .map(|fields| to_fixed_array(fields, element_size))
+            .collect(),
+        None => tuples.into_iter().map(Token::Tuple).collect(),
+    }
+}
+
+// The elements of all arrays are siblings
+fn infer_arrays(arrays: Vec<Token>) -> Vec<Token> {
+    let mut lengths = Vec::with_capacity(arrays.len());
+    let mut elements = Vec::new();
+    for array in &arrays {
+        if let Token::Array(tokens) | Token::FixedArray(tokens) = array {
+            lengths.push(tokens.len());
+            elements.extend(tokens.iter().cloned());
+        }
+    }
+    let mut elements = infer_siblings(elements).into_iter();
+    arrays
+        .iter()
+        .zip(lengths)
+        .map(|(array, length)| {
+            let tokens = elements.by_ref().take(length).collect();
+            match array {
+                Token::FixedArray(_) => Token::FixedArray(tokens),
+                _ => Token::Array(tokens),
+            }
+        })
+        .collect()
+}
+
+fn to_fixed_array(fields: Vec<Token>, element_size: usize) -> Token {
+    if element_size == 1 {
+        return Token::FixedArray(fields);
+    }
+    Token::FixedArray(
+        fields
+            .chunks(element_size)
+            .map(|element| Token::Tuple(element.to_vec()))
+            .collect(),
+    )
+}
+
+// Smallest number of fields that repeat with the same types in all tuples, if the fields contain
+// at least two repetitions. Elements of several fields are encoded in place only if they are
+// static, as dynamic ones would need offsets of their own.
+fn get_element_size(tuples: &[Vec<Token>]) -> Option<usize> {
+    let length = tuples.first()?.len();
+    let hints: Vec<Vec<TypeHint>> = tuples
+        .iter()
+        .map(|fields| fields.iter().map(get_type_hint).collect())
+        .collect();
+    let is_static = tuples.iter().flatten().all(|field| !field.is_dynamic());
+    (1..=length / 2)
+        .filter(|element_size| length.is_multiple_of(*element_size))
+        .find(|element_size| (*element_size == 1 || is_static) && repeats(&hints, *element_size))
+}
+
+fn repeats(hints: &[Vec<TypeHint>], element_size: usize) -> bool {
+    let mut chunks = hints.iter().flat_map(|hints| hints.chunks(element_size));
+    let first = chunks
+        .next()
+        .expect("Tuples contain at least one element")
+        .to_vec();
+    chunks
+        .try_fold(first, |merged, chunk| {
+            merged
+                .iter()
+                .zip(chunk)
+                .map(|(a, b)| unify(a, b))
+                .collect::<Option<Vec<_>>>()
+        })
+        .is_some()
+}
+
+// Type that values of both types have. Unknown types are those of empty values, which can be
+// any dynamic type.
+fn unify(a: &TypeHint, b: &TypeHint) -> Option<TypeHint> {
+    match (expand(a), expand(b)) {
+        (TypeHint::Unknown, hint) | (hint, TypeHint::Unknown) => is_dynamic(&hint).then_some(hint),
+        (TypeHint::Array(a), TypeHint::Array(b)) => Some(TypeHint::array(unify(&a, &b)?)),
+        (TypeHint::FixedArray(a, a_length), TypeHint::FixedArray(b, b_length))
+            if a_length == b_length =>
+        {
+            Some(TypeHint::fixed_array(unify(&a, &b)?, a_length))
+        }
+        (TypeHint::Tuple(a), TypeHint::Tuple(b)) if a.len() == b.len() => Some(TypeHint::tuple(
+            a.iter()
+                .zip(&b)
+                .map(|(a, b)| unify(a, b))
+                .collect::<Option<_>>()?,
+        )),
+        (a, b) => (a == b).then_some(a),
+    }
+}
+
+fn is_dynamic(hint: &TypeHint) -> bool {
+    match hint {
+        TypeHint::Unknown | TypeHint::Array(_) => true,
+        TypeHint::Type(param_type) => param_type.is_dynamic(),
+        TypeHint::FixedArray(element, _) => is_dynamic(element),
+        TypeHint::Tuple(elements) => elements.iter().any(is_dynamic),
+    }
+}
+
+// Hint of a known array or tuple type as the hints of its elements
+pub(crate) fn expand(hint: &TypeHint) -> TypeHint {
+    match hint {
+        TypeHint::Type(ParamType::Array(element)) => {
+            TypeHint::Array(Box::new(TypeHint::Type((**element).clone())))
+        }
+        TypeHint::Type(ParamType::FixedArray(element, length)) => {
+            TypeHint::FixedArray(Box::new(TypeHint::Type((**element).clone())), *length)
+        }
+        TypeHint::Type(ParamType::Tuple(param_types)) => {
+            TypeHint::Tuple(param_types.iter().cloned().map(TypeHint::Type).collect())
+        }
+        hint => hint.clone(),
+    }
+}
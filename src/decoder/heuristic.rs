@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use ethabi::token::Token;
+use ethabi::Address;
+
+use super::annotation::{annotate_words, WordRole};
+use super::word::Word;
+
+/// Scores how plausible a candidate decoding is, higher scores are more plausible and zero is
+/// neutral
+///
+/// Scores of all registered heuristics are added up. For every static word the candidate value
+/// with the highest score is chosen (ties go to the number) and the layouts an engine finds are
+/// ranked by their score, keeping the order of the engine for equal scores.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::{DecodingOptions, Heuristic, Heuristics};
+/// use ethabi::{Address, Token};
+///
+/// // Decode words as addresses whenever they are in the address book
+/// #[derive(Debug)]
+/// struct AddressBook(Vec<Address>);
+///
+/// impl Heuristic for AddressBook {
+///     fn score_word(&self, _word: &[u8; 32], candidate: &Token) -> i64 {
+///         match candidate {
+///             Token::Address(address) if self.0.contains(address) => 10,
+///             _ => 0,
+///         }
+///     }
+/// }
+///
+/// // The address starts with a zero byte, which would otherwise make it a number
+/// let address = "000000000000000000000000000a0b0c0d0e0f000000000000000000000000ff";
+/// let options = DecodingOptions {
+///     heuristics: Heuristics::default().with(AddressBook(vec![address[24..].parse().unwrap()])),
+///     ..Default::default()
+/// };
+/// let result = abi_decoder::decode_arguments_with_options(address, &options).unwrap();
+/// assert_eq!(result.tokens, vec![Token::Address(address[24..].parse().unwrap())]);
+/// ```
+pub trait Heuristic: Debug + Send + Sync {
+    /// Score of decoding the static word as the candidate value (a number, an address or left
+    /// aligned bytes without the trailing zeros)
+    fn score_word(&self, _word: &[u8; 32], _candidate: &Token) -> i64 {
+        0
+    }
+
+    /// Score of a complete layout of the arguments
+    fn score_layout(&self, _tokens: &[Token]) -> i64 {
+        0
+    }
+
+    /// Score of a layout that leaves the number of words of the data out of its values (the gaps
+    /// after byte strings that end before their tail does in non canonical encodings and the
+    /// words after the last tail)
+    fn score_gaps(&self, _gap_words: usize) -> i64 {
+        0
+    }
+}
+
+/// Values that start with a non zero byte are left aligned (i.e. fixed size bytes)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LeftAlignedBytes;
+
+impl Heuristic for LeftAlignedBytes {
+    fn score_word(&self, word: &[u8; 32], candidate: &Token) -> i64 {
+        // TODO: Maybe change default to still use bytes when only one leading zero ?
+        match candidate {
+            Token::FixedBytes(_) if word[0] >= 0x10 => 2,
+            Token::FixedBytes(_) => -1,
+            _ => 0,
+        }
+    }
+}
+
+/// Values that fit into 20 bytes and do not start with a zero byte are addresses
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Addresses;
+
+impl Heuristic for Addresses {
+    fn score_word(&self, word: &[u8; 32], candidate: &Token) -> i64 {
+        match candidate {
+            Token::Address(_) if word[12] >= 0x10 => 1,
+            Token::Address(_) => -1,
+            _ => 0,
+        }
+    }
+}
+
+/// Known addresses with their labels, words that are one of them are decoded as addresses
+///
+/// Clones share the addresses, so the book is cheap to clone until a clone is changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressBook(Arc<HashMap<Address, String>>);
+
+impl AddressBook {
+    /// Adds the address with its label (i.e. the name of the contract), replacing any previous
+    /// label of the address
+    pub fn insert(&mut self, address: Address, label: &str) {
+        Arc::make_mut(&mut self.0).insert(address, label.to_string());
+    }
+
+    /// Label of the address, if it is in the book
+    pub fn label(&self, address: &Address) -> Option<&str> {
+        self.0.get(address).map(String::as_str)
+    }
+}
+
+impl Heuristic for AddressBook {
+    fn score_word(&self, _word: &[u8; 32], candidate: &Token) -> i64 {
+        // Outweighs the other heuristics, which only guess from the shape of the word
+        match candidate {
+            Token::Address(address) if self.0.contains_key(address) => 10,
+            _ => 0,
+        }
+    }
+}
+
+/// Zero length values are empty arrays rather than empty bytes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmptyArrays;
+
+impl Heuristic for EmptyArrays {
+    fn score_layout(&self, tokens: &[Token]) -> i64 {
+        -(tokens.iter().map(count_empty_bytes).sum::<usize>() as i64)
+    }
+}
+
+fn count_empty_bytes(token: &Token) -> usize {
+    match token {
+        Token::Bytes(bytes) => usize::from(bytes.is_empty()),
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            tokens.iter().map(count_empty_bytes).sum()
+        }
+        _ => 0,
+    }
+}
+
+/// Words of the data belong to the values, so layouts with gaps come after those without
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gaps;
+
+impl Heuristic for Gaps {
+    fn score_gaps(&self, gap_words: usize) -> i64 {
+        -(gap_words as i64)
+    }
+}
+
+/// Heuristics used to choose between candidate decodings
+///
+/// The default set ([`LeftAlignedBytes`], [`Addresses`], [`EmptyArrays`] and [`Gaps`])
+/// reproduces the choices of the decoder without any registered heuristics of its own.
+#[derive(Debug, Clone)]
+pub struct Heuristics(Vec<Arc<dyn Heuristic>>);
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Heuristics(vec![
+            Arc::new(LeftAlignedBytes),
+            Arc::new(Addresses),
+            Arc::new(EmptyArrays),
+            Arc::new(Gaps),
+        ])
+    }
+}
+
+impl Heuristics {
+    /// Set without any heuristics, which decodes every static word as a number and keeps the
+    /// order of the layouts
+    pub fn empty() -> Self {
+        Heuristics(Vec::new())
+    }
+
+    /// Adds the heuristic to the set
+    pub fn with(mut self, heuristic: impl Heuristic + 'static) -> Self {
+        self.push(heuristic);
+        self
+    }
+
+    /// Adds the heuristic to the set
+    pub fn push(&mut self, heuristic: impl Heuristic + 'static) {
+        self.0.push(Arc::new(heuristic));
+    }
+
+    /// Decodes the static word as the candidate value with the highest score
+    pub(crate) fn tokenize_word(&self, word: &Word) -> Token {
+        let bytes = &word.bytes;
+        let bytes_len = bytes.iter().rposition(|byte| *byte != 0).unwrap_or(0) + 1;
+        let candidates = [
+            Some(Token::Uint(word.as_u256())),
+            bytes[..12]
+                .iter()
+                .all(|byte| *byte == 0)
+                .then(|| Token::Address(Address::from_slice(&bytes[12..]))),
+            Some(Token::FixedBytes(bytes[..bytes_len].to_vec())),
+        ];
+
+        let mut best: Option<(i64, Token)> = None;
+        for candidate in candidates.into_iter().flatten() {
+            let score = self
+                .0
+                .iter()
+                .map(|heuristic| heuristic.score_word(bytes, &candidate))
+                .sum();
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, _)| score > *best_score)
+            {
+                best = Some((score, candidate));
+            }
+        }
+        best.expect("Every word has a candidate").1
+    }
+
+    /// Score of a complete layout of the arguments
+    pub fn score_layout(&self, tokens: &[Token]) -> i64 {
+        self.0
+            .iter()
+            .map(|heuristic| heuristic.score_layout(tokens))
+            .sum()
+    }
+
+    /// Score of a complete layout of the arguments encoded in the data, including its gaps
+    pub fn score_encoding(&self, tokens: &[Token], data: &[u8]) -> i64 {
+        let gap_words = annotate_words(tokens, data)
+            .iter()
+            .filter(|annotation| matches!(annotation.role, WordRole::Padding | WordRole::Trailing))
+            .count();
+        self.score_layout(tokens)
+            + self
+                .0
+                .iter()
+                .map(|heuristic| heuristic.score_gaps(gap_words))
+                .sum::<i64>()
+    }
+
+    /// Sorts the layouts of the data by their score, best first, keeping the order of layouts with
+    /// the same score
+    pub(crate) fn rank<T>(&self, layouts: &mut [T], data: &[u8], tokens: impl Fn(&T) -> &[Token]) {
+        if self.0.is_empty() || layouts.len() < 2 {
+            return;
+        }
+        layouts.sort_by_cached_key(|layout| {
+            std::cmp::Reverse(self.score_encoding(tokens(layout), data))
+        });
+    }
+}
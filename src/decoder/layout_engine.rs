@@ -0,0 +1,117 @@
+use ethabi::token::Token;
+
+use super::disallowed_markers::DisallowedMarkers;
+use super::empty_values::is_empty_value;
+use super::explain::get_static_size;
+use super::options::DecodingOptions;
+use super::parse_context::{Budget, BytesFirst, ParseContext};
+use super::parse_marker::MarkerType;
+use super::parse_token::remove_offset_tuples;
+use super::word::Word;
+use super::{is_canonical_encoding, parse_arguments};
+
+/// Strategy used to find the layout (i.e. the types and positions of all values) of encoded data
+pub trait LayoutEngine {
+    /// Decodes the words into the arguments of every layout the engine finds, best first, each
+    /// together with whether some values were left undecoded because a limit in the options was
+    /// reached
+    fn decode_layouts(
+        &self,
+        words: &[Word],
+        options: &DecodingOptions,
+        budget: &mut Budget,
+    ) -> Vec<(Vec<Token>, bool)>;
+}
+
+/// Greedy search over the markers generated for each word, which backtracks by disallowing the
+/// markers of values that could not be parsed
+///
+/// Finds one layout and is the engine used by default. If that layout does not re-encode to the
+/// words, the search is run again with each of the offsets of the arguments read as a value
+/// instead, and (for non canonical encodings) with lengths read as the lengths of bytes followed
+/// by a gap before arrays, which gives the other layouts. If the layout has empty arrays, the
+/// search is also run with zero lengths read as empty bytes. Tuples with a single element that
+/// are part of the encoding (i.e. have an offset of their own) are kept in the layout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedySearch;
+
+impl LayoutEngine for GreedySearch {
+    fn decode_layouts(
+        &self,
+        words: &[Word],
+        options: &DecodingOptions,
+        budget: &mut Budget,
+    ) -> Vec<(Vec<Token>, bool)> {
+        let mut context = ParseContext::new(options, words, budget);
+        let decode = |disallowed_markers: &DisallowedMarkers, context: &mut ParseContext| {
+            parse_arguments(words, disallowed_markers, context)
+                .map(|tokens| tokens.into_iter().map(remove_offset_tuples).collect())
+        };
+        let Some(tokens) = decode(&DisallowedMarkers::default(), &mut context) else {
+            return Vec::new();
+        };
+        let mut layouts: Vec<Vec<Token>> = vec![tokens];
+        // Undecoded values never re-encode to the words, and reading more words as values does
+        // not decode them either
+        if !context.is_partial() && !is_canonical_encoding(&layouts[0], words) {
+            for index in get_argument_offsets(&layouts[0]) {
+                let disallowed_markers =
+                    DisallowedMarkers::default().insert(index, MarkerType::Tuple);
+                if let Some(tokens) = decode(&disallowed_markers, &mut context) {
+                    if !layouts.contains(&tokens) {
+                        layouts.push(tokens);
+                    }
+                }
+            }
+            // Bytes followed by a gap are only read if no array fits, so the layout with them is
+            // searched separately for the heuristics to rank
+            if options.allow_non_canonical {
+                context.set_bytes_first(BytesFirst::Gaps);
+                if let Some(tokens) = decode(&DisallowedMarkers::default(), &mut context) {
+                    if !layouts.contains(&tokens) {
+                        layouts.push(tokens);
+                    }
+                }
+            }
+        }
+        // Zero lengths are read as empty arrays, which encode the same as empty bytes, so the
+        // layout with empty bytes in their place is searched separately for the heuristics to rank
+        if !context.is_partial() && layouts[0].iter().any(contains_empty_array) {
+            context.set_bytes_first(BytesFirst::Empty);
+            if let Some(tokens) = decode(&DisallowedMarkers::default(), &mut context) {
+                if !layouts.contains(&tokens) {
+                    layouts.push(tokens);
+                }
+            }
+        }
+        let partial = context.is_partial();
+        layouts
+            .into_iter()
+            .map(|tokens| (tokens, partial))
+            .collect()
+    }
+}
+
+fn contains_empty_array(token: &Token) -> bool {
+    match token {
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            is_empty_value(token) || tokens.iter().any(contains_empty_array)
+        }
+        _ => false,
+    }
+}
+
+// Indices of the words holding the offsets of the dynamic arguments
+fn get_argument_offsets(tokens: &[Token]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut head = 0;
+    for token in tokens {
+        if token.is_dynamic() {
+            offsets.push(head);
+            head += 1;
+        } else {
+            head += get_static_size(token);
+        }
+    }
+    offsets
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use super::heuristic::Heuristics;
+use super::pins::Pin;
+use super::type_hints::TypeHints;
+
+/// Options that change which encodings the decoder accepts
+#[derive(Debug, Clone, Default)]
+pub struct DecodingOptions {
+    /// Accept offsets that point to tails out of order or to the same tail as a previous offset
+    ///
+    /// Compilers always lay out tails in the order of their offsets, which is a strong hint
+    /// when deciding whether a word is an offset or a value. Enabling this accepts any offset
+    /// graph (as `ethabi::decode` does) at the cost of more ambiguous decodings.
+    pub allow_non_canonical: bool,
+    /// Fail instead of returning a decoding if the data deviates from the canonical encoding
+    /// of the decoded values in any way (see [`validate_encoding`](crate::validate_encoding))
+    pub strict: bool,
+    /// Maximum number of values behind an offset (arrays, bytes and dynamic tuples) the decoder
+    /// may try to parse, after which the values it has not decoded yet are returned as raw bytes
+    pub max_steps: Option<usize>,
+    /// Maximum time the decoder may spend, after which the values it has not decoded yet are
+    /// returned as raw bytes
+    pub max_duration: Option<Duration>,
+    /// Maximum number of offsets between an argument and a decoded value, values nested deeper
+    /// are returned as raw bytes
+    pub max_depth: Option<usize>,
+    /// Heuristics choosing the type of static words and ranking the layouts the engine finds
+    pub heuristics: Heuristics,
+    /// Partially known types of the arguments, only decodings consistent with them are returned
+    pub type_hints: Option<TypeHints>,
+    /// Roles of words forced or forbidden by the caller, only decodings respecting all of them are
+    /// returned
+    pub pins: Vec<Pin>,
+    /// Record why each word was interpreted the way it was (see
+    /// [`Explanation`](crate::Explanation))
+    pub explain: bool,
+}
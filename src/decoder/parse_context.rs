@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use ethabi::token::Token;
+
+use super::disallowed_markers::{DisallowedMarkers, MarkersBelow};
+use super::explain::{classify_marker, Rejection, TraceEvent};
+use super::options::DecodingOptions;
+use super::parse_marker::{Location, ParseMarker};
+use super::parse_token::{tokenize_argument, TokenOrTopLevel};
+use super::pins::AllowedRoles;
+use super::pointer_graph::PointerGraph;
+use super::word::Word;
+
+// Start and length of the words of the value, the marker, the disallowed markers that fall into
+// the range of the value, whether to recurse into disallowed markers and which bytes come before
+// arrays
+pub type CacheKey = (usize, usize, ParseMarker, MarkersBelow, bool, BytesFirst);
+
+/// Byte strings a length is read as before trying arrays, to find the layout with them as an
+/// alternative to the one with arrays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BytesFirst {
+    /// Only bytes that end where their tail does
+    #[default]
+    Exact,
+    /// Bytes followed by a gap (in non canonical encodings) as well
+    Gaps,
+    /// Empty bytes as well, instead of empty arrays
+    Empty,
+}
+
+// Number of reruns with recursion into unparsable values that may fail before a failing one ends
+// the search for the value being rerun. Every failed rerun tries all combinations of the invalid
+// markers found in it, so without a bound the search is exponential in their number.
+const EXHAUSTIVE_RERUNS: usize = 64;
+
+/// Work spent on decoding one set of arguments, shared by all attempts to decode (parts of) it
+pub struct Budget {
+    steps: usize,
+    start: Instant,
+    limit_reached: bool,
+    exhaustive_reruns: usize,
+    // Results of parsing ranges of the data, which stay valid for every prefix of it as long as the
+    // type hints and pins allow the same roles
+    cache: HashMap<CacheKey, Option<TokenOrTopLevel>>,
+    cache_roles: Vec<AllowedRoles>,
+    // Classifications and rejections of all attempts when explaining the decoding
+    trace: Option<Vec<TraceEvent>>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            steps: 0,
+            start: Instant::now(),
+            limit_reached: false,
+            exhaustive_reruns: EXHAUSTIVE_RERUNS,
+            cache: HashMap::new(),
+            cache_roles: Vec::new(),
+            trace: None,
+        }
+    }
+}
+
+impl Budget {
+    /// Whether any attempt left a value undecoded because a limit in the options was reached
+    pub fn limit_reached(&self) -> bool {
+        self.limit_reached
+    }
+
+    /// Counts one step and returns false (marking the limit as reached) if the steps or the time
+    /// in the options are used up
+    pub fn step(&mut self, options: &DecodingOptions) -> bool {
+        if !self.check(options) {
+            return false;
+        }
+        self.steps += 1;
+        true
+    }
+
+    /// Returns false (marking the limit as reached) if the steps or the time in the options are
+    /// used up
+    pub fn check(&mut self, options: &DecodingOptions) -> bool {
+        if self.is_exhausted(options) {
+            self.limit_reached = true;
+            return false;
+        }
+        true
+    }
+
+    /// Marks a limit that is not tracked by the budget (such as the maximum depth) as reached
+    pub fn reach_limit(&mut self) {
+        self.limit_reached = true;
+    }
+
+    /// Budget allowing the given number of failed reruns before the search is pruned
+    #[cfg(test)]
+    pub(crate) fn with_exhaustive_reruns(exhaustive_reruns: usize) -> Self {
+        Budget {
+            exhaustive_reruns,
+            ..Default::default()
+        }
+    }
+
+    // Counts one failed rerun and returns false if no more may fail
+    fn fail_rerun(&mut self) -> bool {
+        if self.exhaustive_reruns == 0 {
+            return false;
+        }
+        self.exhaustive_reruns -= 1;
+        true
+    }
+
+    // Drops the cached results if they were parsed with other allowed roles
+    fn use_cache_for(&mut self, allowed_roles: &[AllowedRoles]) {
+        if self.cache_roles != allowed_roles {
+            self.cache.clear();
+            self.cache_roles = allowed_roles.to_vec();
+        }
+    }
+
+    pub(crate) fn start_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    pub(crate) fn take_trace(&mut self) -> Vec<TraceEvent> {
+        self.trace.take().unwrap_or_default()
+    }
+
+    fn is_exhausted(&self, options: &DecodingOptions) -> bool {
+        options
+            .max_steps
+            .is_some_and(|max_steps| self.steps >= max_steps)
+            || options
+                .max_duration
+                .is_some_and(|max_duration| self.start.elapsed() >= max_duration)
+    }
+}
+
+/// State shared by all steps of decoding one set of words
+///
+/// Retrying with additional disallowed markers re-parses the same sub ranges of the data over and
+/// over again, which makes the search exponential for nested data. Since the result of parsing a
+/// range only depends on the constraints that fall into the range, it is cached in the budget,
+/// where it is also reused when decoding prefixes of the data.
+pub struct ParseContext<'a> {
+    pub options: &'a DecodingOptions,
+    pub graph: PointerGraph,
+    // Static value of every word, which only depends on the word and the heuristics
+    static_values: Vec<Token>,
+    // Roles the type hints and pins in the options allow for every word, empty without any
+    allowed_roles: Vec<AllowedRoles>,
+    budget: &'a mut Budget,
+    depth: usize,
+    partial: bool,
+    bytes_first: BytesFirst,
+}
+
+impl<'a> ParseContext<'a> {
+    pub fn new(options: &'a DecodingOptions, words: &[Word], budget: &'a mut Budget) -> Self {
+        let allowed_roles = get_allowed_roles(options, words);
+        budget.use_cache_for(&allowed_roles);
+        ParseContext {
+            options,
+            graph: PointerGraph::from_words(words),
+            static_values: words
+                .iter()
+                .map(|word| tokenize_argument(word, options))
+                .collect(),
+            allowed_roles,
+            budget,
+            depth: 0,
+            partial: false,
+            bytes_first: BytesFirst::default(),
+        }
+    }
+
+    /// Byte strings a length is read as before trying arrays
+    pub fn bytes_first(&self) -> BytesFirst {
+        self.bytes_first
+    }
+
+    /// Reads lengths as the lengths of the byte strings before trying arrays, to find the layout
+    /// with them as an alternative
+    pub fn set_bytes_first(&mut self, bytes_first: BytesFirst) {
+        self.bytes_first = bytes_first;
+    }
+
+    /// Key of parsing the value of the marker, whose words start at the given index of the data
+    /// being decoded
+    pub fn cache_key(
+        &self,
+        parse_marker: &ParseMarker,
+        inner_start: usize,
+        inner_words: &[Word],
+        disallowed_markers: &DisallowedMarkers,
+        recurse_disallow_markers: bool,
+    ) -> CacheKey {
+        // The tail an offset points to is parsed without looking at the words around it, so the
+        // same tail has the same key wherever the offset is (and however far the data goes on)
+        let parse_marker = match parse_marker {
+            ParseMarker::DynamicOffset(..) => ParseMarker::DynamicOffset(
+                0,
+                Location {
+                    start: 0,
+                    end: inner_words.len() - 1,
+                },
+            ),
+            parse_marker => parse_marker.clone(),
+        };
+        // Disallowed markers are indices into the words of the value (and of the values nested in
+        // it, which are shorter), so the others never influence its parsing
+        (
+            inner_start,
+            inner_words.len(),
+            parse_marker,
+            disallowed_markers.below(inner_words.len()),
+            recurse_disallow_markers,
+            self.bytes_first,
+        )
+    }
+
+    /// Static value of the word at the index in the data being decoded
+    pub fn static_value(&self, index: usize) -> Token {
+        self.static_values[index].clone()
+    }
+
+    /// Roles the word at the index in the data being decoded may take
+    pub(crate) fn allowed_roles(&self, index: usize) -> AllowedRoles {
+        self.allowed_roles.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn get_cached(&self, key: &CacheKey) -> Option<&Option<TokenOrTopLevel>> {
+        self.budget.cache.get(key)
+    }
+
+    pub fn cache(&mut self, key: CacheKey, result: Option<TokenOrTopLevel>) {
+        // Results that gave up on some region would be wrong once the limits are lifted (e.g.
+        // when the same range is reached at a lower depth)
+        if !self.partial {
+            self.budget.cache.insert(key, result);
+        }
+    }
+
+    /// Records the rules of the words classified by the markers (relative to the words starting at
+    /// the base) when explaining the decoding
+    pub fn trace_markers(&mut self, parse_markers: &[ParseMarker], base: usize) {
+        if self.budget.trace.is_none() {
+            return;
+        }
+        let events: Vec<TraceEvent> = parse_markers
+            .iter()
+            .flat_map(|parse_marker| {
+                classify_marker(parse_marker, base, |index| self.static_value(index))
+            })
+            .map(|(index, rule)| TraceEvent::Classified(index, rule))
+            .collect();
+        if let Some(trace) = &mut self.budget.trace {
+            trace.extend(events);
+        }
+    }
+
+    /// Records the rejection of the interpretation of the word at the index in the data being
+    /// decoded when explaining the decoding
+    pub fn trace_rejection(&mut self, index: usize, rejection: Rejection) {
+        if let Some(trace) = &mut self.budget.trace {
+            trace.push(TraceEvent::Rejected(index, rejection));
+        }
+    }
+
+    // Counts one step into a nested value and returns false if a limit is reached, in which case
+    // the value has to be returned undecoded
+    pub fn enter(&mut self) -> bool {
+        let depth_exceeded = self
+            .options
+            .max_depth
+            .is_some_and(|max_depth| self.depth >= max_depth);
+        if depth_exceeded {
+            self.budget.reach_limit();
+        }
+        if depth_exceeded || !self.budget.step(self.options) {
+            self.partial = true;
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    /// Counts a failed rerun with recursion into unparsable values and returns whether the other
+    /// invalid markers may still be tried
+    pub fn continue_after_failed_rerun(&mut self) -> bool {
+        self.budget.fail_rerun()
+    }
+
+    pub fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Whether any region was returned undecoded because a limit was reached
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+}
+
+fn get_allowed_roles(options: &DecodingOptions, words: &[Word]) -> Vec<AllowedRoles> {
+    if options.type_hints.is_none() && options.pins.is_empty() {
+        return Vec::new();
+    }
+    let mut allowed_roles = match &options.type_hints {
+        Some(type_hints) => type_hints.allowed_roles(words),
+        None => vec![AllowedRoles::default(); words.len()],
+    };
+    for pin in &options.pins {
+        if let Some(roles) = allowed_roles.get_mut(pin.word) {
+            roles.pin(pin);
+        }
+    }
+    allowed_roles
+}
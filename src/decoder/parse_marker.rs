@@ -1,14 +1,15 @@
-use ethereum_types::U256;
-use std::collections::HashMap;
-use std::str::FromStr;
+use super::disallowed_markers::DisallowedMarkers;
+use super::options::DecodingOptions;
+use super::parse_context::{BytesFirst, ParseContext};
+use super::word::Word;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Location {
     pub start: usize,
     pub end: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParseMarker {
     Word(usize),
     DynamicBytes(usize, Location),      // Paddding, Location
@@ -19,7 +20,7 @@ pub enum ParseMarker {
     TopLevel,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MarkerType {
     Array,
     Tuple,
@@ -28,9 +29,9 @@ pub enum MarkerType {
 }
 
 pub fn add_disallowed_marker(
-    disallowed_markers: &mut HashMap<usize, MarkerType>,
+    disallowed_markers: &DisallowedMarkers,
     parse_marker: &ParseMarker,
-) -> Result<(), String> {
+) -> Result<DisallowedMarkers, String> {
     let index = get_index(parse_marker);
     let marker_to_add = match parse_marker {
         ParseMarker::DynamicOffset(..) | ParseMarker::Tuple(..) => MarkerType::Tuple,
@@ -41,14 +42,13 @@ pub fn add_disallowed_marker(
             panic!("Cannot add disallowed marker for {:?}", parse_marker);
         }
     };
-    if disallowed_markers.contains_key(&index) {
+    if disallowed_markers.get(index).is_some() {
         return Err(format!(
             "Disallowed marker already exists for index {}",
             index
         ));
     }
-    disallowed_markers.insert(index, marker_to_add);
-    Ok(())
+    Ok(disallowed_markers.insert(index, marker_to_add))
 }
 
 pub fn get_index(marker: &ParseMarker) -> usize {
@@ -68,16 +68,24 @@ pub fn get_index(marker: &ParseMarker) -> usize {
 fn get_dynamic_offset_marker(
     parse_markers: &[ParseMarker],
     i: usize,
-    chunks: &[&str],
+    words: &[Word],
     most_recent_tuple_offset: usize,
     data_length: usize,
-    disallowed_markers: &HashMap<usize, MarkerType>,
+    disallowed_markers: &DisallowedMarkers,
+    options: &DecodingOptions,
 ) -> Option<(usize, usize, ParseMarker)> {
-    if disallowed_markers.contains_key(&i) && disallowed_markers[&i] == MarkerType::Tuple {
+    if disallowed_markers.contains(i, &MarkerType::Tuple) {
         return None;
     }
-    let chunk = chunks[i];
-    let offset = get_dynamic_offset(0, i, chunk, most_recent_tuple_offset, data_length)?;
+    let word = &words[i];
+    // Non canonical encodings can have tails in any order so only the position of the offset
+    // itself restricts where it can point to
+    let most_recent_tuple_offset = if options.allow_non_canonical {
+        0
+    } else {
+        most_recent_tuple_offset
+    };
+    let offset = get_dynamic_offset(0, i, word, most_recent_tuple_offset, data_length)?;
 
     let tuple_offset = offset;
 
@@ -95,10 +103,13 @@ fn get_dynamic_offset_marker(
 
 pub fn generate_parse_markers(
     parent_marker: &ParseMarker,
-    disallowed_markers: HashMap<usize, MarkerType>,
-    chunks: &[&str],
+    disallowed_markers: &DisallowedMarkers,
+    words: &[Word],
+    base: usize,
     in_dynamic_offset: bool,
+    context: &ParseContext,
 ) -> Vec<ParseMarker> {
+    let options = context.options;
     match parent_marker {
         ParseMarker::DynamicArray(_, locations) => locations
             .iter()
@@ -109,20 +120,34 @@ pub fn generate_parse_markers(
             let mut parse_markers: Vec<ParseMarker> = Vec::new();
             let mut most_recent_tuple_offset: usize = 0;
             let mut most_recent_tuple_location: Option<usize> = None;
-            let data_length = chunks.len() - 1;
-            let mut first_tuple = <usize>::max_value();
+            let data_length = words.len() - 1;
+            let mut first_tuple = usize::MAX;
             let mut i = 0;
 
             while i <= data_length && i < first_tuple {
-                if let Some((tuple_offset, tuple_location, parse_marker)) =
-                    get_dynamic_offset_marker(
-                        &parse_markers,
-                        i,
-                        chunks,
-                        most_recent_tuple_offset,
-                        data_length,
-                        &disallowed_markers,
-                    )
+                let allowed_roles = context.allowed_roles(base + i);
+                let allows_gap = options.allow_non_canonical
+                    && in_dynamic_offset
+                    && i == 0
+                    && allowed_roles.bytes_length;
+                // Words that can neither be an offset nor a length are static values
+                if !context.graph.is_candidate(base + i) || !allowed_roles.any() {
+                    parse_markers.push(ParseMarker::Word(i));
+                    i += 1;
+                } else if let Some((tuple_offset, tuple_location, parse_marker)) = allowed_roles
+                    .offset
+                    .then(|| {
+                        get_dynamic_offset_marker(
+                            &parse_markers,
+                            i,
+                            words,
+                            most_recent_tuple_offset,
+                            data_length,
+                            disallowed_markers,
+                            options,
+                        )
+                    })
+                    .flatten()
                 {
                     update_tuple_variables(
                         &mut parse_markers,
@@ -136,26 +161,40 @@ pub fn generate_parse_markers(
                     i += 1;
                 } else if let Some(bytes_marker) = get_dynamic_bytes_marker(
                     i,
-                    chunks,
+                    words,
                     data_length,
                     &mut first_tuple,
-                    in_dynamic_offset && i == 0,
+                    in_dynamic_offset && i == 0 && allowed_roles.bytes_length,
+                    true,
+                    context.bytes_first() == BytesFirst::Empty,
                 ) {
-                    if let ParseMarker::DynamicBytes(ref _padding, ref location) = bytes_marker {
-                        i = location.end;
-                        parse_markers.push(bytes_marker);
-                    } else {
-                        panic!("Invalid bytes marker");
-                    }
+                    parse_markers.push(bytes_marker);
+                    break;
+                } else if let Some(bytes_marker) = get_dynamic_bytes_marker(
+                    i,
+                    words,
+                    data_length,
+                    &mut first_tuple,
+                    context.bytes_first() == BytesFirst::Gaps && allows_gap,
+                    false,
+                    false,
+                ) {
+                    parse_markers.push(bytes_marker);
+                    break;
                 } else if let Some(array_marker) = get_array_marker(
                     &parse_markers,
                     i,
-                    chunks,
+                    words,
                     data_length,
                     &mut most_recent_tuple_offset,
                     &mut most_recent_tuple_location,
                     &mut first_tuple,
-                    in_dynamic_offset && i == 0,
+                    in_dynamic_offset
+                        && i == 0
+                        // Empty bytes are parsed as empty arrays as well
+                        && (allowed_roles.array_length
+                            || (allowed_roles.bytes_length && words[i].as_usize() == Some(0))),
+                    options,
                 ) {
                     if let ParseMarker::StaticArray(_element_size, ref location) = array_marker {
                         i = location.end;
@@ -166,16 +205,64 @@ pub fn generate_parse_markers(
                     } else {
                         panic!("Invalid array marker");
                     }
+                } else if let Some(bytes_marker) = get_dynamic_bytes_marker(
+                    i,
+                    words,
+                    data_length,
+                    &mut first_tuple,
+                    allows_gap,
+                    false,
+                    false,
+                ) {
+                    // The words after the content are a gap that is not part of any value. The
+                    // fields after bytes in a struct have offsets and tails of their own and data
+                    // appended after the last tail is trailing data, so only non canonical
+                    // encodings leave a gap after the content. Arrays are tried first unless the
+                    // layout with the gaps is searched as an alternative, the heuristics rank
+                    // them.
+                    parse_markers.push(bytes_marker);
+                    break;
                 } else {
                     parse_markers.push(ParseMarker::Word(i));
                     i += 1;
                 }
             }
+            if options.allow_non_canonical {
+                update_tail_locations(&mut parse_markers, data_length);
+            }
             parse_markers
         }
     }
 }
 
+// Tails of out of order offsets cannot be delimited while iterating over the offsets, so instead
+// each tail ends where the next tail (in memory order) begins. Offsets pointing to the same tail
+// share its location.
+fn update_tail_locations(parse_markers: &mut [ParseMarker], data_length: usize) {
+    let mut tail_starts: Vec<usize> = parse_markers
+        .iter()
+        .filter_map(|marker| match marker {
+            ParseMarker::DynamicOffset(_, location) => Some(location.start),
+            _ => None,
+        })
+        .collect();
+    tail_starts.sort_unstable();
+    tail_starts.dedup();
+    for marker in parse_markers.iter_mut() {
+        if let ParseMarker::DynamicOffset(_, ref mut location) = marker {
+            location.end = get_tail_end(&tail_starts, location.start, data_length);
+        }
+    }
+}
+
+fn get_tail_end(tail_starts: &[usize], start: usize, data_length: usize) -> usize {
+    tail_starts
+        .iter()
+        .find(|tail_start| **tail_start > start)
+        .map(|tail_start| tail_start - 1)
+        .unwrap_or(data_length)
+}
+
 fn update_tuple_variables(
     parse_markers: &mut [ParseMarker],
     tuple_offset: usize,
@@ -186,7 +273,7 @@ fn update_tuple_variables(
 ) {
     update_tuple_location(parse_markers, most_recent_tuple_location, tuple_offset - 1);
 
-    if *first_tuple == <usize>::max_value() {
+    if tuple_offset < *first_tuple {
         *first_tuple = tuple_offset;
     }
 
@@ -222,16 +309,15 @@ fn update_tuple_location(
 fn get_dynamic_offset(
     ref_point: usize,
     i: usize,
-    chunk: &str,
+    word: &Word,
     most_recent_tuple: usize,
     data_length: usize,
 ) -> Option<usize> {
-    if U256::from_str(chunk).unwrap() > U256::from(data_length) * U256::from(32) {
+    let decoded_num = word.as_usize()?;
+    if decoded_num > data_length * 32 {
         return None;
     }
 
-    let decoded_num = U256::from_str(chunk).unwrap().as_usize();
-
     if decoded_num % 32 != 0 {
         return None;
     }
@@ -249,12 +335,13 @@ fn get_dynamic_offset(
 fn get_array_marker(
     parse_markers: &Vec<ParseMarker>,
     i: usize,
-    chunks: &[&str],
+    words: &[Word],
     data_length: usize,
     most_recent_tuple_offset: &mut usize,
     most_recent_tuple_location: &mut Option<usize>,
     first_tuple: &mut usize,
     is_first_element_in_dynamic_offset: bool,
+    options: &DecodingOptions,
 ) -> Option<ParseMarker> {
     if !is_first_element_in_dynamic_offset {
         return None;
@@ -262,39 +349,41 @@ fn get_array_marker(
     if let Some(marker) = get_array_marker_dynamic(
         parse_markers,
         i,
-        chunks,
+        words,
         data_length,
         most_recent_tuple_offset,
         most_recent_tuple_location,
         first_tuple,
+        options,
     ) {
         Some(marker)
     } else {
-        get_array_marker_static(i, chunks, data_length, first_tuple)
+        get_array_marker_static(i, words, data_length, first_tuple)
     }
 }
 
+// Bytes whose content ends exactly where the tail ends are preferred, otherwise (if not exact)
+// the content only has to fit into the tail, as in non canonical encodings with a gap after the
+// content
 fn get_dynamic_bytes_marker(
     i: usize,
-    chunks: &[&str],
+    words: &[Word],
     data_length: usize,
     first_tuple: &mut usize,
     first_element_in_dynamic_offset: bool,
+    exact: bool,
+    empty: bool,
 ) -> Option<ParseMarker> {
     if !first_element_in_dynamic_offset {
         return None;
     }
     let remaining_data_length = std::cmp::min(data_length, *first_tuple - 1) - i;
-    let raw_length = U256::from_str(chunks[i]).ok()?;
-    if raw_length > U256::from(<usize>::max_value()) {
-        return None;
-    }
+    let parsed_length = words[i].as_usize()?;
 
-    let parsed_length = raw_length.as_usize();
-
-    // For zero length we prefer empty array over empty bytes
-    // TODO: Review
-    if parsed_length == 0 {
+    // Zero length values are parsed as empty arrays, which encode the same as empty bytes and get
+    // their type from their siblings after decoding, unless the layout with empty bytes is
+    // searched as an alternative
+    if parsed_length == 0 && !empty {
         return None;
     }
 
@@ -304,13 +393,20 @@ fn get_dynamic_bytes_marker(
     }
     let padding = length_words * 32 - parsed_length;
 
-    if length_words + i != remaining_data_length {
+    let fits = if exact {
+        length_words + i == remaining_data_length
+    } else {
+        length_words + i < remaining_data_length
+    };
+    if !fits {
         return None;
     }
 
-    let last_word = chunks[i + length_words];
-    let padding_bytes = &last_word[64 - padding * 2..];
-    if padding_bytes != "0".repeat(padding * 2) {
+    let last_word = &words[i + length_words];
+    if last_word.bytes[32 - padding..]
+        .iter()
+        .any(|byte| *byte != 0)
+    {
         return None;
     }
 
@@ -325,11 +421,11 @@ fn get_dynamic_bytes_marker(
 
 fn get_array_marker_static(
     i: usize,
-    chunks: &[&str],
+    words: &[Word],
     data_length: usize,
     first_tuple: &mut usize,
 ) -> Option<ParseMarker> {
-    let (length, element_size) = get_array_length(i, chunks[i], data_length, false, first_tuple)?;
+    let (length, element_size) = get_array_length(i, &words[i], data_length, false, first_tuple)?;
 
     // If length is zero static / dynamic arrays are the same
     if length == 0 {
@@ -352,16 +448,18 @@ fn get_array_marker_static(
     Some(marker)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn get_array_marker_dynamic(
     parse_markers: &Vec<ParseMarker>,
     i: usize,
-    chunks: &[&str],
+    words: &[Word],
     data_length: usize,
     most_recent_tuple_offset: &mut usize,
     most_recent_tuple_location: &mut Option<usize>,
     first_tuple: &mut usize,
+    options: &DecodingOptions,
 ) -> Option<ParseMarker> {
-    let (length, _) = get_array_length(i, chunks[i], data_length, true, first_tuple)?;
+    let (length, _) = get_array_length(i, &words[i], data_length, true, first_tuple)?;
 
     // If length is zero static / dynamic arrays are the same
     if length == 0 {
@@ -384,21 +482,24 @@ fn get_array_marker_dynamic(
     let mut most_recent_tuple_location_copy = *most_recent_tuple_location;
     let mut first_tuple_copy = *first_tuple;
     let mut parse_markers_copy = (*parse_markers).clone();
-    let limited_chunks = &chunks[i + 1..data_length].to_vec();
-    if limited_chunks.is_empty() {
+    let limited_words = &words[i + 1..data_length];
+    // Every element needs an offset before the last word, which only the check of the first
+    // offset ensures in canonical encodings
+    if limited_words.is_empty() || length > limited_words.len() {
         return None;
     }
     for j in 0..length {
         if let Some((tuple_offset, tuple_location, tuple_parse_marker)) = get_dynamic_offset_marker(
             &parse_markers_copy,
             j,
-            limited_chunks,
+            limited_words,
             most_recent_tuple_offset_copy,
             data_length - i - 1,
-            &HashMap::new(),
+            &DisallowedMarkers::default(),
+            options,
         ) {
             if let ParseMarker::DynamicOffset(_, ref location) = tuple_parse_marker {
-                if j == 0 && location.start != i + length {
+                if j == 0 && location.start != i + length && !options.allow_non_canonical {
                     return None;
                 }
                 parse_markers_copy.push(tuple_parse_marker);
@@ -420,6 +521,12 @@ fn get_array_marker_dynamic(
                 if length != remaining_data_length {
                     return None;
                 }
+                if options.allow_non_canonical {
+                    update_tail_locations(
+                        &mut parse_markers_copy[parse_markers.len()..],
+                        data_length - i - 1,
+                    );
+                }
                 let mut locations = Vec::new();
                 for marker in parse_markers_copy.iter().skip(parse_markers.len()) {
                     if let ParseMarker::DynamicOffset(_j, ref location) = marker {
@@ -443,15 +550,15 @@ fn get_array_marker_dynamic(
 
 fn get_array_length(
     i: usize,
-    chunk: &str,
+    word: &Word,
     data_length: usize,
     is_dynamic: bool,
     first_tuple: &mut usize,
 ) -> Option<(usize, usize)> {
-    if U256::from_str(chunk).unwrap() + U256::from(i) > U256::from(data_length) {
+    let raw_length = word.as_usize()?;
+    if raw_length > data_length - i {
         return None;
     }
-    let raw_length = U256::from_str(chunk).unwrap().as_usize();
 
     // TODO: Excluding single element arrays of static content
     if raw_length == 1 && !is_dynamic {
@@ -1,11 +1,17 @@
 use ethabi::param_type::ParamType;
-use ethabi::token::{LenientTokenizer, Token, Tokenizer};
-use hex;
-use std::collections::HashMap;
+use ethabi::token::Token;
 
+use super::disallowed_markers::DisallowedMarkers;
+use super::explain::{
+    get_marker_kind, get_rejected_word, get_rule_kind, Rejection, RejectionReason,
+};
+use super::options::DecodingOptions;
+use super::parse_context::{CacheKey, ParseContext};
 use super::parse_marker::{
     add_disallowed_marker, generate_parse_markers, get_index, Location, MarkerType, ParseMarker,
 };
+use super::visit::{fold_token, visit_token};
+use super::word::{words_to_bytes, Word};
 
 #[derive(Debug, Clone)]
 pub enum TokenOrTopLevel {
@@ -22,43 +28,362 @@ impl TokenOrTopLevel {
     }
 }
 
+/// Parses the value described by the marker, whose locations are relative to the words starting
+/// at the given index of the data being decoded
+///
+/// Nested values are parsed with an explicit stack of frames instead of recursion, so the nesting
+/// depth of the data is only bounded by memory and the limits in the options.
 pub fn parse_token(
     parse_marker: &ParseMarker,
-    chunks: &[&str],
-    disallowed_markers: &HashMap<usize, MarkerType>,
+    words: &[Word],
+    start: usize,
+    disallowed_markers: &DisallowedMarkers,
     recurse_disallow_markers: bool,
+    context: &mut ParseContext,
 ) -> Option<TokenOrTopLevel> {
+    let mut stack = match begin_parse(
+        parse_marker.clone(),
+        words,
+        start,
+        disallowed_markers.clone(),
+        recurse_disallow_markers,
+        context,
+    ) {
+        Begin::Done(result) => return result,
+        Begin::Frame(frame) => vec![frame],
+    };
+    let mut returned = None;
+    while let Some(frame) = stack.last_mut() {
+        match frame.advance(returned.take(), context) {
+            Step::Parse(
+                parse_marker,
+                words,
+                start,
+                disallowed_markers,
+                recurse_disallow_markers,
+            ) => {
+                match begin_parse(
+                    parse_marker,
+                    words,
+                    start,
+                    disallowed_markers,
+                    recurse_disallow_markers,
+                    context,
+                ) {
+                    Begin::Done(result) => returned = Some(result),
+                    Begin::Frame(frame) => stack.push(frame),
+                }
+            }
+            Step::Return(result) => {
+                let frame = stack.pop().expect("Returning frame is on the stack");
+                if frame.entered {
+                    context.exit();
+                }
+                context.cache(frame.cache_key, result.clone());
+                returned = Some(result);
+            }
+        }
+    }
+    returned.expect("Outermost frame returned a result")
+}
+
+enum Begin<'w> {
+    Done(Option<TokenOrTopLevel>),
+    Frame(Frame<'w>),
+}
+
+// Either the result of the frame or the value it needs to be parsed before it can continue
+enum Step<'w> {
+    Parse(ParseMarker, &'w [Word], usize, DisallowedMarkers, bool),
+    Return(Option<TokenOrTopLevel>),
+}
+
+// Parses values that do not contain other values right away and sets up a frame for all others
+fn begin_parse<'w>(
+    parse_marker: ParseMarker,
+    words: &'w [Word],
+    start: usize,
+    disallowed_markers: DisallowedMarkers,
+    recurse_disallow_markers: bool,
+    context: &mut ParseContext,
+) -> Begin<'w> {
     match parse_marker {
-        ParseMarker::Tuple(ref location) => parse_tuple(location, chunks),
-        ParseMarker::Word(location) => parse_word(location, chunks),
-        ParseMarker::DynamicBytes(padding, location) => {
-            parse_dynamic_bytes(padding, location, chunks)
+        ParseMarker::Tuple(..) | ParseMarker::Word(..) | ParseMarker::DynamicBytes(..) => {
+            Begin::Done(parse_flat_value(&parse_marker, words, start, context))
         }
-        ParseMarker::StaticArray(element_size, ref location) => parse_static_array(
-            element_size,
+        ParseMarker::StaticArray(element_size, ref location) => Begin::Done(parse_static_array(
+            &element_size,
             location,
-            chunks,
-            disallowed_markers,
-            recurse_disallow_markers,
-        ),
-        _ => parse_nested_token(
-            parse_marker,
-            chunks,
+            words,
+            start,
+            context,
+        )),
+        _ => {
+            let inner_offset = match parse_marker {
+                ParseMarker::TopLevel => 0,
+                ParseMarker::DynamicArray(..) => 1,
+                ParseMarker::DynamicOffset(_, ref location) => location.start,
+                _ => panic!("Non nested marker passed to begin_parse"),
+            };
+            let inner_words = match parse_marker {
+                ParseMarker::DynamicOffset(_, ref location) => {
+                    &words[location.start..location.end + 1]
+                }
+                _ => &words[inner_offset..],
+            };
+            let cache_key = context.cache_key(
+                &parse_marker,
+                start + inner_offset,
+                inner_words,
+                &disallowed_markers,
+                recurse_disallow_markers,
+            );
+            if let Some(result) = context.get_cached(&cache_key) {
+                return Begin::Done(result.clone());
+            }
+            // Every nested value (and array element) sits behind an offset so only those count
+            // towards the limits
+            let entered = matches!(parse_marker, ParseMarker::DynamicOffset(..));
+            if entered && !context.enter() {
+                return Begin::Done(Some(TokenOrTopLevel::Token(get_raw_bytes(
+                    &parse_marker,
+                    words,
+                ))));
+            }
+            Begin::Frame(Frame {
+                parse_marker,
+                words,
+                start,
+                inner_words,
+                inner_start: start + inner_offset,
+                disallowed_markers,
+                recurse_disallow_markers,
+                cache_key,
+                entered,
+                stage: Stage::Start,
+            })
+        }
+    }
+}
+
+// Parsing of a value that contains other values
+struct Frame<'w> {
+    parse_marker: ParseMarker,
+    words: &'w [Word],
+    // Index of the first of the words in the data being decoded
+    start: usize,
+    inner_words: &'w [Word],
+    inner_start: usize,
+    disallowed_markers: DisallowedMarkers,
+    recurse_disallow_markers: bool,
+    cache_key: CacheKey,
+    entered: bool,
+    stage: Stage,
+}
+
+enum Stage {
+    Start,
+    // Parsing the values of the markers generated with the given disallowed markers one by one.
+    // If one of them cannot be parsed its marker is disallowed as well and the markers are
+    // generated again.
+    GenerateTokens {
+        disallowed_markers: DisallowedMarkers,
+        parse_markers: Vec<ParseMarker>,
+        tokens: Vec<Token>,
+    },
+    // Parsing the whole value again with one of the markers of invalid tokens disallowed, first
+    // without and then with recursion into the markers of values that cannot be parsed
+    Rerun {
+        invalid_token_markers: Vec<(usize, MarkerType)>,
+        next: usize,
+        recurse_disallow_markers: bool,
+    },
+}
+
+impl<'w> Frame<'w> {
+    // Continues parsing with the result of the last value requested by `Step::Parse`
+    fn advance(
+        &mut self,
+        mut returned: Option<Option<TokenOrTopLevel>>,
+        context: &mut ParseContext,
+    ) -> Step<'w> {
+        loop {
+            match &mut self.stage {
+                Stage::Start => {
+                    if let ParseMarker::DynamicOffset(..) = self.parse_marker {
+                        if self.disallowed_markers.contains(0, &MarkerType::Tuple) {
+                            return Step::Return(None);
+                        }
+                    }
+                    self.stage = self.generate_tokens(self.disallowed_markers.clone(), context);
+                }
+                Stage::GenerateTokens {
+                    disallowed_markers,
+                    parse_markers,
+                    tokens,
+                } => {
+                    if let Some(result) = returned.take() {
+                        let parse_marker = &parse_markers[tokens.len()];
+                        if let Some(wrapped_token) = result {
+                            tokens.push(wrapped_token.to_token());
+                        } else if self.recurse_disallow_markers {
+                            match add_disallowed_marker(disallowed_markers, parse_marker) {
+                                Ok(new_disallowed_markers) => {
+                                    context.trace_rejection(
+                                        self.inner_start + get_rejected_word(parse_marker),
+                                        Rejection {
+                                            kind: get_marker_kind(parse_marker),
+                                            reason: RejectionReason::Unparsable,
+                                        },
+                                    );
+                                    self.stage =
+                                        self.generate_tokens(new_disallowed_markers, context);
+                                    continue;
+                                }
+                                Err(_) => return Step::Return(None),
+                            }
+                        } else {
+                            return Step::Return(None);
+                        }
+                    }
+                    if let Some(parse_marker) = parse_markers.get(tokens.len()) {
+                        return Step::Parse(
+                            parse_marker.clone(),
+                            self.inner_words,
+                            self.inner_start,
+                            disallowed_markers.clone(),
+                            self.recurse_disallow_markers,
+                        );
+                    }
+                    let invalid_token_markers = get_invalid_token_markers(parse_markers, tokens);
+                    if invalid_token_markers.is_empty() {
+                        return Step::Return(Some(wrap_tokens(
+                            &self.parse_marker,
+                            std::mem::take(tokens),
+                        )));
+                    }
+                    if !self.recurse_disallow_markers {
+                        return Step::Return(None);
+                    }
+                    for (index, marker_type) in &invalid_token_markers {
+                        context.trace_rejection(
+                            self.inner_start + index,
+                            Rejection {
+                                kind: get_rule_kind(marker_type),
+                                reason: RejectionReason::StaticTuple,
+                            },
+                        );
+                    }
+                    self.stage = Stage::Rerun {
+                        invalid_token_markers,
+                        next: 0,
+                        recurse_disallow_markers: false,
+                    };
+                }
+                // Every invalid marker is tried on its own, first without and then with recursion.
+                // Reruns parse the same sub ranges again, which the cache in the context answers.
+                // Once the budget of failed reruns with recursion is used up, the first one that
+                // fails ends the search and the value is treated as unparsable.
+                Stage::Rerun {
+                    invalid_token_markers,
+                    next,
+                    recurse_disallow_markers,
+                } => {
+                    if let Some(result) = returned.take() {
+                        if result.is_some()
+                            || *recurse_disallow_markers && !context.continue_after_failed_rerun()
+                        {
+                            return Step::Return(result);
+                        }
+                    }
+                    while let Some((index, marker_type)) = invalid_token_markers.get(*next) {
+                        *next += 1;
+                        if !self.disallowed_markers.contains(*index, marker_type) {
+                            return Step::Parse(
+                                self.parse_marker.clone(),
+                                self.words,
+                                self.start,
+                                self.disallowed_markers.insert(*index, marker_type.clone()),
+                                *recurse_disallow_markers,
+                            );
+                        }
+                    }
+                    if *recurse_disallow_markers {
+                        return Step::Return(None);
+                    }
+                    *next = 0;
+                    *recurse_disallow_markers = true;
+                }
+            }
+        }
+    }
+
+    fn generate_tokens(
+        &self,
+        disallowed_markers: DisallowedMarkers,
+        context: &mut ParseContext,
+    ) -> Stage {
+        let parse_markers = generate_parse_markers(
+            &self.parse_marker,
+            &disallowed_markers,
+            self.inner_words,
+            self.inner_start,
+            matches!(self.parse_marker, ParseMarker::DynamicOffset(..)),
+            context,
+        );
+        context.trace_markers(&parse_markers, self.inner_start);
+        Stage::GenerateTokens {
             disallowed_markers,
-            recurse_disallow_markers,
-        ),
+            parse_markers,
+            tokens: Vec::new(),
+        }
+    }
+}
+
+fn wrap_tokens(parse_marker: &ParseMarker, tokens: Vec<Token>) -> TokenOrTopLevel {
+    match parse_marker {
+        ParseMarker::TopLevel => TokenOrTopLevel::TopLevel(tokens),
+        ParseMarker::DynamicArray(..) => TokenOrTopLevel::Token(Token::Array(tokens)),
+        ParseMarker::DynamicOffset(..) => TokenOrTopLevel::Token(Token::Tuple(tokens)),
+        _ => panic!("Non nested marker passed to wrap_tokens"),
+    }
+}
+
+// Region of the data covered by a nested value that is not decoded any further
+fn get_raw_bytes(parse_marker: &ParseMarker, words: &[Word]) -> Token {
+    let raw_words = match parse_marker {
+        ParseMarker::DynamicOffset(_, location) => &words[location.start..location.end + 1],
+        _ => panic!("Non offset marker passed to get_raw_bytes"),
+    };
+    Token::Bytes(words_to_bytes(raw_words))
+}
+
+// Values that do not contain offsets and can therefore be parsed without any further search
+fn parse_flat_value(
+    parse_marker: &ParseMarker,
+    words: &[Word],
+    start: usize,
+    context: &ParseContext,
+) -> Option<TokenOrTopLevel> {
+    match parse_marker {
+        ParseMarker::Tuple(location) => parse_tuple(location, start, context),
+        ParseMarker::Word(location) => parse_word(location, start, context),
+        ParseMarker::DynamicBytes(padding, location) => {
+            parse_dynamic_bytes(padding, location, words)
+        }
+        _ => panic!("Nested marker passed to parse_flat_value"),
     }
 }
 
 fn parse_static_array(
     element_size: &usize,
     location: &Location,
-    chunks: &[&str],
-    disallowed_markers: &HashMap<usize, MarkerType>,
-    recurse_disallow_markers: bool,
+    words: &[Word],
+    start: usize,
+    context: &ParseContext,
 ) -> Option<TokenOrTopLevel> {
+    let data_to_parse = &words[location.start..location.end];
     let mut parse_tree = Vec::new();
-    let data_to_parse = chunks[location.start..location.end].to_vec();
     let mut i = 0;
     while i < data_to_parse.len() {
         let new_parse_marker = if *element_size == 1 {
@@ -69,203 +394,45 @@ fn parse_static_array(
                 end: i + element_size,
             })
         };
-
         parse_tree.push(
-            parse_token(
+            parse_flat_value(
                 &new_parse_marker,
-                &data_to_parse,
-                disallowed_markers,
-                recurse_disallow_markers,
+                data_to_parse,
+                start + location.start,
+                context,
             )?
             .to_token(),
         );
         i += element_size;
     }
-    let result = Token::Array(parse_tree);
-    Some(TokenOrTopLevel::Token(result))
+    Some(TokenOrTopLevel::Token(Token::Array(parse_tree)))
 }
 
 fn parse_dynamic_bytes(
     padding: &usize,
     location: &Location,
-    chunks: &[&str],
+    words: &[Word],
 ) -> Option<TokenOrTopLevel> {
-    let mut decoded_bytes: Vec<u8> = chunks[location.start..location.end]
-        .iter()
-        .flat_map(|chunk| hex::decode(chunk).expect("Failed to decode dynamic bytes"))
-        .collect();
+    let mut decoded_bytes = words_to_bytes(&words[location.start..location.end]);
     decoded_bytes.truncate(decoded_bytes.len().saturating_sub(*padding));
     Some(TokenOrTopLevel::Token(Token::Bytes(decoded_bytes)))
 }
 
-fn parse_word(location: &usize, chunks: &[&str]) -> Option<TokenOrTopLevel> {
-    Some(TokenOrTopLevel::Token(tokenize_argument(chunks[*location])))
+fn parse_word(location: &usize, start: usize, context: &ParseContext) -> Option<TokenOrTopLevel> {
+    Some(TokenOrTopLevel::Token(
+        context.static_value(start + location),
+    ))
 }
 
-fn parse_tuple(location: &Location, chunks: &[&str]) -> Option<TokenOrTopLevel> {
-    let data_to_parse = chunks[location.start..location.end].to_vec();
-    let elements = data_to_parse.iter().map(|x| tokenize_argument(x)).collect();
-    Some(TokenOrTopLevel::Token(Token::Tuple(elements)))
-}
-
-fn parse_nested_token(
-    outer_parse_marker: &ParseMarker,
-    chunks: &[&str],
-    disallowed_markers: &HashMap<usize, MarkerType>,
-    recurse_disallow_markers: bool,
-) -> Option<TokenOrTopLevel> {
-    let data_to_parse = match outer_parse_marker {
-        ParseMarker::TopLevel => chunks,
-        ParseMarker::DynamicArray(..) => &chunks[1..],
-        ParseMarker::DynamicOffset(_, ref location) => {
-            if disallowed_markers.contains_key(&0) && disallowed_markers[&0] == MarkerType::Tuple {
-                return None;
-            }
-            &chunks[location.start..location.end + 1]
-        }
-        _ => panic!("Non nested marker passed to parse_nested_token"),
-    };
-    let (parse_markers, tokens) = generate_tokens(
-        outer_parse_marker,
-        disallowed_markers,
-        data_to_parse,
-        recurse_disallow_markers,
-    )?;
-    let wrapped_token = match outer_parse_marker {
-        ParseMarker::TopLevel => TokenOrTopLevel::TopLevel(tokens.clone()),
-        ParseMarker::DynamicArray(..) => TokenOrTopLevel::Token(Token::Array(tokens.clone())),
-        ParseMarker::DynamicOffset(_, _) => TokenOrTopLevel::Token(Token::Tuple(tokens.clone())),
-        _ => panic!("Non nested marker passed to parse_nested_token"),
-    };
-    strip_invalid_tokens(
-        outer_parse_marker,
-        &parse_markers,
-        wrapped_token,
-        tokens,
-        disallowed_markers,
-        chunks,
-        recurse_disallow_markers,
-    )
-}
-
-fn generate_tokens(
-    outer_parse_marker: &ParseMarker,
-    disallowed_markers: &HashMap<usize, MarkerType>,
-    inner_data: &[&str],
-    recurse_disallow_markers: bool,
-) -> Option<(Vec<ParseMarker>, Vec<Token>)> {
-    let mut tokens = Vec::new();
-    let parse_markers = generate_parse_markers(
-        outer_parse_marker,
-        disallowed_markers.clone(),
-        inner_data,
-        matches!(outer_parse_marker, ParseMarker::DynamicOffset(..)),
-    );
-    for parse_marker in parse_markers.clone() {
-        let result = parse_token(
-            &parse_marker,
-            inner_data,
-            disallowed_markers,
-            recurse_disallow_markers,
-        );
-
-        if let Some(wrapped_token) = result {
-            tokens.push(wrapped_token.to_token());
-        } else if recurse_disallow_markers {
-            let mut new_disallowed_markers = disallowed_markers.clone();
-            add_disallowed_marker(&mut new_disallowed_markers, &parse_marker).ok()?;
-            return generate_tokens(
-                outer_parse_marker,
-                &new_disallowed_markers,
-                inner_data,
-                recurse_disallow_markers,
-            );
-        } else {
-            return None;
-        }
-    }
-    Some((parse_markers, tokens))
-}
-
-fn strip_invalid_tokens(
-    parse_marker: &ParseMarker,
-    parse_markers: &[ParseMarker],
-    token: TokenOrTopLevel,
-    mut tokens: Vec<Token>,
-    disallowed_markers: &HashMap<usize, MarkerType>,
-    data_to_parse: &[&str],
-    recurse_disallow_markers: bool,
-) -> Option<TokenOrTopLevel> {
-    let invalid_token_markers = get_invalid_token_markers(parse_markers, &tokens);
-    if !invalid_token_markers.is_empty() {
-        if recurse_disallow_markers {
-            let result = rerun_with_invalid_token_markers(
-                parse_marker,
-                &invalid_token_markers,
-                disallowed_markers,
-                data_to_parse,
-                false,
-            );
-
-            if result.is_some() {
-                result
-            } else {
-                let result = rerun_with_invalid_token_markers(
-                    parse_marker,
-                    &invalid_token_markers,
-                    disallowed_markers,
-                    data_to_parse,
-                    true,
-                );
-                if result.is_some() {
-                    result
-                } else {
-                    None
-                }
-            }
-        } else {
-            None
-        }
-    } else {
-        match token {
-            TokenOrTopLevel::TopLevel(_) => {
-                tokens = tokens
-                    .iter()
-                    .map(|e| remove_single_element_tuples((*e).clone()))
-                    .collect();
-
-                Some(TokenOrTopLevel::TopLevel(tokens))
-            }
-            _ => Some(token),
-        }
-    }
-}
-
-fn rerun_with_invalid_token_markers(
-    parse_marker: &ParseMarker,
-    invalid_token_markers: &Vec<(usize, MarkerType)>,
-    disallowed_markers: &HashMap<usize, MarkerType>,
-    data_to_parse: &[&str],
-    recurse_disallow_markers: bool,
+fn parse_tuple(
+    location: &Location,
+    start: usize,
+    context: &ParseContext,
 ) -> Option<TokenOrTopLevel> {
-    for invalid_token_marker in invalid_token_markers {
-        if !disallowed_markers.contains_key(&invalid_token_marker.0)
-            || invalid_token_marker.1 != disallowed_markers[&invalid_token_marker.0]
-        {
-            let mut new_disallowed_markers = disallowed_markers.clone();
-            new_disallowed_markers.insert(invalid_token_marker.0, invalid_token_marker.1.clone());
-            let new_result = parse_token(
-                parse_marker,
-                data_to_parse,
-                &new_disallowed_markers,
-                recurse_disallow_markers,
-            );
-            if new_result.is_some() {
-                return new_result;
-            }
-        }
-    }
-    None
+    let elements = (location.start..location.end)
+        .map(|index| context.static_value(start + index))
+        .collect();
+    Some(TokenOrTopLevel::Token(Token::Tuple(elements)))
 }
 
 fn get_invalid_token_markers(
@@ -282,59 +449,34 @@ fn get_invalid_token_markers(
     invalid_token_markers
 }
 
-fn remove_single_element_tuples(token: Token) -> Token {
+// Parsing wraps every value behind an offset in a tuple, which only encodes differently than the
+// value itself for bytes and arrays (as dynamic tuples have an offset of their own)
+pub fn remove_offset_tuples(token: Token) -> Token {
     match token {
+        Token::Tuple(mut tokens)
+            if tokens.len() == 1 && matches!(tokens[0], Token::Bytes(_) | Token::Array(_)) =>
+        {
+            remove_offset_tuples(tokens.remove(0))
+        }
         Token::Tuple(tokens) => {
-            if tokens.len() == 1 {
-                remove_single_element_tuples(tokens[0].clone())
-            } else {
-                Token::Tuple(
-                    tokens
-                        .iter()
-                        .map(|e| remove_single_element_tuples((*e).clone()))
-                        .collect(),
-                )
-            }
+            Token::Tuple(tokens.into_iter().map(remove_offset_tuples).collect())
+        }
+        Token::Array(tokens) => {
+            Token::Array(tokens.into_iter().map(remove_offset_tuples).collect())
         }
-        Token::Array(tokens) => Token::Array(
-            tokens
-                .iter()
-                .map(|e| remove_single_element_tuples((*e).clone()))
-                .collect(),
-        ),
         _ => token,
     }
 }
 
-pub fn tokenize_argument(argument: &str) -> Token {
-    let trimmed_argument = argument.trim_start_matches('0');
-
-    // If word does not start with a 0 then assume it is a left aligned type (bytes)
-    if trimmed_argument.len() == argument.len() {
-        let mut right_trimmed_argument = argument.trim_end_matches('0').to_owned();
-        if right_trimmed_argument.len() % 2 == 1 {
-            right_trimmed_argument.push('0');
-        }
-        let bytes_len = right_trimmed_argument.len() / 2;
-
-        return LenientTokenizer::tokenize(
-            &ParamType::FixedBytes(bytes_len),
-            &right_trimmed_argument,
-        )
-        .expect("Failed to tokenize bytes argument");
-    }
-    // TODO: Maybe change default to still use bytes when only one leading zero ?
-
-    // If bytes match an address that does not start with 0 byte then assume it is an address
-    if let Ok(token) = LenientTokenizer::tokenize(&ParamType::Address, trimmed_argument) {
-        return token;
-    }
+pub fn remove_single_element_tuples(token: Token) -> Token {
+    fold_token(token, &mut |_: &[usize], token: Token| match token {
+        Token::Tuple(mut tokens) if tokens.len() == 1 => tokens.remove(0),
+        token => token,
+    })
+}
 
-    if let Ok(token) = LenientTokenizer::tokenize(&ParamType::Uint(256), argument) {
-        token
-    } else {
-        panic!("Could not tokenize argument: {}", argument);
-    }
+pub fn tokenize_argument(word: &Word, options: &DecodingOptions) -> Token {
+    options.heuristics.tokenize_word(word)
 }
 
 pub fn check_token(token: &Token, parse_marker: &ParseMarker) -> Option<MarkerType> {
@@ -351,17 +493,35 @@ pub fn check_token(token: &Token, parse_marker: &ParseMarker) -> Option<MarkerTy
 }
 
 pub fn contains_dynamic_type(token: &Token) -> bool {
+    let mut dynamic = false;
+    visit_token(token, &mut |_: &[usize], token: &Token| {
+        dynamic |= matches!(token, Token::Array(_) | Token::Bytes(_) | Token::String(_));
+        !dynamic
+    });
+    dynamic
+}
+
+pub fn get_param_type(token: &Token) -> ParamType {
     match token {
-        Token::Tuple(tokens) => {
-            for token in tokens {
-                if contains_dynamic_type(token) {
-                    return true;
-                }
-            }
-            false
+        Token::Address(_) => ParamType::Address,
+        Token::FixedBytes(bytes) => ParamType::FixedBytes(bytes.len()),
+        Token::Bytes(_) => ParamType::Bytes,
+        Token::Int(_) => ParamType::Int(256),
+        Token::Uint(_) => ParamType::Uint(256),
+        Token::Bool(_) => ParamType::Bool,
+        Token::String(_) => ParamType::String,
+        Token::FixedArray(tokens) => {
+            ParamType::FixedArray(Box::new(get_element_param_type(tokens)), tokens.len())
         }
-        Token::Array(_) => true,
-        Token::Bytes(_) => true,
-        _ => false,
+        // Empty arrays decode the same regardless of element type
+        Token::Array(tokens) => ParamType::Array(Box::new(get_element_param_type(tokens))),
+        Token::Tuple(tokens) => ParamType::Tuple(tokens.iter().map(get_param_type).collect()),
     }
 }
+
+fn get_element_param_type(tokens: &[Token]) -> ParamType {
+    tokens
+        .first()
+        .map(get_param_type)
+        .unwrap_or(ParamType::Uint(256))
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ethabi::token::Token;
+
+use super::annotation::{annotate_words, get_token, WordAnnotation, WordRole};
+use super::empty_values::is_empty_value;
+use super::error::{DecodingError, PinError};
+
+/// Role of a word that can be pinned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinnedRole {
+    /// Static value in the head of a tuple or array
+    Value,
+    /// Offset pointing to the tail of a dynamic value
+    Offset,
+    /// Length of a byte string or string
+    BytesLength,
+    /// Length of a dynamic array
+    ArrayLength,
+}
+
+/// Correction of the role of one word, which every returned decoding has to respect
+///
+/// Pins are written as `<word>=<role>` to force and `<word>!=<role>` to forbid a role, with the
+/// roles `value`, `offset`, `bytes-length` and `array-length`.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::{DecodingOptions, Pin, PinnedRole};
+/// use ethabi::Token;
+///
+/// // Without pins this is an empty array
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000000";
+/// let options = DecodingOptions {
+///     pins: vec![Pin::is_not(0, PinnedRole::Offset)],
+///     ..Default::default()
+/// };
+/// let result = abi_decoder::decode_arguments_with_options(encoded, &options).unwrap();
+/// assert_eq!(result.tokens, vec![Token::Uint(32.into()), Token::Uint(0.into())]);
+/// assert_eq!("0!=offset".parse(), Ok(Pin::is_not(0, PinnedRole::Offset)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pin {
+    /// Index of the word in the encoded arguments
+    pub word: usize,
+    /// Pinned role
+    pub role: PinnedRole,
+    /// Whether the word has to have the role (or must not have it)
+    pub is: bool,
+}
+
+impl Pin {
+    /// Forces the role of the word
+    pub fn is(word: usize, role: PinnedRole) -> Self {
+        Pin {
+            word,
+            role,
+            is: true,
+        }
+    }
+
+    /// Forbids the role for the word
+    pub fn is_not(word: usize, role: PinnedRole) -> Self {
+        Pin {
+            word,
+            role,
+            is: false,
+        }
+    }
+
+    /// Whether the pins contradict each other, in which case no decoding can respect both
+    pub fn conflicts_with(&self, other: &Pin) -> bool {
+        self.word == other.word
+            && match (self.is, other.is) {
+                (true, true) => self.role != other.role,
+                (true, false) | (false, true) => self.role == other.role,
+                (false, false) => false,
+            }
+    }
+}
+
+impl fmt::Display for PinnedRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PinnedRole::Value => "value",
+            PinnedRole::Offset => "offset",
+            PinnedRole::BytesLength => "bytes-length",
+            PinnedRole::ArrayLength => "array-length",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for PinnedRole {
+    type Err = PinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "value" => Ok(PinnedRole::Value),
+            "offset" => Ok(PinnedRole::Offset),
+            "bytes-length" => Ok(PinnedRole::BytesLength),
+            "array-length" => Ok(PinnedRole::ArrayLength),
+            _ => Err(PinError::UnknownRole(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = if self.is { "=" } else { "!=" };
+        write!(f, "{}{}{}", self.word, operator, self.role)
+    }
+}
+
+impl FromStr for Pin {
+    type Err = PinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (word, role) = s
+            .split_once('=')
+            .ok_or_else(|| PinError::Syntax(s.to_string()))?;
+        let (word, is) = match word.strip_suffix('!') {
+            Some(word) => (word, false),
+            None => (word, true),
+        };
+        Ok(Pin {
+            word: word
+                .trim()
+                .parse()
+                .map_err(|_| PinError::Syntax(s.to_string()))?,
+            role: role.trim().parse()?,
+            is,
+        })
+    }
+}
+
+/// Roles other than a static value a word may take in the decoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AllowedRoles {
+    pub offset: bool,
+    pub bytes_length: bool,
+    pub array_length: bool,
+}
+
+impl Default for AllowedRoles {
+    fn default() -> Self {
+        AllowedRoles {
+            offset: true,
+            bytes_length: true,
+            array_length: true,
+        }
+    }
+}
+
+impl AllowedRoles {
+    pub const VALUE: AllowedRoles = AllowedRoles {
+        offset: false,
+        bytes_length: false,
+        array_length: false,
+    };
+    pub const OFFSET: AllowedRoles = AllowedRoles {
+        offset: true,
+        bytes_length: false,
+        array_length: false,
+    };
+    pub const BYTES_LENGTH: AllowedRoles = AllowedRoles {
+        offset: false,
+        bytes_length: true,
+        array_length: false,
+    };
+    pub const ARRAY_LENGTH: AllowedRoles = AllowedRoles {
+        offset: false,
+        bytes_length: false,
+        array_length: true,
+    };
+    // First word of a tuple in a tail, which is its first value or the offset of it
+    pub const TUPLE_HEAD: AllowedRoles = AllowedRoles {
+        offset: true,
+        bytes_length: false,
+        array_length: false,
+    };
+
+    /// Whether the word can be anything but a static value
+    pub fn any(&self) -> bool {
+        self.offset || self.bytes_length || self.array_length
+    }
+
+    /// Restricts the roles to the ones the pin allows
+    pub fn pin(&mut self, pin: &Pin) {
+        if pin.is {
+            *self = match pin.role {
+                PinnedRole::Value => AllowedRoles::VALUE,
+                PinnedRole::Offset => AllowedRoles::OFFSET,
+                PinnedRole::BytesLength => AllowedRoles::BYTES_LENGTH,
+                PinnedRole::ArrayLength => AllowedRoles::ARRAY_LENGTH,
+            };
+        } else {
+            match pin.role {
+                // The search falls back to static values, so decodings that still use one are
+                // only filtered out afterwards
+                PinnedRole::Value => {}
+                PinnedRole::Offset => self.offset = false,
+                PinnedRole::BytesLength => self.bytes_length = false,
+                PinnedRole::ArrayLength => self.array_length = false,
+            }
+        }
+    }
+}
+
+/// Returns the first word with conflicting pins as an error
+pub(crate) fn check_pins(pins: &[Pin]) -> Result<(), DecodingError> {
+    for (index, pin) in pins.iter().enumerate() {
+        if pins[index + 1..]
+            .iter()
+            .any(|other| pin.conflicts_with(other))
+        {
+            return Err(DecodingError::ConflictingPins(pin.word));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the decoded arguments respect all pins for the data (without function selector)
+pub(crate) fn respects_pins(pins: &[Pin], tokens: &[Token], data: &[u8]) -> bool {
+    if pins.is_empty() {
+        return true;
+    }
+    let annotations = annotate_words(tokens, data);
+    pins.iter().all(|pin| {
+        let has_role = annotations
+            .get(pin.word)
+            .is_some_and(|annotation| has_role(annotation, tokens, pin.role));
+        has_role == pin.is
+    })
+}
+
+fn has_role(annotation: &WordAnnotation, tokens: &[Token], role: PinnedRole) -> bool {
+    match (&annotation.role, role) {
+        (WordRole::HeadValue, PinnedRole::Value)
+        | (WordRole::Offset { .. }, PinnedRole::Offset) => true,
+        (WordRole::Length { .. }, PinnedRole::BytesLength | PinnedRole::ArrayLength) => {
+            match get_token(tokens, &annotation.path) {
+                // Empty values can be either
+                Some(token) if is_empty_value(token) => true,
+                Some(Token::Array(_)) => role == PinnedRole::ArrayLength,
+                _ => role == PinnedRole::BytesLength,
+            }
+        }
+        _ => false,
+    }
+}
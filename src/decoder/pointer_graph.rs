@@ -0,0 +1,224 @@
+use std::ops::Range;
+
+use super::word::{to_words, Word};
+
+/// Word that could be an offset pointing to the tail of a dynamic value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OffsetCandidate {
+    /// Index of the word containing the offset
+    pub from: usize,
+    /// Index of the word the offset is relative to (the start of the enclosing tuple / array)
+    pub base: usize,
+    /// Index of the word the offset points to
+    pub to: usize,
+}
+
+/// Word that could be the length of an array or of a byte string
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LengthCandidate {
+    /// Index of the word containing the length
+    pub index: usize,
+    /// Number of elements if the word is the length of an array (every element takes at least one
+    /// word, so it has to fit into the remaining data)
+    pub elements: Option<usize>,
+    /// Words containing the content if the word is the length of a byte string (the padding of the
+    /// last word has to be zero)
+    pub bytes: Option<Range<usize>>,
+}
+
+/// Graph of which words could be offsets to which other words and which could be lengths
+///
+/// Offsets are relative to the start of the tuple or array they are part of, so the analysis
+/// starts at the first word and follows every candidate offset to the region it could point to
+/// (and the region after a potential length at its target). Every offset and length the decoder
+/// can consider is part of the graph, which makes it an upper bound of the possible structures of
+/// the data.
+///
+/// Example
+/// ```rust
+/// // An array of the two numbers 3 and 4
+/// let data = hex::decode(
+///     "0000000000000000000000000000000000000000000000000000000000000020\
+///      0000000000000000000000000000000000000000000000000000000000000002\
+///      0000000000000000000000000000000000000000000000000000000000000003\
+///      0000000000000000000000000000000000000000000000000000000000000004",
+/// )
+/// .unwrap();
+/// let graph = abi_decoder::PointerGraph::new(&data);
+/// let offset = abi_decoder::OffsetCandidate { from: 0, base: 0, to: 1 };
+/// assert_eq!(graph.offsets_from(0), &[offset]);
+/// assert_eq!(graph.length(1).unwrap().elements, Some(2));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PointerGraph {
+    word_count: usize,
+    // Sorted by offset, base and target
+    offsets: Vec<OffsetCandidate>,
+    // Positions in offsets sorted by target
+    offsets_by_target: Vec<usize>,
+    // Sorted by index
+    lengths: Vec<LengthCandidate>,
+    bases: Vec<usize>,
+    candidates: Vec<bool>,
+}
+
+impl PointerGraph {
+    /// Analyzes the abi encoded data (without function selector), an incomplete word at the end
+    /// can neither be an offset nor a length and is ignored
+    pub fn new(data: &[u8]) -> Self {
+        let complete_length = data.len() - data.len() % 32;
+        Self::from_words(&to_words(&data[..complete_length]))
+    }
+
+    /// Analyzes the words of the abi encoded data
+    pub fn from_words(words: &[Word]) -> Self {
+        let word_count = words.len();
+        // Index and value in words of every word that is a multiple of 32 small enough to point
+        // into the data from somewhere
+        let offset_words: Vec<(usize, usize)> = words
+            .iter()
+            .enumerate()
+            .filter_map(|(index, word)| {
+                let value = word.as_usize()?;
+                (value % 32 == 0 && value / 32 < word_count).then_some((index, value / 32))
+            })
+            .collect();
+        let mut offsets = Vec::new();
+        let mut is_base = vec![false; word_count + 1];
+        is_base[0] = true;
+        let mut pending_bases = vec![0];
+        while let Some(base) = pending_bases.pop() {
+            let first = offset_words.partition_point(|(from, _)| *from < base);
+            for (from, value) in &offset_words[first..] {
+                let to = base + value;
+                if to <= *from || to >= word_count {
+                    continue;
+                }
+                offsets.push(OffsetCandidate {
+                    from: *from,
+                    base,
+                    to,
+                });
+                // The target is either the start of a tuple or the length of an array whose
+                // elements start after it
+                for next_base in [to, to + 1] {
+                    if !is_base[next_base] {
+                        is_base[next_base] = true;
+                        pending_bases.push(next_base);
+                    }
+                }
+            }
+        }
+        offsets.sort_unstable();
+        let mut offsets_by_target: Vec<usize> = (0..offsets.len()).collect();
+        offsets_by_target.sort_by_key(|position| offsets[*position].to);
+
+        let lengths: Vec<LengthCandidate> = words
+            .iter()
+            .enumerate()
+            .filter_map(|(index, word)| get_length_candidate(index, word, words))
+            .collect();
+
+        let mut candidates = vec![false; word_count];
+        for offset in &offsets {
+            candidates[offset.from] = true;
+        }
+        for length in &lengths {
+            candidates[length.index] = true;
+        }
+        PointerGraph {
+            word_count,
+            offsets,
+            offsets_by_target,
+            lengths,
+            bases: (0..word_count).filter(|base| is_base[*base]).collect(),
+            candidates,
+        }
+    }
+
+    /// Number of complete words in the analyzed data
+    pub fn word_count(&self) -> usize {
+        self.word_count
+    }
+
+    /// All candidate offsets sorted by the index of the word containing them
+    pub fn offsets(&self) -> &[OffsetCandidate] {
+        &self.offsets
+    }
+
+    /// Candidate offsets contained in the word at the index
+    pub fn offsets_from(&self, index: usize) -> &[OffsetCandidate] {
+        let start = self.offsets.partition_point(|offset| offset.from < index);
+        let end = self.offsets.partition_point(|offset| offset.from <= index);
+        &self.offsets[start..end]
+    }
+
+    /// Candidate offsets pointing to the word at the index
+    pub fn offsets_to(&self, index: usize) -> impl Iterator<Item = &OffsetCandidate> {
+        let start = self
+            .offsets_by_target
+            .partition_point(|position| self.offsets[*position].to < index);
+        let end = self
+            .offsets_by_target
+            .partition_point(|position| self.offsets[*position].to <= index);
+        self.offsets_by_target[start..end]
+            .iter()
+            .map(|position| &self.offsets[*position])
+    }
+
+    /// All words that could be the length of an array or byte string sorted by index
+    pub fn lengths(&self) -> &[LengthCandidate] {
+        &self.lengths
+    }
+
+    /// Length candidate for the word at the index
+    pub fn length(&self, index: usize) -> Option<&LengthCandidate> {
+        self.lengths
+            .binary_search_by_key(&index, |length| length.index)
+            .ok()
+            .map(|position| &self.lengths[position])
+    }
+
+    /// Indices of all words that could be the start of a tuple or array (i.e. that offsets could
+    /// be relative to)
+    pub fn bases(&self) -> &[usize] {
+        &self.bases
+    }
+
+    /// Whether the word could be an offset or a length, all other words can only be static values
+    pub fn is_candidate(&self, index: usize) -> bool {
+        self.candidates.get(index).copied().unwrap_or(false)
+    }
+}
+
+fn get_length_candidate(index: usize, word: &Word, words: &[Word]) -> Option<LengthCandidate> {
+    let length = word.as_usize()?;
+    let remaining_words = words.len() - index - 1;
+    let elements = (length <= remaining_words).then_some(length);
+    let bytes = get_bytes_location(index, length, words);
+    if elements.is_none() && bytes.is_none() {
+        return None;
+    }
+    Some(LengthCandidate {
+        index,
+        elements,
+        bytes,
+    })
+}
+
+fn get_bytes_location(index: usize, length: usize, words: &[Word]) -> Option<Range<usize>> {
+    let length_words = length.div_ceil(32);
+    if length_words > words.len() - index - 1 {
+        return None;
+    }
+    let location = index + 1..index + 1 + length_words;
+    let padding = length_words * 32 - length;
+    if padding > 0
+        && words[location.end - 1].bytes[32 - padding..]
+            .iter()
+            .any(|byte| *byte != 0)
+    {
+        return None;
+    }
+    Some(location)
+}
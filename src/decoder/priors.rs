@@ -0,0 +1,326 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ethabi::param_type::Reader;
+use ethabi::token::Token;
+use ethabi::ParamType;
+
+use super::error::PriorsError;
+use super::heuristic::Heuristic;
+use super::pointer_graph::PointerGraph;
+use super::word::{to_words, Word};
+
+// Score of one unit of log likelihood, large enough to outweigh the default heuristics whenever the
+// corpus has an opinion
+const SCALE: f64 = 1000.0;
+
+/// Number of words that had a role among all words that could have had it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ratio {
+    /// Words that had the role
+    pub hits: usize,
+    /// Words that could have had the role
+    pub total: usize,
+}
+
+impl Ratio {
+    fn add(&mut self, hit: bool) {
+        self.hits += usize::from(hit);
+        self.total += 1;
+    }
+
+    // Log odds of a hit, smoothed so that an empty ratio has no opinion
+    fn log_odds(&self) -> f64 {
+        ((self.hits as f64 + 1.0) / ((self.total - self.hits) as f64 + 1.0)).ln()
+    }
+}
+
+/// Statistics of how words are used in calldata with known types, which rank the layouts of
+/// other calldata by their likelihood when registered as a [`Heuristic`]
+///
+/// Priors are learned from a corpus with one sample per line, consisting of the signature of the
+/// called function and the calldata (including the function selector):
+/// ```text
+/// transfer(address,uint256) 0xa9059cbb000000000000000000000000...
+/// ```
+/// Empty lines and lines starting with `#` are skipped. Priors are saved and loaded in a plain
+/// text format using [`Display`](fmt::Display) and [`FromStr`].
+///
+/// Example
+/// ```rust
+/// let corpus = "f(uint256[]) 0x12345678\
+///     0000000000000000000000000000000000000000000000000000000000000020\
+///     0000000000000000000000000000000000000000000000000000000000000001\
+///     0000000000000000000000000000000000000000000000000000000000000007";
+/// let priors = abi_decoder::Priors::from_corpus(corpus).unwrap();
+/// assert_eq!(priors.lengths.hits, 1);
+/// assert_eq!(priors, priors.to_string().parse().unwrap());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Priors {
+    /// Words that were the length of an array or byte string among the words that could have
+    /// been one
+    pub lengths: Ratio,
+    /// Words that were addresses among the non zero words fitting into 20 bytes, by the number of
+    /// leading zero bytes of the 20 bytes
+    pub addresses: [Ratio; 20],
+    /// Number of arrays and byte strings at each depth (the number of offsets between the
+    /// argument and the value)
+    pub depths: Vec<usize>,
+}
+
+impl Priors {
+    /// Learns the priors from all samples of the corpus
+    pub fn from_corpus(corpus: &str) -> Result<Self, PriorsError> {
+        let mut priors = Priors::default();
+        for (line_index, line) in corpus.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (signature, calldata) = line
+                .split_once(char::is_whitespace)
+                .ok_or(PriorsError::InvalidLine(line_index + 1))?;
+            let param_types =
+                parse_signature(signature).ok_or(PriorsError::InvalidLine(line_index + 1))?;
+            let calldata = hex::decode(calldata.trim().trim_start_matches("0x"))
+                .map_err(|_| PriorsError::InvalidLine(line_index + 1))?;
+            if calldata.len() < 4 {
+                return Err(PriorsError::InvalidSample(line_index + 1));
+            }
+            priors
+                .add_sample(&param_types, &calldata[4..])
+                .ok_or(PriorsError::InvalidSample(line_index + 1))?;
+        }
+        Ok(priors)
+    }
+
+    /// Adds the roles of the words in the encoded arguments (without function selector) of the
+    /// given types to the priors, returns `None` if the data is not an encoding of the types
+    pub fn add_sample(&mut self, param_types: &[ParamType], data: &[u8]) -> Option<()> {
+        ethabi::decode(param_types, data).ok()?;
+        let words = to_words(&data[..data.len() - data.len() % 32]);
+        let mut sample = Sample {
+            words: &words,
+            lengths: vec![None; words.len()],
+            addresses: vec![false; words.len()],
+        };
+        sample.walk_tuple(param_types, 0, 0)?;
+
+        for length in PointerGraph::from_words(&words).lengths() {
+            self.lengths.add(sample.lengths[length.index].is_some());
+        }
+        for depth in sample.lengths.iter().flatten() {
+            if self.depths.len() <= *depth {
+                self.depths.resize(depth + 1, 0);
+            }
+            self.depths[*depth] += 1;
+        }
+        for (word, is_address) in words.iter().zip(&sample.addresses) {
+            if let Some(leading_zeros) = get_address_leading_zeros(&word.bytes) {
+                self.addresses[leading_zeros].add(*is_address);
+            }
+        }
+        Some(())
+    }
+
+    // Log probability of a length at the depth, smoothed so that unseen depths stay possible
+    fn depth_log_probability(&self, depth: usize) -> f64 {
+        let total: usize = self.depths.iter().sum();
+        let count = self.depths.get(depth).copied().unwrap_or(0);
+        ((count as f64 + 1.0) / (total as f64 + self.depths.len() as f64 + 1.0)).ln()
+    }
+
+    fn score_lengths(&self, token: &Token, depth: usize) -> f64 {
+        match token {
+            Token::Bytes(_) | Token::String(_) => {
+                self.lengths.log_odds() + self.depth_log_probability(depth + 1)
+            }
+            Token::Array(tokens) => {
+                self.lengths.log_odds()
+                    + self.depth_log_probability(depth + 1)
+                    + tokens
+                        .iter()
+                        .map(|token| self.score_lengths(token, depth + 1))
+                        .sum::<f64>()
+            }
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                let depth = depth + usize::from(token.is_dynamic());
+                tokens
+                    .iter()
+                    .map(|token| self.score_lengths(token, depth))
+                    .sum()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl Heuristic for Priors {
+    fn score_word(&self, word: &[u8; 32], candidate: &Token) -> i64 {
+        match (candidate, get_address_leading_zeros(word)) {
+            (Token::Address(_), Some(leading_zeros)) => {
+                (self.addresses[leading_zeros].log_odds() * SCALE).round() as i64
+            }
+            _ => 0,
+        }
+    }
+
+    fn score_layout(&self, tokens: &[Token]) -> i64 {
+        let score: f64 = tokens
+            .iter()
+            .map(|token| self.score_lengths(token, 0))
+            .sum();
+        (score * SCALE).round() as i64
+    }
+}
+
+impl fmt::Display for Priors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lengths {} {}", self.lengths.hits, self.lengths.total)?;
+        for (leading_zeros, ratio) in self.addresses.iter().enumerate() {
+            writeln!(
+                f,
+                "addresses {} {} {}",
+                leading_zeros, ratio.hits, ratio.total
+            )?;
+        }
+        for (depth, count) in self.depths.iter().enumerate() {
+            writeln!(f, "depth {} {}", depth, count)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Priors {
+    type Err = PriorsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut priors = Priors::default();
+        for (line_index, line) in s.lines().enumerate() {
+            let invalid_line = PriorsError::InvalidLine(line_index + 1);
+            let mut fields = line.split_whitespace();
+            let Some(key) = fields.next() else {
+                continue;
+            };
+            let values = fields
+                .map(usize::from_str)
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| invalid_line.clone())?;
+            match (key, values.as_slice()) {
+                ("lengths", [hits, total]) if hits <= total => {
+                    priors.lengths = Ratio {
+                        hits: *hits,
+                        total: *total,
+                    };
+                }
+                ("addresses", [leading_zeros, hits, total])
+                    if *leading_zeros < 20 && hits <= total =>
+                {
+                    priors.addresses[*leading_zeros] = Ratio {
+                        hits: *hits,
+                        total: *total,
+                    };
+                }
+                ("depth", [depth, count]) => {
+                    if priors.depths.len() <= *depth {
+                        priors.depths.resize(depth + 1, 0);
+                    }
+                    priors.depths[*depth] = *count;
+                }
+                _ => return Err(invalid_line),
+            }
+        }
+        Ok(priors)
+    }
+}
+
+// Types of the arguments of a function signature such as `transfer(address,uint256)`
+fn parse_signature(signature: &str) -> Option<Vec<ParamType>> {
+    let arguments = &signature[signature.find('(')?..];
+    if arguments == "()" {
+        return Some(Vec::new());
+    }
+    match Reader::read(arguments).ok()? {
+        ParamType::Tuple(param_types) => Some(param_types),
+        _ => None,
+    }
+}
+
+// Number of leading zero bytes of a non zero word that fits into 20 bytes
+fn get_address_leading_zeros(word: &[u8; 32]) -> Option<usize> {
+    if word[..12].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    word[12..].iter().position(|byte| *byte != 0)
+}
+
+// Roles of the words in the encoding of a sample
+struct Sample<'a> {
+    words: &'a [Word],
+    // Depth of the value for words that are lengths
+    lengths: Vec<Option<usize>>,
+    addresses: Vec<bool>,
+}
+
+impl<'a> Sample<'a> {
+    fn walk_tuple(&mut self, param_types: &[ParamType], base: usize, depth: usize) -> Option<()> {
+        let mut head = base;
+        for param_type in param_types {
+            if param_type.is_dynamic() {
+                let offset = self.words.get(head)?.as_usize()?;
+                self.walk_dynamic(param_type, base + offset / 32, depth + 1)?;
+                head += 1;
+            } else {
+                head = self.walk_static(param_type, head)?;
+            }
+        }
+        Some(())
+    }
+
+    fn walk_dynamic(&mut self, param_type: &ParamType, start: usize, depth: usize) -> Option<()> {
+        match param_type {
+            ParamType::Bytes | ParamType::String => {
+                *self.lengths.get_mut(start)? = Some(depth);
+                Some(())
+            }
+            ParamType::Array(element_type) => {
+                let length = self.words.get(start)?.as_usize()?;
+                *self.lengths.get_mut(start)? = Some(depth);
+                let element_types = vec![(**element_type).clone(); length];
+                self.walk_tuple(&element_types, start + 1, depth)
+            }
+            ParamType::FixedArray(element_type, length) => {
+                let element_types = vec![(**element_type).clone(); *length];
+                self.walk_tuple(&element_types, start, depth)
+            }
+            ParamType::Tuple(param_types) => self.walk_tuple(param_types, start, depth),
+            _ => panic!("Static type passed to walk_dynamic"),
+        }
+    }
+
+    // Returns the index of the word after the value
+    fn walk_static(&mut self, param_type: &ParamType, index: usize) -> Option<usize> {
+        match param_type {
+            ParamType::Address => {
+                *self.addresses.get_mut(index)? = true;
+                Some(index + 1)
+            }
+            ParamType::FixedArray(element_type, length) => {
+                let mut index = index;
+                for _ in 0..*length {
+                    index = self.walk_static(element_type, index)?;
+                }
+                Some(index)
+            }
+            ParamType::Tuple(param_types) => {
+                let mut index = index;
+                for param_type in param_types {
+                    index = self.walk_static(param_type, index)?;
+                }
+                Some(index)
+            }
+            _ => Some(index + 1),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ethabi::param_type::ParamType;
+use ethabi::token::Token;
+
+use super::empty_values::is_empty_value;
+use super::error::QueryError;
+use super::fixed_arrays::expand;
+use super::parse_token::get_param_type;
+use super::type_hints::{TypeHint, TypeHints};
+use super::visit::{visit_token, visit_tokens};
+
+/// Path expression selecting values from the decoded arguments
+///
+/// A query starts with `$`, which stands for the arguments, followed by steps that each select
+/// values from the ones the previous step selected:
+/// - `[1]` or `.1` selects the second argument, element or field
+/// - `[*]` or `.*` selects all arguments, elements or fields
+/// - `[?address]` selects the arguments, elements or fields of the given type, written like a
+///   type hint (i.e. `[?(uint256,bytes)[]]` or `[?(address,?)]`, where `?` stands for any type).
+///   Empty values are of every dynamic type.
+/// - `..` selects the values together with everything nested in them (i.e. `$..[?address]`
+///   selects all addresses)
+///
+/// Example
+/// ```rust
+/// use abi_decoder::Query;
+/// use ethabi::Token;
+///
+/// // Two tuples of an address and calldata
+/// let tokens = vec![Token::Array(vec![
+///     Token::Tuple(vec![Token::Address([1; 20].into()), Token::Bytes(vec![1, 2])]),
+///     Token::Tuple(vec![Token::Address([2; 20].into()), Token::Bytes(vec![3])]),
+/// ])];
+/// let query: Query = "$[0][*].1".parse().unwrap();
+/// let selected = query.select(&tokens);
+/// assert_eq!(selected[0], (vec![0, 0, 1], &Token::Bytes(vec![1, 2])));
+/// assert_eq!(selected[1], (vec![0, 1, 1], &Token::Bytes(vec![3])));
+///
+/// let addresses: Query = "$..[?address]".parse().unwrap();
+/// assert_eq!(addresses.select(&tokens).len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query(Vec<Step>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Index(usize),
+    All,
+    Type(TypeHint),
+    Descendants,
+}
+
+// Value selected by a step, the arguments themselves have an empty path and no token
+type Selected<'a> = (Vec<usize>, Option<&'a Token>);
+
+impl Query {
+    /// Selects the values the query matches together with their paths (the index of the argument
+    /// followed by the indices of the elements and fields leading to the value), in the order
+    /// they appear in the arguments
+    pub fn select<'a>(&self, tokens: &'a [Token]) -> Vec<(Vec<usize>, &'a Token)> {
+        let mut selected: Vec<Selected<'a>> = vec![(Vec::new(), None)];
+        for step in &self.0 {
+            selected = selected
+                .into_iter()
+                .flat_map(|(path, token)| apply_step(step, path, token, tokens))
+                .collect();
+        }
+        // Values nested in several selected values are selected once for each of them
+        let mut selected: Vec<_> = selected
+            .into_iter()
+            .filter_map(|(path, token)| Some((path, token?)))
+            .collect();
+        selected.sort_by(|(a, _), (b, _)| a.cmp(b));
+        selected.dedup_by(|(a, _), (b, _)| a == b);
+        selected
+    }
+}
+
+fn apply_step<'a>(
+    step: &Step,
+    path: Vec<usize>,
+    token: Option<&'a Token>,
+    arguments: &'a [Token],
+) -> Vec<Selected<'a>> {
+    if let Step::Descendants = step {
+        // The arguments are not a token, so visiting them starts with their elements
+        let mut selected = match token {
+            Some(_) => Vec::new(),
+            None => vec![(Vec::new(), None)],
+        };
+        let mut select = |relative_path: &[usize], token: &'a Token| {
+            selected.push(([path.as_slice(), relative_path].concat(), Some(token)));
+            true
+        };
+        match token {
+            Some(token) => visit_token(token, &mut select),
+            None => visit_tokens(arguments, &mut select),
+        }
+        return selected;
+    }
+    let children = match token {
+        None => arguments,
+        Some(Token::Tuple(tokens) | Token::Array(tokens) | Token::FixedArray(tokens)) => tokens,
+        Some(_) => return Vec::new(),
+    };
+    children
+        .iter()
+        .enumerate()
+        .filter(|(index, child)| match step {
+            Step::Index(selected) => index == selected,
+            Step::Type(hint) => has_type(child, hint),
+            _ => true,
+        })
+        .map(|(index, child)| ([path.as_slice(), &[index]].concat(), Some(child)))
+        .collect()
+}
+
+fn has_type(token: &Token, hint: &TypeHint) -> bool {
+    match (expand(hint), token) {
+        (TypeHint::Unknown, _) => true,
+        (TypeHint::Type(ParamType::Bytes | ParamType::String) | TypeHint::Array(_), _)
+            if is_empty_value(token) =>
+        {
+            true
+        }
+        (TypeHint::Type(param_type), _) => get_param_type(token) == param_type,
+        (TypeHint::Array(element), Token::Array(tokens)) => {
+            tokens.iter().all(|token| has_type(token, &element))
+        }
+        (TypeHint::FixedArray(element, length), Token::FixedArray(tokens)) => {
+            tokens.len() == length && tokens.iter().all(|token| has_type(token, &element))
+        }
+        (TypeHint::Tuple(hints), Token::Tuple(tokens)) => {
+            tokens.len() == hints.len()
+                && tokens
+                    .iter()
+                    .zip(&hints)
+                    .all(|(token, hint)| has_type(token, hint))
+        }
+        _ => false,
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$")?;
+        for step in &self.0 {
+            match step {
+                Step::Index(index) => write!(f, "[{}]", index)?,
+                Step::All => write!(f, "[*]")?,
+                Step::Type(hint) => write!(f, "[?{}]", hint)?,
+                Step::Descendants => write!(f, "..")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let query: String = s.split_whitespace().collect();
+        let mut parser = QueryParser {
+            query: &query,
+            position: 0,
+        };
+        parser.expect(b'$')?;
+        let mut steps = Vec::new();
+        while let Some(byte) = parser.peek() {
+            parser.position += 1;
+            match byte {
+                b'.' if parser.peek() == Some(b'.') => {
+                    parser.position += 1;
+                    steps.push(Step::Descendants);
+                    // `$..[0]` and `$..0` select the same
+                    if parser.peek() != Some(b'[') {
+                        steps.push(parser.parse_field()?);
+                    }
+                }
+                b'.' => steps.push(parser.parse_field()?),
+                b'[' => {
+                    steps.push(parser.parse_selector()?);
+                    parser.expect(b']')?;
+                }
+                _ => return Err(QueryError::Syntax(parser.position - 1)),
+            }
+        }
+        Ok(Query(steps))
+    }
+}
+
+struct QueryParser<'a> {
+    query: &'a str,
+    position: usize,
+}
+
+impl<'a> QueryParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.query.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), QueryError> {
+        if self.peek() != Some(byte) {
+            return Err(QueryError::Syntax(self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Index or `*` after a dot
+    fn parse_field(&mut self) -> Result<Step, QueryError> {
+        if self.peek() == Some(b'*') {
+            self.position += 1;
+            return Ok(Step::All);
+        }
+        self.parse_index()
+    }
+
+    // Index, `*` or type filter inside brackets
+    fn parse_selector(&mut self) -> Result<Step, QueryError> {
+        match self.peek() {
+            Some(b'*') => {
+                self.position += 1;
+                Ok(Step::All)
+            }
+            Some(b'?') => {
+                self.position += 1;
+                self.parse_type()
+            }
+            _ => self.parse_index(),
+        }
+    }
+
+    fn parse_index(&mut self) -> Result<Step, QueryError> {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.query[start..self.position]
+            .parse()
+            .map(Step::Index)
+            .map_err(|_| QueryError::Syntax(start))
+    }
+
+    // Takes the type up to the bracket closing the filter, types contain brackets of their own
+    fn parse_type(&mut self) -> Result<Step, QueryError> {
+        let start = self.position;
+        let mut depth = 0;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'[' => depth += 1,
+                b']' if depth == 0 => break,
+                b']' => depth -= 1,
+                _ => {}
+            }
+            self.position += 1;
+        }
+        let name = &self.query[start..self.position];
+        let TypeHints(mut hints) = format!("({})", name)
+            .parse()
+            .map_err(|_| QueryError::InvalidType(name.to_string()))?;
+        if hints.len() != 1 {
+            return Err(QueryError::InvalidType(name.to_string()));
+        }
+        Ok(Step::Type(hints.remove(0)))
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ethabi::param_type::{ParamType, Writer};
+use ethereum_types::H256;
+
+use super::error::{SignatureError, TypeHintsError};
+use super::type_hints::{TypeHint, TypeHints};
+
+/// Known signatures of functions, errors and events by their selector
+///
+/// The selector of functions and errors is the first 4 bytes of the hash of their signature,
+/// events are identified by the whole hash (the first topic of their logs). Selectors of
+/// different signatures can collide, so all signatures with the selector are kept in the order
+/// they were added.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::Signatures;
+///
+/// let signatures: Signatures = "transfer(address, uint)\nTransfer(address,address,uint256)"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(signatures.functions([0xa9, 0x05, 0x9c, 0xbb]), ["transfer(address,uint256)"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Signatures {
+    functions: HashMap<[u8; 4], Vec<Signature>>,
+    events: HashMap<H256, Vec<Signature>>,
+}
+
+// Signature written the way its hash is computed, together with the types of its arguments
+#[derive(Debug, Clone, PartialEq)]
+struct Signature {
+    text: String,
+    param_types: Vec<ParamType>,
+}
+
+impl Signatures {
+    /// Adds the signature (i.e. `transfer(address,uint256)`), which is normalized to the form its
+    /// hash is computed from (i.e. `uint` is `uint256` and whitespace is removed)
+    pub fn add(&mut self, signature: &str) -> Result<(), SignatureError> {
+        let signature: String = signature.split_whitespace().collect();
+        let syntax_error = || SignatureError::Syntax(signature.clone());
+        let (name, arguments) = signature.split_at(signature.find('(').ok_or_else(syntax_error)?);
+        if name.is_empty() {
+            return Err(syntax_error());
+        }
+        let param_types = match arguments.parse::<TypeHints>() {
+            Ok(TypeHints(hints)) => hints
+                .into_iter()
+                .map(|hint| match hint {
+                    TypeHint::Type(param_type) => Ok(param_type),
+                    _ => Err(syntax_error()),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(TypeHintsError::UnknownType(name)) => {
+                return Err(SignatureError::UnknownType(name))
+            }
+            Err(TypeHintsError::Syntax(_)) => return Err(syntax_error()),
+        };
+        let hash = ethabi::long_signature(name, &param_types);
+        let types: Vec<String> = param_types.iter().map(Writer::write).collect();
+        let signature = Signature {
+            text: format!("{}({})", name, types.join(",")),
+            param_types,
+        };
+        let selector = hash[..4].try_into().expect("Hashes are 32 bytes");
+        for signatures in [
+            self.functions.entry(selector).or_default(),
+            self.events.entry(hash).or_default(),
+        ] {
+            if !signatures.contains(&signature) {
+                signatures.push(signature.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Signatures of the functions and errors with the selector
+    pub fn functions(&self, selector: [u8; 4]) -> Vec<&str> {
+        get_texts(self.functions.get(&selector))
+    }
+
+    /// Signatures of the events whose logs have the topic as their first topic
+    pub fn events(&self, topic: H256) -> Vec<&str> {
+        get_texts(self.events.get(&topic))
+    }
+
+    /// Types of the arguments of the functions and errors with the selector, together with their
+    /// signatures
+    pub(crate) fn function_types(&self, selector: [u8; 4]) -> Vec<(&str, &[ParamType])> {
+        self.functions
+            .get(&selector)
+            .into_iter()
+            .flatten()
+            .map(|signature| (signature.text.as_str(), signature.param_types.as_slice()))
+            .collect()
+    }
+}
+
+fn get_texts(signatures: Option<&Vec<Signature>>) -> Vec<&str> {
+    signatures
+        .into_iter()
+        .flatten()
+        .map(|signature| signature.text.as_str())
+        .collect()
+}
+
+/// Reads one signature per line, skipping empty lines and comments starting with `#`
+impl FromStr for Signatures {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut signatures = Signatures::default();
+        for line in s.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                signatures.add(line)?;
+            }
+        }
+        Ok(signatures)
+    }
+}
@@ -1,15 +1,38 @@
 use super::*;
 use crate::{
-    test_utils::{address_token_from_string, fixed_bytes_token_from_string, parameterize},
+    test_utils::{
+        address_token_from_string, bytes_token_from_string, fixed_bytes_token_from_string,
+        parameterize, reencode_with_trailing_data,
+    },
     utils::print_parse_tree,
 };
-use ethabi::Token;
+use annotation::{annotate_words, get_token, WordRole};
+use decoded_value::{DecodedValue, Resolution, WordType};
+use disallowed_markers::DisallowedMarkers;
+use error::{PinError, QueryError, TypeHintsError};
+use ethabi::{ParamType, Token};
 use ethereum_types::U256;
-use hex;
+use layout_engine::LayoutEngine;
+use parse_context::{Budget, ParseContext};
+use parse_marker::MarkerType;
+use pins::{Pin, PinnedRole};
+use query::Query;
+use type_hints::TypeHints;
+use validation::{DeviationKind, EncodingDeviation};
 
 parameterize!(
     test_same_encoding,
     [
+        // The tail of the struct holds the bytes followed by the other fields and their tails
+        (
+            struct_with_bytes_followed_by_fields,
+            vec![Token::Tuple(vec![
+                bytes_token_from_string("abcdef"),
+                Token::Uint(U256::from(7)),
+                Token::Array(vec![Token::Uint(U256::from(8)), Token::Uint(U256::from(9))]),
+                bytes_token_from_string("0123456789"),
+            ])]
+        ),
         (
             address_bytes_and_uint256,
             vec![
@@ -289,79 +312,2487 @@ parameterize!(
     ]
 );
 
-fn test_same_encoding(arguments: Vec<Token>) {
-    println!("Arguments:");
-    for argument in &arguments {
-        print_parse_tree(argument, 0);
-    }
-    println!();
-    println!();
-    let encoded_arguments = add_padding(&hex::encode(ethabi::encode(&arguments)));
+parameterize!(
+    test_detects_domain_tag,
+    [
+        (
+            opensea_tag_after_array,
+            (
+                vec![Token::Array(vec![
+                    Token::Uint(U256::from(128)),
+                    Token::Uint(U256::from(1024)),
+                ])],
+                "360c6ebe",
+                Some("opensea.io")
+            )
+        ),
+        (
+            unknown_padded_tag_after_bytes,
+            (
+                vec![
+                    address_token_from_string("0x7C07F7aBe10CE8e33DC6C5aD68FE033085256A84"),
+                    Token::Bytes(vec![1, 2, 3]),
+                ],
+                "0000000000000000000000000000000000000000000000000000000012345678",
+                None
+            )
+        ),
+        (
+            // Together with the first word of the padding the data is an array of two numbers
+            opensea_tag_after_bytes_of_array_length,
+            (
+                vec![Token::Bytes(b"ab".to_vec())],
+                "0000000000000000000000000000000000000000000000000000000000000000\
+                 00000000000000000000000000000000000000000000000000000000360c6ebe",
+                Some("opensea.io")
+            )
+        ),
+    ]
+);
 
-    let chunks = chunk_data(&encoded_arguments);
-    for (i, chunk) in chunks.iter().enumerate() {
-        println!(
-            "{}: {} - {}",
-            i,
-            chunk,
-            u64::from_str_radix(chunk.trim_start_matches('0'), 16).unwrap_or(0)
-        );
-    }
-    let tokens = decode_chunks(chunks);
-    for token in &tokens {
-        print_parse_tree(token, 0);
-    }
-    assert_eq!(tokens, arguments);
-}
+parameterize!(
+    test_non_canonical_encoding,
+    [
+        (
+            arrays_with_tails_in_reverse_order,
+            (
+                vec![160, 64, 2, 3, 4, 2, 1, 2],
+                vec![
+                    Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ]
+            )
+        ),
+        (
+            arrays_sharing_one_tail,
+            (
+                vec![64, 64, 2, 3, 4],
+                vec![
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ]
+            )
+        ),
+        (
+            array_elements_in_reverse_order,
+            (
+                vec![32, 2, 160, 64, 2, 3, 4, 2, 1, 2],
+                vec![Token::Array(vec![
+                    Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ])]
+            )
+        ),
+        (
+            bytes_followed_by_gap,
+            (
+                vec![64, 160, 31, 0x0700, 9, 2, 3, 4],
+                vec![
+                    Token::Bytes(words_to_bytes(&[0x0700])[..31].to_vec()),
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ]
+            )
+        ),
+        (
+            bytes_followed_by_two_words,
+            (
+                vec![64, 192, 31, 0x0700, 5, 6, 2, 3, 4],
+                vec![
+                    Token::Bytes(words_to_bytes(&[0x0700])[..31].to_vec()),
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ]
+            )
+        ),
+        (
+            struct_with_gap_after_first_bytes,
+            (
+                vec![32, 64, 160, 31, 0x0700, 9, 31, 0x0800],
+                vec![Token::Tuple(vec![
+                    Token::Bytes(words_to_bytes(&[0x0700])[..31].to_vec()),
+                    Token::Bytes(words_to_bytes(&[0x0800])[..31].to_vec()),
+                ])]
+            )
+        ),
+    ]
+);
 
-fn test_can_reencode_with_added_data_at_the_end(arguments: Vec<Token>) {
-    println!("Arguments:");
-    for argument in &arguments {
-        print_parse_tree(argument, 0);
-    }
-    println!();
-    println!();
-    let encoded_arguments = add_padding(&hex::encode(ethabi::encode(&arguments)));
+parameterize!(
+    test_non_canonical_option_decodes_canonical_data,
+    [
+        // The array fills all but the last word with offsets, which out of order offsets allow
+        (
+            array_as_long_as_its_tail,
+            vec![Token::Tuple(vec![Token::Array(vec![
+                Token::Uint(U256::from(32)),
+                Token::Uint(U256::from(274)),
+            ])])]
+        ),
+        (
+            bytes_and_array_of_two_bytes,
+            vec![
+                Token::Bytes(vec![1, 2, 3]),
+                Token::Array(vec![Token::Bytes(vec![4]), Token::Bytes(vec![5, 6])]),
+            ]
+        ),
+    ]
+);
 
-    let mut chunks = chunk_data(&encoded_arguments);
-    let extra_data = &"01".repeat(32);
-    chunks.push(extra_data);
-    for (i, chunk) in chunks.iter().enumerate() {
-        println!(
-            "{}: {} - {}",
-            i,
-            chunk,
-            u64::from_str_radix(chunk.trim_start_matches('0'), 16).unwrap_or(0)
-        );
-    }
-    let tokens = decode_chunks(chunks);
-    for token in &tokens {
-        print_parse_tree(token, 0);
-    }
-    // assert_eq!(tokens, arguments);
-}
+parameterize!(
+    test_validation,
+    [
+        (
+            canonical_encoding,
+            (
+                vec![
+                    ParamType::Address,
+                    ParamType::Bytes,
+                    ParamType::Array(Box::new(ParamType::Uint(256))),
+                ],
+                ethabi::encode(&[
+                    address_token_from_string("0x7C07F7aBe10CE8e33DC6C5aD68FE033085256A84"),
+                    Token::Bytes(vec![1, 2, 3]),
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ]),
+                vec![]
+            )
+        ),
+        (
+            tails_in_reverse_order,
+            (
+                vec![
+                    ParamType::Array(Box::new(ParamType::Uint(256))),
+                    ParamType::Array(Box::new(ParamType::Uint(256))),
+                ],
+                words_to_bytes(&[160, 64, 2, 3, 4, 2, 1, 2]),
+                vec![
+                    EncodingDeviation {
+                        word_index: 0,
+                        kind: DeviationKind::NonMinimalOffset {
+                            expected: 2,
+                            actual: 5,
+                        },
+                    },
+                    EncodingDeviation {
+                        word_index: 1,
+                        kind: DeviationKind::NonMinimalOffset {
+                            expected: 8,
+                            actual: 2,
+                        },
+                    },
+                ]
+            )
+        ),
+        (
+            gap_before_tail,
+            (
+                vec![ParamType::Array(Box::new(ParamType::Uint(256)))],
+                words_to_bytes(&[64, 0, 1, 5]),
+                vec![
+                    EncodingDeviation {
+                        word_index: 0,
+                        kind: DeviationKind::NonMinimalOffset {
+                            expected: 1,
+                            actual: 2,
+                        },
+                    },
+                    EncodingDeviation {
+                        word_index: 1,
+                        kind: DeviationKind::UnreferencedGap { length: 1 },
+                    },
+                ]
+            )
+        ),
+        (
+            dirty_address_and_padding,
+            (
+                vec![ParamType::Address, ParamType::Bytes],
+                with_byte(
+                    with_byte(
+                        ethabi::encode(&[
+                            address_token_from_string("0x7C07F7aBe10CE8e33DC6C5aD68FE033085256A84"),
+                            Token::Bytes(vec![1, 2, 3]),
+                        ]),
+                        0,
+                        0xff
+                    ),
+                    3 * 32 + 31,
+                    0xff
+                ),
+                vec![
+                    EncodingDeviation {
+                        word_index: 0,
+                        kind: DeviationKind::DirtyHighBits,
+                    },
+                    EncodingDeviation {
+                        word_index: 3,
+                        kind: DeviationKind::DirtyPadding,
+                    },
+                ]
+            )
+        ),
+        (
+            offset_out_of_bounds,
+            (
+                vec![ParamType::Bytes],
+                words_to_bytes(&[320, 1]),
+                vec![
+                    EncodingDeviation {
+                        word_index: 0,
+                        kind: DeviationKind::InvalidData,
+                    },
+                    EncodingDeviation {
+                        word_index: 1,
+                        kind: DeviationKind::UnreferencedGap { length: 1 },
+                    },
+                ]
+            )
+        ),
+    ]
+);
 
-fn test_different_encoding(arguments_and_expected_tokens: (Vec<Token>, Vec<Token>)) {
-    let (arguments, expected_tokens) = arguments_and_expected_tokens;
-    for argument in &arguments {
-        print_parse_tree(argument, 0);
-    }
-    println!();
-    println!();
-    let encoded_arguments = add_padding(&hex::encode(ethabi::encode(&arguments)));
+parameterize!(
+    test_strict_decoding,
+    [
+        (canonical_array, (vec![32, 2, 3, 4], 0, true)),
+        (shared_tail, (vec![64, 64, 2, 3, 4], 0, false)),
+        // Incomplete data after a complete encoding is returned as trailing data
+        (array_and_unaligned_data, (vec![32, 2, 3, 4, 5], 4, true)),
+        (unaligned_data, (vec![1, 2, 3], 4, false)),
+    ]
+);
 
-    let chunks = chunk_data(&encoded_arguments);
-    for (i, chunk) in chunks.iter().enumerate() {
-        println!(
-            "{}: {} - {}",
-            i,
-            chunk,
-            u64::from_str_radix(chunk.trim_start_matches('0'), 16).unwrap_or(0)
-        );
-    }
-    let tokens = decode_chunks(chunks);
-    for token in &tokens {
-        print_parse_tree(token, 0);
-    }
-    assert_eq!(tokens, expected_tokens);
+parameterize!(
+    test_decodes_deep_nesting_on_small_stack,
+    [(nested_arrays_256_levels, 256)]
+);
+
+parameterize!(
+    test_decoding_limits,
+    [
+        (
+            nested_arrays_below_max_depth,
+            (
+                vec![32, 2, 64, 160, 2, 1, 2, 2, 3, 4],
+                DecodingOptions {
+                    max_depth: Some(1),
+                    ..Default::default()
+                },
+                vec![Token::Array(vec![
+                    Token::Bytes(words_to_bytes(&[2, 1, 2])),
+                    Token::Bytes(words_to_bytes(&[2, 3, 4])),
+                ])]
+            )
+        ),
+        (
+            no_steps_left,
+            (
+                vec![32, 2, 3, 4],
+                DecodingOptions {
+                    max_steps: Some(0),
+                    ..Default::default()
+                },
+                vec![Token::Bytes(words_to_bytes(&[2, 3, 4]))]
+            )
+        ),
+        (
+            no_time_left,
+            (
+                vec![32, 2, 3, 4],
+                DecodingOptions {
+                    max_duration: Some(std::time::Duration::ZERO),
+                    ..Default::default()
+                },
+                vec![Token::Bytes(words_to_bytes(&[2, 3, 4]))]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_disallowed_markers_do_not_depend_on_insertion_order,
+    [
+        (ascending, (vec![1, 4, 7], 5)),
+        (descending, (vec![7, 4, 1], 5)),
+        (repeated_index, (vec![3, 0, 3], 2)),
+    ]
+);
+
+parameterize!(
+    test_constraint_solver_finds_layout,
+    [
+        // The greedy search never decodes arrays of a single static tuple
+        (
+            array_of_single_static_tuple,
+            vec![Token::Array(vec![Token::Tuple(vec![
+                Token::Uint(U256::from(5)),
+                Token::Uint(U256::from(6)),
+                Token::Uint(U256::from(7)),
+            ])])]
+        ),
+        (
+            array_of_two_uint256,
+            vec![Token::Array(vec![
+                Token::Uint(U256::from(2)),
+                Token::Uint(U256::from(3)),
+            ])]
+        ),
+        (
+            bytes_and_array_of_bytes,
+            vec![
+                Token::Bytes(vec![1, 2, 3]),
+                Token::Array(vec![Token::Bytes(vec![4]), Token::Bytes(vec![])]),
+            ]
+        ),
+        (
+            nested_arrays,
+            vec![Token::Array(vec![
+                Token::Array(vec![Token::Uint(U256::from(1))]),
+                Token::Array(vec![Token::Uint(U256::from(2)), Token::Uint(U256::from(3)),]),
+            ])]
+        ),
+    ]
+);
+
+parameterize!(
+    test_pointer_graph,
+    [
+        (
+            graph_of_array,
+            (
+                vec![32, 2, 3, 4],
+                vec![(0, 0, 1)],
+                vec![0, 1],
+                vec![0, 1, 2]
+            )
+        ),
+        (
+            graph_of_two_arrays,
+            (
+                vec![64, 160, 2, 1, 2, 2, 3, 4],
+                vec![(0, 0, 2), (1, 0, 5)],
+                vec![0, 1, 2, 3, 4, 5],
+                vec![0, 2, 3, 5, 6]
+            )
+        ),
+        // Offsets in the tail are relative to the start of the tail
+        (
+            graph_of_nested_offset,
+            (
+                vec![32, 32, 2, 3],
+                vec![(0, 0, 1), (1, 1, 2)],
+                vec![0, 1],
+                vec![0, 1, 2, 3]
+            )
+        ),
+        (
+            graph_of_static_values,
+            (vec![7, 33, 5], vec![], vec![], vec![0])
+        ),
+    ]
+);
+
+parameterize!(
+    test_heuristics,
+    [
+        (
+            address_with_leading_zero_is_number,
+            (
+                vec![address_token_from_string(
+                    "0x000a0b0c0d0e0f000000000000000000000000ff"
+                )],
+                heuristic::Heuristics::default(),
+                vec![Token::Uint(
+                    U256::from_str_radix("a0b0c0d0e0f000000000000000000000000ff", 16).unwrap()
+                )]
+            )
+        ),
+        (
+            address_with_leading_zero_in_address_book,
+            (
+                vec![address_token_from_string(
+                    "0x000a0b0c0d0e0f000000000000000000000000ff"
+                )],
+                heuristic::Heuristics::default().with(AddressBook(vec![
+                    "000a0b0c0d0e0f000000000000000000000000ff".parse().unwrap()
+                ])),
+                vec![address_token_from_string(
+                    "0x000a0b0c0d0e0f000000000000000000000000ff"
+                )]
+            )
+        ),
+        (
+            no_heuristics_decode_numbers,
+            (
+                vec![
+                    address_token_from_string("0x7C07F7aBe10CE8e33DC6C5aD68FE033085256A84"),
+                    fixed_bytes_token_from_string("7C07F7aBe10CE8e33DC6C5aD68FE033085256A"),
+                ],
+                heuristic::Heuristics::empty(),
+                vec![
+                    Token::Uint(
+                        U256::from_str_radix("7C07F7aBe10CE8e33DC6C5aD68FE033085256A84", 16)
+                            .unwrap()
+                    ),
+                    Token::Uint(U256::from_big_endian(&ethabi::encode(&[
+                        fixed_bytes_token_from_string("7C07F7aBe10CE8e33DC6C5aD68FE033085256A")
+                    ]))),
+                ]
+            )
+        ),
+        (
+            empty_value_is_array,
+            (
+                vec![Token::Bytes(vec![])],
+                heuristic::Heuristics::default(),
+                vec![Token::Array(vec![])]
+            )
+        ),
+        (
+            empty_value_is_bytes_with_layout_heuristic,
+            (
+                vec![Token::Bytes(vec![])],
+                heuristic::Heuristics::default().with(EmptyBytes),
+                vec![Token::Bytes(vec![])]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_priors,
+    [
+        (
+            addresses_with_leading_zero_learned,
+            (
+                vec![
+                    (
+                        "f(address)",
+                        vec![address_token_from_string(
+                            "0x00ab0b0c0d0e0f000000000000000000000000ff"
+                        )]
+                    ),
+                    (
+                        "f(address)",
+                        vec![address_token_from_string(
+                            "0x00cd0b0c0d0e0f0000000000000000000000ffff"
+                        )]
+                    ),
+                ],
+                vec![address_token_from_string(
+                    "0x001234567890abcdef1234567890abcdef123456"
+                )],
+                vec![address_token_from_string(
+                    "0x001234567890abcdef1234567890abcdef123456"
+                )]
+            )
+        ),
+        (
+            large_numbers_learned,
+            (
+                vec![
+                    (
+                        "f(uint160)",
+                        vec![Token::Uint(
+                            U256::from_str_radix("7C07F7aBe10CE8e33DC6C5aD68FE033085256A84", 16)
+                                .unwrap()
+                        )]
+                    ),
+                    (
+                        "f(uint160)",
+                        vec![Token::Uint(
+                            U256::from_str_radix("ab07F7aBe10CE8e33DC6C5aD68FE033085256A84", 16)
+                                .unwrap()
+                        )]
+                    ),
+                ],
+                vec![address_token_from_string(
+                    "0x7C07F7aBe10CE8e33DC6C5aD68FE033085256A84"
+                )],
+                vec![Token::Uint(
+                    U256::from_str_radix("7C07F7aBe10CE8e33DC6C5aD68FE033085256A84", 16).unwrap()
+                )]
+            )
+        ),
+        // Without nested arrays in the corpus the flat layout is more likely
+        (
+            flat_arrays_learned,
+            (
+                vec![(
+                    "f(uint256[])",
+                    vec![Token::Array(vec![Token::Uint(U256::from(7))])]
+                )],
+                vec![Token::Array(vec![Token::Array(vec![])])],
+                vec![Token::Array(vec![Token::Tuple(vec![
+                    Token::Uint(U256::from(32)),
+                    Token::Uint(U256::from(0)),
+                ])])]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_falls_back_to_round_trip_layout,
+    [
+        (
+            first_layout_round_trips,
+            (
+                vec![32, 1, 7],
+                vec![vec![Token::Array(vec![Token::Uint(U256::from(7))])]],
+                vec![Token::Array(vec![Token::Uint(U256::from(7))])],
+                true,
+                false
+            )
+        ),
+        (
+            next_layout_round_trips,
+            (
+                vec![1, 2, 3],
+                vec![
+                    vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))],
+                    vec![
+                        Token::Uint(U256::from(1)),
+                        Token::Uint(U256::from(2)),
+                        Token::Uint(U256::from(3))
+                    ],
+                ],
+                vec![
+                    Token::Uint(U256::from(1)),
+                    Token::Uint(U256::from(2)),
+                    Token::Uint(U256::from(3))
+                ],
+                true,
+                false
+            )
+        ),
+        (
+            no_layout_round_trips,
+            (
+                vec![1, 2, 3],
+                vec![vec![Token::Uint(U256::from(1))]],
+                vec![Token::Uint(U256::from(1))],
+                false,
+                // Decoding the data as a uint256 gives the same token, the rest is unreferenced
+                true
+            )
+        ),
+        (
+            layout_misreads_data,
+            (
+                vec![32, 2, 7, 8],
+                vec![vec![Token::Array(vec![Token::Uint(U256::from(7))])]],
+                vec![Token::Array(vec![Token::Uint(U256::from(7))])],
+                false,
+                false
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_greedy_search_round_trips,
+    [
+        (
+            tuple_of_bytes,
+            vec![Token::Tuple(vec![Token::Bytes(vec![1, 2, 3])])]
+        ),
+        (
+            array_of_tuples_of_bytes,
+            vec![
+                Token::Array(vec![
+                    Token::Tuple(vec![Token::Bytes(vec![1])]),
+                    Token::Tuple(vec![Token::Bytes(vec![2])])
+                ]),
+                Token::Uint(U256::from(3))
+            ]
+        ),
+        (
+            tuple_of_array,
+            vec![
+                Token::Uint(U256::from(3)),
+                Token::Tuple(vec![Token::Array(vec![Token::Uint(U256::from(4))])])
+            ]
+        ),
+    ]
+);
+
+parameterize!(
+    test_greedy_search_finds_round_trip_layout,
+    [
+        (
+            shared_tail_read_once,
+            (
+                vec![64, 64, 2, 3, 4],
+                vec![
+                    Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                    Token::Uint(U256::from(64)),
+                ]
+            )
+        ),
+        (
+            reversed_tails_read_as_numbers,
+            (
+                vec![160, 64, 2, 3, 4, 2, 1, 2],
+                vec![
+                    Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+                    Token::Uint(U256::from(64)),
+                    Token::Uint(U256::from(2)),
+                    Token::Uint(U256::from(3)),
+                    Token::Uint(U256::from(4)),
+                ]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_explanation,
+    [
+        (
+            array_with_trailing_data,
+            (
+                vec![Token::Array(vec![
+                    Token::Uint(U256::from(3)),
+                    Token::Uint(U256::from(4))
+                ])],
+                "360c6ebe",
+                vec![
+                    Some(explain::Rule::Offset { target: 1 }),
+                    Some(explain::Rule::StaticArrayLength {
+                        elements: 2,
+                        element_size: 1
+                    }),
+                    Some(explain::Rule::Value(ParamType::Uint(256))),
+                    Some(explain::Rule::Value(ParamType::Uint(256))),
+                    None,
+                ],
+                vec![]
+            )
+        ),
+        (
+            explained_tuple_of_bytes,
+            (
+                vec![Token::Tuple(vec![Token::Bytes(vec![1, 2, 3])])],
+                "",
+                vec![
+                    Some(explain::Rule::Offset { target: 1 }),
+                    Some(explain::Rule::Offset { target: 2 }),
+                    Some(explain::Rule::BytesLength { bytes: 3 }),
+                    Some(explain::Rule::BytesContent { length_word: 2 }),
+                ],
+                vec![]
+            )
+        ),
+        // The greedy search does not find the array of a single static tuple
+        (
+            offset_to_static_tuple_rejected,
+            (
+                vec![
+                    Token::Array(vec![Token::Tuple(vec![
+                        Token::Uint(U256::from(7)),
+                        Token::Uint(U256::from(8))
+                    ])]),
+                    Token::Bytes(vec![1, 2, 3])
+                ],
+                "",
+                vec![Some(explain::Rule::Value(ParamType::Uint(256))); 7],
+                vec![
+                    (
+                        0,
+                        explain::Rejection {
+                            kind: explain::RuleKind::Offset,
+                            reason: explain::RejectionReason::StaticTuple
+                        }
+                    ),
+                    (
+                        1,
+                        explain::Rejection {
+                            kind: explain::RuleKind::Offset,
+                            reason: explain::RejectionReason::Unparsable
+                        }
+                    ),
+                ]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_word_annotations,
+    [
+        (
+            uint_and_bytes,
+            (
+                vec![Token::Uint(U256::from(7)), Token::Bytes(vec![1; 40])],
+                vec![7, 0x40, 40, 1, 2],
+                vec![
+                    (WordRole::HeadValue, vec![0]),
+                    (WordRole::Offset { target: 2 }, vec![1]),
+                    (WordRole::Length { region: 3..5 }, vec![1]),
+                    (
+                        WordRole::BytesData {
+                            length_word: 2,
+                            padding: 0
+                        },
+                        vec![1]
+                    ),
+                    (
+                        WordRole::BytesData {
+                            length_word: 2,
+                            padding: 24
+                        },
+                        vec![1]
+                    ),
+                ]
+            )
+        ),
+        (
+            array_with_trailing_word,
+            (
+                vec![Token::Array(vec![
+                    Token::Uint(U256::from(3)),
+                    Token::Uint(U256::from(4))
+                ])],
+                vec![0x20, 2, 3, 4, 5],
+                vec![
+                    (WordRole::Offset { target: 1 }, vec![0]),
+                    (WordRole::Length { region: 2..4 }, vec![0]),
+                    (WordRole::HeadValue, vec![0, 0]),
+                    (WordRole::HeadValue, vec![0, 1]),
+                    (WordRole::Trailing, vec![]),
+                ]
+            )
+        ),
+        (
+            annotated_gap_before_tail,
+            (
+                vec![Token::Bytes(vec![1; 3])],
+                vec![0x40, 0, 3, 1],
+                vec![
+                    (WordRole::Offset { target: 2 }, vec![0]),
+                    (WordRole::Padding, vec![]),
+                    (WordRole::Length { region: 3..4 }, vec![0]),
+                    (
+                        WordRole::BytesData {
+                            length_word: 2,
+                            padding: 29
+                        },
+                        vec![0]
+                    ),
+                ]
+            )
+        ),
+        (
+            dynamic_tuple_with_array,
+            (
+                vec![Token::Tuple(vec![
+                    Token::Uint(U256::from(1)),
+                    Token::Array(vec![Token::Uint(U256::from(2))])
+                ])],
+                vec![0x20, 1, 0x40, 1, 2],
+                vec![
+                    (WordRole::Offset { target: 1 }, vec![0]),
+                    (WordRole::HeadValue, vec![0, 0]),
+                    (WordRole::Offset { target: 3 }, vec![0, 1]),
+                    (WordRole::Length { region: 4..5 }, vec![0, 1]),
+                    (WordRole::HeadValue, vec![0, 1, 0]),
+                ]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_type_hints,
+    [
+        (
+            hinted_static_words,
+            (
+                "(uint256,uint256)",
+                vec![Token::Uint(U256::from(32)), Token::Uint(U256::from(0))]
+            )
+        ),
+        (
+            hinted_tuples_instead_of_fixed_arrays,
+            (
+                "((uint256,uint256)[])",
+                vec![Token::Array(vec![
+                    Token::Tuple(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+                    Token::Tuple(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+                ])]
+            )
+        ),
+        (
+            hinted_empty_values,
+            (
+                "(bytes,string,uint256[])",
+                vec![
+                    Token::Bytes(vec![]),
+                    Token::String(String::new()),
+                    Token::Array(vec![])
+                ]
+            )
+        ),
+        (
+            address_and_unknowns,
+            (
+                "(address,?,bytes,?[])",
+                vec![
+                    address_token_from_string("0x0000000000000000000000000000000000000001"),
+                    Token::Uint(U256::from(5)),
+                    Token::Bytes(b"hello".to_vec()),
+                    Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+                ]
+            )
+        ),
+        (
+            hinted_array_of_static_tuples,
+            (
+                "((uint256,address)[])",
+                vec![Token::Array(vec![
+                    Token::Tuple(vec![
+                        Token::Uint(U256::from(1)),
+                        address_token_from_string("0x0000000000000000000000000000000000000002"),
+                    ]),
+                    Token::Tuple(vec![
+                        Token::Uint(U256::from(3)),
+                        address_token_from_string("0x0000000000000000000000000000000000000004"),
+                    ]),
+                ])]
+            )
+        ),
+        (
+            hinted_fixed_array_and_bool,
+            (
+                "(uint256[2],bool)",
+                vec![
+                    Token::FixedArray(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(0))]),
+                    Token::Bool(true),
+                ]
+            )
+        ),
+        (
+            partially_known_tuple,
+            (
+                "((?,string))",
+                vec![Token::Tuple(vec![
+                    Token::Uint(U256::from(3)),
+                    Token::String("hello".to_string()),
+                ])]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_invalid_type_hints,
+    [
+        (missing_parentheses, ("address", TypeHintsError::Syntax(0))),
+        (trailing_comma, ("(address,)", TypeHintsError::Syntax(9))),
+        (
+            unknown_type,
+            (
+                "(address,foo)",
+                TypeHintsError::UnknownType("foo".to_string())
+            )
+        ),
+        (invalid_array_length, ("(?[x])", TypeHintsError::Syntax(3))),
+    ]
+);
+
+parameterize!(
+    test_pins,
+    [
+        (
+            forbidden_offset,
+            (
+                vec![0x20, 0],
+                vec![Pin::is_not(0, PinnedRole::Offset)],
+                vec![Token::Uint(U256::from(32)), Token::Uint(U256::from(0))]
+            )
+        ),
+        (
+            forced_value,
+            (
+                vec![0x20, 0],
+                vec![Pin::is(0, PinnedRole::Value)],
+                vec![Token::Uint(U256::from(32)), Token::Uint(U256::from(0))]
+            )
+        ),
+        (
+            forbidden_array_length,
+            (
+                vec![0x20, 2, 3, 4],
+                vec![Pin::is_not(1, PinnedRole::ArrayLength)],
+                vec![
+                    Token::Uint(U256::from(32)),
+                    Token::Uint(U256::from(2)),
+                    Token::Uint(U256::from(3)),
+                    Token::Uint(U256::from(4)),
+                ]
+            )
+        ),
+        (
+            forced_offset_and_array_length,
+            (
+                vec![0x20, 2, 3, 4],
+                vec![
+                    Pin::is(0, PinnedRole::Offset),
+                    Pin::is(1, PinnedRole::ArrayLength)
+                ],
+                vec![Token::Array(vec![
+                    Token::Uint(U256::from(3)),
+                    Token::Uint(U256::from(4))
+                ])]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_rejected_pins,
+    [
+        (
+            conflicting_forced_roles,
+            (
+                vec![
+                    Pin::is(0, PinnedRole::Offset),
+                    Pin::is(0, PinnedRole::Value)
+                ],
+                DecodingError::ConflictingPins(0)
+            )
+        ),
+        (
+            forced_and_forbidden_role,
+            (
+                vec![
+                    Pin::is_not(0, PinnedRole::Value),
+                    Pin::is(1, PinnedRole::BytesLength),
+                    Pin::is_not(1, PinnedRole::BytesLength)
+                ],
+                DecodingError::ConflictingPins(1)
+            )
+        ),
+        (
+            unsatisfiable_pin,
+            (
+                vec![Pin::is(0, PinnedRole::Offset)],
+                DecodingError::NoMatchingLayout
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_invalid_hex,
+    [
+        (not_hex, ("zz", 0)),
+        (
+            selector_prefix,
+            (
+                "0x0000000000000000000000000000000000000000000000000000000000000001",
+                1
+            )
+        ),
+        (
+            character_in_trailing_data,
+            (
+                "00000000000000000000000000000000000000000000000000000000000000010g",
+                65
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_parse_pin,
+    [
+        (forced_pin, ("3=offset", Ok(Pin::is(3, PinnedRole::Offset)))),
+        (
+            forbidden_pin,
+            (
+                "7!=bytes-length",
+                Ok(Pin::is_not(7, PinnedRole::BytesLength))
+            )
+        ),
+        (
+            pin_without_word,
+            ("x=offset", Err(PinError::Syntax("x=offset".to_string())))
+        ),
+        (
+            pin_with_unknown_role,
+            ("1=length", Err(PinError::UnknownRole("length".to_string())))
+        ),
+    ]
+);
+
+parameterize!(
+    test_single_element_tuples,
+    [
+        (
+            struct_with_single_bytes_field,
+            (
+                vec![Token::Tuple(vec![bytes_token_from_string("abcdef")])],
+                true,
+                vec![bytes_token_from_string("abcdef")]
+            )
+        ),
+        (
+            struct_with_single_array_field,
+            (
+                vec![Token::Tuple(vec![Token::Array(vec![
+                    Token::Uint(U256::from(5)),
+                    Token::Uint(U256::from(6))
+                ])])],
+                true,
+                vec![Token::Array(vec![
+                    Token::Uint(U256::from(5)),
+                    Token::Uint(U256::from(6))
+                ])]
+            )
+        ),
+        (
+            nested_struct_with_single_field,
+            (
+                vec![
+                    Token::Uint(U256::from(1)),
+                    Token::Tuple(vec![bytes_token_from_string("abcdef")])
+                ],
+                false,
+                vec![
+                    Token::Uint(U256::from(1)),
+                    bytes_token_from_string("abcdef")
+                ]
+            )
+        ),
+        (
+            bytes_without_struct,
+            (
+                vec![bytes_token_from_string("abcdef")],
+                false,
+                vec![bytes_token_from_string("abcdef")]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_fixed_arrays,
+    [
+        (
+            array_of_uint256_triples,
+            vec![Token::Array(vec![
+                Token::FixedArray(vec![
+                    Token::Uint(U256::from(1)),
+                    Token::Uint(U256::from(2)),
+                    Token::Uint(U256::from(3))
+                ]),
+                Token::FixedArray(vec![
+                    Token::Uint(U256::from(4)),
+                    Token::Uint(U256::from(5)),
+                    Token::Uint(U256::from(6))
+                ]),
+            ])]
+        ),
+        (
+            array_of_address_pairs,
+            vec![Token::Array(vec![
+                Token::FixedArray(vec![
+                    address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                    address_token_from_string("0xfedcba9876543210fedcba9876543210fedcba98")
+                ]),
+                Token::FixedArray(vec![
+                    address_token_from_string("0xfedcba9876543210fedcba9876543210fedcba98"),
+                    address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01")
+                ]),
+            ])]
+        ),
+        (
+            repeated_static_tuples,
+            vec![Token::Array(
+                (1..3)
+                    .map(|i| {
+                        Token::FixedArray(vec![
+                            Token::Tuple(vec![
+                                address_token_from_string(
+                                    "0xabcdef0123456789abcdef0123456789abcdef01",
+                                ),
+                                Token::Uint(U256::from(i)),
+                            ]),
+                            Token::Tuple(vec![
+                                address_token_from_string(
+                                    "0xfedcba9876543210fedcba9876543210fedcba98",
+                                ),
+                                Token::Uint(U256::from(i + 2)),
+                            ]),
+                        ])
+                    })
+                    .collect()
+            )]
+        ),
+        (
+            fixed_array_of_bytes,
+            vec![Token::FixedArray(vec![
+                bytes_token_from_string("abcdef"),
+                bytes_token_from_string("0123")
+            ])]
+        ),
+        (
+            fixed_array_of_arrays,
+            vec![Token::FixedArray(vec![
+                Token::Array(vec![
+                    Token::Uint(U256::from(1000)),
+                    Token::Uint(U256::from(2000))
+                ]),
+                Token::Array(vec![
+                    Token::Uint(U256::from(3000)),
+                    Token::Uint(U256::from(4000))
+                ]),
+            ])]
+        ),
+        (
+            different_types_stay_tuples,
+            vec![Token::Array(vec![
+                Token::Tuple(vec![
+                    address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                    Token::Uint(U256::from(1))
+                ]),
+                Token::Tuple(vec![
+                    address_token_from_string("0xfedcba9876543210fedcba9876543210fedcba98"),
+                    Token::Uint(U256::from(2))
+                ]),
+            ])]
+        ),
+    ]
+);
+
+parameterize!(
+    test_empty_values,
+    [
+        (
+            empty_bytes_among_bytes,
+            (
+                vec![Token::Array(vec![
+                    Token::Tuple(vec![
+                        address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                        bytes_token_from_string("abcdef")
+                    ]),
+                    Token::Tuple(vec![
+                        address_token_from_string("0xfedcba9876543210fedcba9876543210fedcba98"),
+                        Token::Bytes(vec![])
+                    ]),
+                ])],
+                "((address,bytes)[])"
+            )
+        ),
+        (
+            empty_array_among_arrays,
+            (
+                vec![Token::Array(vec![
+                    Token::Array(vec![]),
+                    Token::Array(vec![Token::Uint(U256::from(5)), Token::Uint(U256::from(6))]),
+                ])],
+                "(uint256[][])"
+            )
+        ),
+        (lone_empty_value, (vec![Token::Array(vec![])], "(?)")),
+        (
+            only_empty_siblings,
+            (
+                vec![
+                    Token::Uint(U256::from(1)),
+                    Token::Array(vec![Token::Array(vec![]), Token::Array(vec![])])
+                ],
+                "(uint256,?[])"
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_decoded_values,
+    [
+        (
+            zero_resolved_as_bool,
+            (
+                vec![Token::Uint(U256::from(0))],
+                vec![(0, 1)],
+                vec![WordType::Bool],
+                vec![Token::Bool(false)]
+            )
+        ),
+        (
+            address_resolved_as_uint,
+            (
+                vec![
+                    address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                    bytes_token_from_string("abcdef")
+                ],
+                vec![(0, 1), (2, 4)],
+                vec![WordType::Uint],
+                vec![
+                    Token::Uint(
+                        U256::from_str_radix("abcdef0123456789abcdef0123456789abcdef01", 16)
+                            .unwrap()
+                    ),
+                    bytes_token_from_string("abcdef")
+                ]
+            )
+        ),
+        (
+            numbers_resolved_as_addresses,
+            (
+                vec![
+                    Token::Uint(U256::from(7)),
+                    Token::Array(vec![
+                        Token::Uint(U256::from(1000)),
+                        Token::Uint(U256::from(2000))
+                    ])
+                ],
+                vec![(0, 1), (2, 5)],
+                vec![WordType::Address],
+                vec![
+                    Token::Address(ethereum_types::H160::from_low_u64_be(7)),
+                    Token::Array(vec![
+                        Token::Address(ethereum_types::H160::from_low_u64_be(1000)),
+                        Token::Address(ethereum_types::H160::from_low_u64_be(2000))
+                    ])
+                ]
+            )
+        ),
+        (
+            negative_number_resolved_as_int,
+            (
+                vec![Token::Uint(U256::MAX)],
+                vec![(0, 1)],
+                vec![WordType::Address, WordType::Int],
+                vec![Token::Int(U256::MAX)]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_visit,
+    [
+        (
+            visits_parents_before_children,
+            (
+                nested_tokens(),
+                true,
+                vec![
+                    vec![0],
+                    vec![1],
+                    vec![1, 0],
+                    vec![1, 0, 0],
+                    vec![1, 0, 1],
+                    vec![1, 0, 1, 0],
+                    vec![1, 1],
+                ]
+            )
+        ),
+        (
+            skips_elements_of_arrays,
+            (
+                nested_tokens(),
+                false,
+                vec![
+                    vec![0],
+                    vec![1],
+                    vec![1, 0],
+                    vec![1, 0, 0],
+                    vec![1, 0, 1],
+                    vec![1, 1]
+                ]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_fold,
+    [
+        (
+            numbers_replaced_with_depth,
+            (
+                nested_tokens(),
+                vec![
+                    Token::Uint(U256::from(1)),
+                    Token::Tuple(vec![
+                        Token::Tuple(vec![
+                            address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                            Token::Array(vec![Token::Uint(U256::from(4))])
+                        ]),
+                        Token::Uint(U256::from(2)),
+                    ])
+                ]
+            )
+        ),
+        (
+            nested_single_element_tuples_unwrapped,
+            (
+                vec![Token::Tuple(vec![Token::Tuple(vec![Token::Array(vec![
+                    Token::Tuple(vec![Token::Bool(true)])
+                ])])])],
+                vec![Token::Array(vec![Token::Bool(true)])]
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_query,
+    [
+        (argument, ("$[1]", vec![vec![1]])),
+        (
+            second_field_of_every_tuple,
+            ("$[1][*].1", vec![vec![1, 0, 1], vec![1, 1, 1]])
+        ),
+        (field_of_field, ("$.1.0.1.0", vec![vec![1, 0, 1, 0]])),
+        (
+            all_numbers,
+            (
+                "$..[?uint256]",
+                vec![vec![0], vec![1, 0, 1, 0], vec![1, 1, 0]]
+            )
+        ),
+        (
+            all_addresses_by_dot,
+            ("$..*[?address]", vec![vec![1, 0, 0]])
+        ),
+        (
+            tuples_by_type,
+            ("$[1][?(address,uint256[])]", vec![vec![1, 0]])
+        ),
+        (any_array, ("$..[?uint256[]]", vec![vec![1, 0, 1], vec![2]])),
+        (any_type, ("$[??]", vec![vec![0], vec![1], vec![2]])),
+        (index_out_of_range, ("$[3]", vec![])),
+        (field_of_number, ("$[0].0", vec![])),
+    ]
+);
+
+parameterize!(
+    test_invalid_query,
+    [
+        (missing_root, ("[0]", QueryError::Syntax(0))),
+        (unclosed_bracket, ("$[0", QueryError::Syntax(3))),
+        (not_an_index, ("$.a", QueryError::Syntax(2))),
+        (descendants_at_end, ("$..", QueryError::Syntax(3))),
+        (
+            unknown_filter_type,
+            ("$[?uint7]", QueryError::InvalidType("uint7".to_string()))
+        ),
+        (
+            several_filter_types,
+            (
+                "$[?address,bool]",
+                QueryError::InvalidType("address,bool".to_string())
+            )
+        ),
+    ]
+);
+
+parameterize!(
+    test_decodes_large_input_quickly,
+    [
+        (
+            many_single_element_arrays,
+            vec![Token::Array(
+                (0..24)
+                    .map(|i| Token::Array(vec![Token::Tuple(vec![
+                        Token::Uint(U256::from(i / 3)),
+                        Token::Uint(U256::from(i % 3)),
+                    ])]))
+                    .collect(),
+            )]
+        ),
+        (
+            seaport_orders,
+            vec![
+                Token::Array((0..5).map(seaport_order).collect()),
+                Token::Array(vec![]),
+                Token::FixedBytes(vec![0; 32]),
+                Token::Uint(U256::from(5)),
+            ]
+        )
+    ]
+);
+
+parameterize!(
+    test_does_not_cut_off_complete_encoding,
+    [
+        (
+            arrays_after_bytes_in_tuple,
+            vec![
+                Token::Uint(U256::from(125)),
+                Token::Tuple(vec![
+                    Token::Array(vec![
+                        Token::Bytes(vec![0xaa; 32]),
+                        Token::Bytes(vec![0xbb; 30])
+                    ]),
+                    Token::Uint(U256::from(719)),
+                    Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+                ]),
+            ]
+        ),
+        (
+            array_of_zero_tuple_after_number,
+            vec![
+                Token::Uint(U256::from(2)),
+                Token::Array(vec![Token::Tuple(vec![
+                    Token::Uint(U256::zero()),
+                    Token::Uint(U256::zero()),
+                ])]),
+            ]
+        ),
+        (
+            array_after_static_tuple,
+            vec![
+                Token::Uint(U256::from(3)),
+                Token::Tuple(vec![
+                    Token::Tuple(vec![Token::Uint(U256::zero())]),
+                    Token::Uint(U256::from(856)),
+                    Token::Array(vec![Token::Uint(U256::from(128))]),
+                ]),
+            ]
+        ),
+    ]
+);
+
+parameterize!(
+    test_finds_end_of_large_input_quickly,
+    [(
+        seaport_orders_with_extra_data,
+        vec![
+            Token::Array((0..20).map(seaport_order).collect()),
+            Token::Array(vec![]),
+            Token::FixedBytes(vec![0; 32]),
+            Token::Uint(U256::from(20)),
+        ]
+    )]
+);
+
+parameterize!(
+    test_pruned_reruns_find_same_layout,
+    [
+        (
+            few_single_element_arrays,
+            vec![Token::Array(
+                (0..10)
+                    .map(|i| Token::Array(vec![Token::Tuple(vec![
+                        Token::Uint(U256::from(i / 3)),
+                        Token::Uint(U256::from(i % 3)),
+                    ])]))
+                    .collect(),
+            )]
+        ),
+        (
+            single_seaport_order,
+            vec![Token::Array(vec![seaport_order(0)]), Token::Array(vec![])]
+        ),
+        (
+            arrays_of_small_numbers,
+            vec![Token::Array(vec![
+                Token::Array(vec![Token::Uint(U256::from(1))]),
+                Token::Array(vec![Token::Uint(U256::from(2)), Token::Uint(U256::from(3))]),
+                Token::Array(vec![Token::Uint(U256::from(0))]),
+            ])]
+        ),
+        (
+            tuples_of_bytes,
+            vec![Token::Array(vec![
+                Token::Tuple(vec![
+                    Token::Bytes(vec![1, 2, 3]),
+                    Token::Uint(U256::from(64))
+                ]),
+                Token::Tuple(vec![Token::Bytes(vec![]), Token::Uint(U256::from(32))]),
+            ])]
+        ),
+    ]
+);
+
+parameterize!(
+    test_solver_decodes_wide_head_quickly,
+    [
+        // None of the words the numbers point to can start a tail
+        (numbers_pointing_into_bytes, wide_head(vec![0xff; 32 * 20])),
+        // Tails can start at any of the words, but most of the regions between them do not decode
+        (
+            numbers_pointing_at_lengths,
+            wide_head(words_to_bytes(&[2; 20]))
+        ),
+    ]
+);
+
+fn nested_tokens() -> Vec<Token> {
+    vec![
+        Token::Uint(U256::from(5)),
+        Token::Tuple(vec![
+            Token::Tuple(vec![
+                address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                Token::Array(vec![Token::Uint(U256::from(6))]),
+            ]),
+            Token::Uint(U256::from(7)),
+        ]),
+    ]
+}
+
+fn words_to_bytes(words: &[u64]) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|word| {
+            let mut bytes = [0; 32];
+            U256::from(*word).to_big_endian(&mut bytes);
+            bytes
+        })
+        .collect()
+}
+
+fn with_byte(mut data: Vec<u8>, index: usize, value: u8) -> Vec<u8> {
+    data[index] = value;
+    data
+}
+
+fn test_validation(
+    types_data_and_expected_deviations: (Vec<ParamType>, Vec<u8>, Vec<EncodingDeviation>),
+) {
+    let (param_types, data, expected_deviations) = types_data_and_expected_deviations;
+    assert_eq!(validate_encoding(&param_types, &data), expected_deviations);
+}
+
+fn test_strict_decoding(words_cut_off_bytes_and_is_valid: (Vec<u64>, usize, bool)) {
+    let (words, cut_off_bytes, is_valid) = words_cut_off_bytes_and_is_valid;
+    let mut encoded_arguments = hex::encode(words_to_bytes(&words));
+    encoded_arguments.truncate(encoded_arguments.len() - cut_off_bytes * 2);
+    let options = DecodingOptions {
+        allow_non_canonical: true,
+        strict: true,
+        ..Default::default()
+    };
+    let result = decode_arguments(&encoded_arguments, &options);
+    assert_eq!(result.is_ok(), is_valid);
+}
+
+fn test_decodes_deep_nesting_on_small_stack(depth: usize) {
+    let mut argument = Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]);
+    for _ in 0..depth {
+        argument = Token::Array(vec![argument]);
+    }
+    let words = to_words(&ethabi::encode(&[argument.clone()]));
+
+    // The search used to recurse several times per level of nesting, which overflowed this stack.
+    // Cloning and dropping the decoded tokens still recurses, so the stack can't be arbitrarily
+    // small.
+    let solver_words = words.clone();
+    let tokens = std::thread::Builder::new()
+        .stack_size(depth * 3 * 1024)
+        .spawn(move || decode_words(&words))
+        .unwrap()
+        .join()
+        .expect("Decoding overflowed the stack");
+    assert_eq!(tokens, vec![argument.clone()]);
+
+    let layouts = std::thread::Builder::new()
+        .stack_size(depth * 3 * 1024)
+        .spawn(move || {
+            constraint_solver::ConstraintSolver::default().decode_layouts(
+                &solver_words,
+                &DecodingOptions::default(),
+                &mut Budget::default(),
+            )
+        })
+        .unwrap()
+        .join()
+        .expect("Solving overflowed the stack");
+    assert!(layouts
+        .iter()
+        .any(|(layout, _)| *layout == vec![argument.clone()]));
+}
+
+fn test_decoding_limits(
+    words_options_and_expected_tokens: (Vec<u64>, DecodingOptions, Vec<Token>),
+) {
+    let (words, options, expected_tokens) = words_options_and_expected_tokens;
+    let encoded_arguments = hex::encode(words_to_bytes(&words));
+
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert!(!result.partial);
+
+    let result = decode_arguments(&encoded_arguments, &options).unwrap();
+    for token in &result.tokens {
+        print_parse_tree(token, 0);
+    }
+    assert_eq!(result.tokens, expected_tokens);
+    assert!(result.partial);
+    assert_eq!(result.trailing_data, None);
+}
+
+fn print_words(words: &[Word]) {
+    println!("#### Encoded calldata (without function selector) ####");
+    for (i, word) in words.iter().enumerate() {
+        println!(
+            "{}: {} - {}",
+            i,
+            hex::encode(word.bytes),
+            word.as_usize().unwrap_or(0)
+        );
+    }
+    println!("\n");
+}
+
+// Tokens of the greedy search, in which every value behind an offset is wrapped in a tuple of its
+// own, and whether any region was left undecoded because a limit in the options was reached
+fn try_decode_words(
+    words: &[Word],
+    options: &DecodingOptions,
+    budget: &mut Budget,
+) -> Option<(Vec<Token>, bool)> {
+    let mut context = ParseContext::new(options, words, budget);
+    let tokens = parse_arguments(words, &DisallowedMarkers::default(), &mut context)?;
+    Some((tokens, context.is_partial()))
+}
+
+fn decode_words(words: &[Word]) -> Vec<Token> {
+    try_decode_words(words, &DecodingOptions::default(), &mut Budget::default())
+        .expect("Failed to parse arguments")
+        .0
+        .into_iter()
+        .map(parse_token::remove_single_element_tuples)
+        .collect()
+}
+
+fn test_same_encoding(arguments: Vec<Token>) {
+    println!("Arguments:");
+    for argument in &arguments {
+        print_parse_tree(argument, 0);
+    }
+    println!();
+    println!();
+    let encoded_arguments = add_padding(&hex::encode(ethabi::encode(&arguments)));
+
+    let words = to_words(&hex::decode(&encoded_arguments).unwrap());
+    print_words(&words);
+    let tokens = decode_words(&words);
+    for token in &tokens {
+        print_parse_tree(token, 0);
+    }
+    assert_eq!(tokens, arguments);
+    assert_eq!(
+        decode_arguments(&encoded_arguments, &DecodingOptions::default())
+            .unwrap()
+            .trailing_data,
+        None
+    );
+}
+
+fn test_can_reencode_with_added_data_at_the_end(arguments: Vec<Token>) {
+    println!("Arguments:");
+    for argument in &arguments {
+        print_parse_tree(argument, 0);
+    }
+    println!();
+    println!();
+    let encoded_arguments = add_padding(&hex::encode(ethabi::encode(&arguments)));
+    let extra_data = "01".repeat(32);
+
+    let result = decode_arguments(
+        &format!("{}{}", encoded_arguments, extra_data),
+        &DecodingOptions::default(),
+    )
+    .unwrap();
+    for token in &result.tokens {
+        print_parse_tree(token, 0);
+    }
+    assert_eq!(result.tokens, arguments);
+    let trailing_data = result.trailing_data.expect("Trailing data not detected");
+    assert_eq!(trailing_data.data, vec![1; 32]);
+    assert_eq!(trailing_data.domain_tag, None);
+}
+
+fn test_detects_domain_tag(arguments_and_tag: (Vec<Token>, &str, Option<&str>)) {
+    let (arguments, tag, expected_domain) = arguments_and_tag;
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+
+    let result = decode_arguments(
+        &format!("{}{}", encoded_arguments, tag),
+        &DecodingOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(result.tokens, arguments);
+    let trailing_data = result.trailing_data.expect("Trailing data not detected");
+    let domain_tag = trailing_data.domain_tag.expect("Domain tag not detected");
+    assert_eq!(hex::encode(domain_tag.tag), tag.trim_start_matches('0'));
+    assert_eq!(domain_tag.domain, expected_domain);
+}
+
+fn test_non_canonical_encoding(words_and_expected_tokens: (Vec<u64>, Vec<Token>)) {
+    let (words, expected_tokens) = words_and_expected_tokens;
+    let encoded_arguments: String = words.iter().map(|word| format!("{:064x}", word)).collect();
+
+    // By default the offsets have to be in order so the data is decoded differently
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert_ne!(result.tokens, expected_tokens);
+    assert!(!result.non_canonical);
+
+    let options = DecodingOptions {
+        allow_non_canonical: true,
+        ..Default::default()
+    };
+    let result = decode_arguments(&encoded_arguments, &options).unwrap();
+    for token in &result.tokens {
+        print_parse_tree(token, 0);
+    }
+    assert_eq!(result.tokens, expected_tokens);
+    assert!(result.non_canonical);
+    assert_eq!(result.trailing_data, None);
+
+    // The constraint solver finds the layout among the ones it ranks
+    let solver = constraint_solver::ConstraintSolver::default();
+    let result = decode_arguments_with_engine(&encoded_arguments, &options, &solver).unwrap();
+    assert!(result.tokens == expected_tokens || result.alternatives.contains(&expected_tokens));
+}
+
+fn test_non_canonical_option_decodes_canonical_data(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let options = DecodingOptions {
+        allow_non_canonical: true,
+        ..Default::default()
+    };
+    let result = decode_arguments(&encoded_arguments, &options).unwrap();
+    assert_eq!(result.tokens, arguments);
+    assert!(result.round_trip);
+    assert!(!result.non_canonical);
+}
+
+fn test_different_encoding(arguments_and_expected_tokens: (Vec<Token>, Vec<Token>)) {
+    let (arguments, expected_tokens) = arguments_and_expected_tokens;
+    for argument in &arguments {
+        print_parse_tree(argument, 0);
+    }
+    println!();
+    println!();
+    let encoded_arguments = add_padding(&hex::encode(ethabi::encode(&arguments)));
+
+    let words = to_words(&hex::decode(&encoded_arguments).unwrap());
+    print_words(&words);
+    let tokens = decode_words(&words);
+    for token in &tokens {
+        print_parse_tree(token, 0);
+    }
+    assert_eq!(tokens, expected_tokens);
+}
+
+fn test_disallowed_markers_do_not_depend_on_insertion_order(
+    indices_and_limit: (Vec<usize>, usize),
+) {
+    let (indices, limit) = indices_and_limit;
+    let insert_all = |indices: &mut dyn Iterator<Item = &usize>| {
+        indices.fold(DisallowedMarkers::default(), |markers, index| {
+            markers.insert(*index, MarkerType::Tuple)
+        })
+    };
+    let markers = insert_all(&mut indices.iter());
+    let reversed_markers = insert_all(&mut indices.iter().rev());
+    assert_eq!(markers, reversed_markers);
+    for index in &indices {
+        assert!(markers.contains(*index, &MarkerType::Tuple));
+    }
+    assert_eq!(markers.get(limit), None);
+    assert_eq!(markers.below(limit), reversed_markers.below(limit));
+    assert_ne!(markers.below(limit), markers.below(usize::MAX));
+}
+
+#[test]
+fn test_disallowed_markers_are_persistent() {
+    let markers = DisallowedMarkers::default().insert(2, MarkerType::Tuple);
+    // The view of the first set is built before the others are derived from it
+    assert!(markers.contains(2, &MarkerType::Tuple));
+    let replaced = markers.insert(2, MarkerType::DynamicBytes);
+    let extended = markers.insert(0, MarkerType::Array);
+
+    assert!(markers.contains(2, &MarkerType::Tuple));
+    assert_eq!(markers.get(0), None);
+    assert!(replaced.contains(2, &MarkerType::DynamicBytes));
+    assert!(extended.contains(0, &MarkerType::Array));
+    assert!(extended.contains(2, &MarkerType::Tuple));
+    assert_eq!(
+        replaced,
+        DisallowedMarkers::default().insert(2, MarkerType::DynamicBytes)
+    );
+    assert_eq!(
+        extended.below(1),
+        replaced.insert(0, MarkerType::Array).below(1)
+    );
+}
+
+fn test_constraint_solver_finds_layout(arguments: Vec<Token>) {
+    let encoded_arguments = ethabi::encode(&arguments);
+    let words = to_words(&encoded_arguments);
+    let solver = constraint_solver::ConstraintSolver::default();
+    let layouts =
+        solver.decode_layouts(&words, &DecodingOptions::default(), &mut Budget::default());
+    for (layout, partial) in &layouts {
+        print_parse_tree(&Token::Tuple(layout.clone()), 0);
+        assert!(!partial);
+        assert_eq!(ethabi::encode(layout), encoded_arguments);
+    }
+    assert!(layouts.iter().any(|(layout, _)| *layout == arguments));
+    assert!(layouts.len() <= solver.max_layouts);
+
+    let result = decode_arguments_with_engine(
+        &hex::encode(&encoded_arguments),
+        &DecodingOptions::default(),
+        &solver,
+    )
+    .unwrap();
+    // Layouts without dynamic values say nothing about where the arguments end, the others are
+    // ranked by the heuristics
+    let mut structured_layouts: Vec<Vec<Token>> = layouts
+        .into_iter()
+        .map(|(layout, _)| layout)
+        .filter(|layout| layout.iter().any(parse_token::contains_dynamic_type))
+        .collect();
+    heuristic::Heuristics::default()
+        .rank(&mut structured_layouts, &encoded_arguments, |layout| layout);
+    assert_eq!(result.tokens, structured_layouts[0]);
+    // The best layout read with fixed size arrays comes before the other layouts
+    let mut alternatives = structured_layouts[1..].to_vec();
+    let fixed_arrays = fixed_arrays::infer_fixed_arrays(structured_layouts[0].clone());
+    if fixed_arrays != structured_layouts[0] && !alternatives.contains(&fixed_arrays) {
+        alternatives.insert(0, fixed_arrays);
+    }
+    assert_eq!(result.alternatives, alternatives);
+}
+
+// Words, offsets (from, base, to), indices of the lengths and bases
+type PointerGraphCase = (Vec<u64>, Vec<(usize, usize, usize)>, Vec<usize>, Vec<usize>);
+
+fn test_pointer_graph(words_offsets_lengths_and_bases: PointerGraphCase) {
+    let (words, offsets, lengths, bases) = words_offsets_lengths_and_bases;
+    let graph = pointer_graph::PointerGraph::new(&words_to_bytes(&words));
+    let expected_offsets: Vec<pointer_graph::OffsetCandidate> = offsets
+        .into_iter()
+        .map(|(from, base, to)| pointer_graph::OffsetCandidate { from, base, to })
+        .collect();
+    assert_eq!(graph.offsets(), expected_offsets);
+    let length_indices: Vec<usize> = graph.lengths().iter().map(|length| length.index).collect();
+    assert_eq!(length_indices, lengths);
+    assert_eq!(graph.bases(), bases);
+    for index in 0..words.len() {
+        assert_eq!(
+            graph.is_candidate(index),
+            !graph.offsets_from(index).is_empty() || graph.length(index).is_some()
+        );
+    }
+}
+
+#[derive(Debug)]
+struct AddressBook(Vec<ethabi::Address>);
+
+impl heuristic::Heuristic for AddressBook {
+    fn score_word(&self, _word: &[u8; 32], candidate: &Token) -> i64 {
+        match candidate {
+            Token::Address(address) if self.0.contains(address) => 10,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EmptyBytes;
+
+impl heuristic::Heuristic for EmptyBytes {
+    fn score_layout(&self, tokens: &[Token]) -> i64 {
+        if tokens.contains(&Token::Bytes(vec![])) {
+            10
+        } else {
+            0
+        }
+    }
+}
+
+fn test_heuristics(
+    arguments_heuristics_and_expected_tokens: (Vec<Token>, heuristic::Heuristics, Vec<Token>),
+) {
+    let (arguments, heuristics, expected_tokens) = arguments_heuristics_and_expected_tokens;
+    let options = DecodingOptions {
+        heuristics,
+        ..Default::default()
+    };
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let engines: [&dyn LayoutEngine; 2] = [
+        &layout_engine::GreedySearch,
+        &constraint_solver::ConstraintSolver::default(),
+    ];
+    for engine in engines {
+        let result = decode_arguments_with_engine(&encoded_arguments, &options, engine).unwrap();
+        assert_eq!(result.tokens, expected_tokens);
+    }
+}
+
+// Samples (signature and arguments) of the corpus, arguments to decode and expected tokens
+type PriorsCase = (Vec<(&'static str, Vec<Token>)>, Vec<Token>, Vec<Token>);
+
+fn test_priors(corpus_arguments_and_expected_tokens: PriorsCase) {
+    let (samples, arguments, expected_tokens) = corpus_arguments_and_expected_tokens;
+    let corpus: String = samples
+        .iter()
+        .map(|(signature, arguments)| {
+            format!(
+                "{} 0x12345678{}\n",
+                signature,
+                hex::encode(ethabi::encode(arguments))
+            )
+        })
+        .collect();
+    let priors = priors::Priors::from_corpus(&corpus).unwrap();
+    assert_eq!(
+        priors.to_string().parse::<priors::Priors>().unwrap(),
+        priors
+    );
+
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let solver = constraint_solver::ConstraintSolver::default();
+    let result =
+        decode_arguments_with_engine(&encoded_arguments, &DecodingOptions::default(), &solver)
+            .unwrap();
+    assert_ne!(result.tokens, expected_tokens);
+
+    let options = DecodingOptions {
+        heuristics: heuristic::Heuristics::default().with(priors),
+        ..Default::default()
+    };
+    let result = decode_arguments_with_engine(&encoded_arguments, &options, &solver).unwrap();
+    assert_eq!(result.tokens, expected_tokens);
+}
+
+// Engine returning the same layouts for any data
+struct FixedLayouts(Vec<Vec<Token>>);
+
+impl LayoutEngine for FixedLayouts {
+    fn decode_layouts(
+        &self,
+        _words: &[Word],
+        _options: &DecodingOptions,
+        _budget: &mut Budget,
+    ) -> Vec<(Vec<Token>, bool)> {
+        self.0
+            .iter()
+            .map(|tokens| (tokens.clone(), false))
+            .collect()
+    }
+}
+
+// Words, layouts returned by the engine, expected tokens, round trip and non canonical
+type RoundTripCase = (Vec<u64>, Vec<Vec<Token>>, Vec<Token>, bool, bool);
+
+fn test_falls_back_to_round_trip_layout(round_trip_case: RoundTripCase) {
+    let (words, layouts, expected_tokens, round_trip, non_canonical) = round_trip_case;
+    let encoded_arguments = hex::encode(words_to_bytes(&words));
+    let result = decode_arguments_with_engine(
+        &encoded_arguments,
+        &DecodingOptions::default(),
+        &FixedLayouts(layouts),
+    )
+    .unwrap();
+    assert_eq!(result.tokens, expected_tokens);
+    assert_eq!(result.round_trip, round_trip);
+    assert_eq!(result.non_canonical, non_canonical);
+}
+
+fn test_greedy_search_round_trips(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    for token in &result.tokens {
+        print_parse_tree(token, 0);
+    }
+    assert!(result.round_trip);
+    assert_eq!(
+        hex::encode(ethabi::encode(&result.tokens)),
+        encoded_arguments
+    );
+}
+
+// The first layout of non canonical data does not re-encode to it, reading one of its offsets as a
+// number gives a later layout that does
+fn test_greedy_search_finds_round_trip_layout(words_and_expected_tokens: (Vec<u64>, Vec<Token>)) {
+    let (words, expected_tokens) = words_and_expected_tokens;
+    let data = words_to_bytes(&words);
+    let options = DecodingOptions {
+        allow_non_canonical: true,
+        ..Default::default()
+    };
+    let layouts = layout_engine::GreedySearch.decode_layouts(
+        &to_words(&data),
+        &options,
+        &mut Budget::default(),
+    );
+    assert_ne!(ethabi::encode(&layouts[0].0), data);
+    let round_trip_layouts: Vec<&Vec<Token>> = layouts[1..]
+        .iter()
+        .map(|(tokens, _)| tokens)
+        .filter(|tokens| ethabi::encode(tokens) == data)
+        .collect();
+    assert!(round_trip_layouts.contains(&&expected_tokens));
+
+    // Non canonical encodings are allowed, so the first layout stays the decoding
+    let result = decode_arguments(&hex::encode(&data), &options).unwrap();
+    assert_eq!(result.tokens, layouts[0].0);
+    assert!(!result.round_trip);
+    assert!(result.non_canonical);
+    assert!(result.alternatives.contains(&expected_tokens));
+
+    // Without them the layout that round trips is the decoding
+    let result = decode_arguments(&hex::encode(&data), &DecodingOptions::default()).unwrap();
+    assert_eq!(result.tokens, expected_tokens);
+    assert!(result.round_trip);
+}
+
+// Arguments, hex encoded trailing data, rules of the words and some of the rejections
+type ExplanationCase = (
+    Vec<Token>,
+    &'static str,
+    Vec<Option<explain::Rule>>,
+    Vec<(usize, explain::Rejection)>,
+);
+
+fn test_explanation(arguments_rules_and_rejections: ExplanationCase) {
+    let (arguments, trailing_data, expected_rules, expected_rejections) =
+        arguments_rules_and_rejections;
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments)) + trailing_data;
+    let options = DecodingOptions {
+        explain: true,
+        ..Default::default()
+    };
+    let explanation = decode_arguments(&encoded_arguments, &options)
+        .unwrap()
+        .explanation
+        .unwrap();
+    println!("{}", explanation);
+    let rules: Vec<Option<explain::Rule>> = explanation
+        .words
+        .iter()
+        .map(|trace| trace.rule.clone())
+        .collect();
+    assert_eq!(rules, expected_rules);
+    for (index, rejection) in expected_rejections {
+        assert!(explanation.words[index].rejections.contains(&rejection));
+    }
+    assert_eq!(
+        explanation.to_json().as_array().unwrap().len(),
+        expected_rules.len()
+    );
+
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert_eq!(result.explanation, None);
+}
+
+#[test]
+fn test_explanation_of_gap_after_bytes() {
+    let words = [64, 160, 31, 0x0700, 9, 2, 3, 4];
+    let encoded_arguments: String = words.iter().map(|word| format!("{:064x}", word)).collect();
+    let options = DecodingOptions {
+        allow_non_canonical: true,
+        explain: true,
+        ..Default::default()
+    };
+    let explanation = decode_arguments(&encoded_arguments, &options)
+        .unwrap()
+        .explanation
+        .unwrap();
+    // The word after the content of the bytes is part of the arguments but of no value
+    assert_eq!(explanation.words[4].rule, Some(explain::Rule::Padding));
+    assert!(explanation
+        .to_string()
+        .contains("Word 4: padding between tails"));
+    assert!(!explanation.to_string().contains("trailing data"));
+}
+
+#[test]
+fn test_bytes_followed_by_gap_are_an_alternative_to_arrays() {
+    // The first bytes are followed by a gap and the length of the second argument fits an array
+    // of two numbers as well as two bytes followed by another gap
+    let mut data = words_to_bytes(&[96, 192, 288, 31, 0x0700, 9, 2, 0, 5, 2, 7, 8]);
+    data[7 * 32..7 * 32 + 2].copy_from_slice(&[1, 2]);
+    let words = to_words(&data);
+    let first_argument = Token::Bytes(words_to_bytes(&[0x0700])[..31].to_vec());
+    let third_argument = Token::Array(vec![Token::Uint(U256::from(7)), Token::Uint(U256::from(8))]);
+    let array_layout = vec![
+        first_argument.clone(),
+        Token::Array(vec![
+            Token::Uint(U256::from(0x0102) << 240),
+            Token::Uint(U256::from(5)),
+        ]),
+        third_argument.clone(),
+    ];
+    let gap_layout = vec![first_argument, Token::Bytes(vec![1, 2]), third_argument];
+    let options = DecodingOptions {
+        allow_non_canonical: true,
+        ..Default::default()
+    };
+    let engines: [&dyn LayoutEngine; 2] = [
+        &layout_engine::GreedySearch,
+        &constraint_solver::ConstraintSolver::default(),
+    ];
+    for engine in engines {
+        let mut layouts: Vec<_> = engine
+            .decode_layouts(&words, &options, &mut Budget::default())
+            .into_iter()
+            .map(|(tokens, _)| tokens)
+            .collect();
+        assert!(layouts.contains(&gap_layout));
+        // The gap is what ranks the bytes after the array
+        options
+            .heuristics
+            .rank(&mut layouts, &data, |tokens| tokens);
+        let array_position = layouts.iter().position(|layout| *layout == array_layout);
+        let gap_position = layouts.iter().position(|layout| *layout == gap_layout);
+        assert!(array_position < gap_position);
+    }
+
+    let heuristics = heuristic::Heuristics::empty().with(heuristic::Gaps);
+    assert_eq!(heuristics.score_encoding(&array_layout, &data), -1);
+    assert_eq!(heuristics.score_encoding(&gap_layout, &data), -2);
+}
+
+// Tokens, words of the encoded data and the expected roles and token paths of the words
+type AnnotationCase = (Vec<Token>, Vec<u64>, Vec<(WordRole, Vec<usize>)>);
+
+fn test_word_annotations(tokens_words_and_annotations: AnnotationCase) {
+    let (tokens, words, expected_annotations) = tokens_words_and_annotations;
+    let annotations = annotate_words(&tokens, &words_to_bytes(&words));
+    let annotations: Vec<(WordRole, Vec<usize>)> = annotations
+        .into_iter()
+        .map(|annotation| {
+            if !annotation.path.is_empty() {
+                assert!(get_token(&tokens, &annotation.path).is_some());
+            }
+            (annotation.role, annotation.path)
+        })
+        .collect();
+    assert_eq!(annotations, expected_annotations);
+}
+
+fn test_type_hints(template_and_arguments: (&str, Vec<Token>)) {
+    let (template, arguments) = template_and_arguments;
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let options = DecodingOptions {
+        type_hints: Some(template.parse().unwrap()),
+        ..Default::default()
+    };
+    let result = decode_arguments(&encoded_arguments, &options).unwrap();
+    assert_eq!(result.tokens, arguments);
+    assert!(result.round_trip);
+}
+
+fn test_invalid_type_hints(template_and_error: (&str, TypeHintsError)) {
+    let (template, error) = template_and_error;
+    assert_eq!(template.parse::<TypeHints>(), Err(error));
+}
+
+#[test]
+fn test_type_hints_not_matched() {
+    let options = DecodingOptions {
+        type_hints: Some("(bytes)".parse().unwrap()),
+        ..Default::default()
+    };
+    let encoded_arguments = hex::encode(words_to_bytes(&[1, 2]));
+    assert_eq!(
+        decode_arguments(&encoded_arguments, &options),
+        Err(DecodingError::NoMatchingLayout)
+    );
+}
+
+fn test_pins(words_pins_and_expected_tokens: (Vec<u64>, Vec<Pin>, Vec<Token>)) {
+    let (words, pins, expected_tokens) = words_pins_and_expected_tokens;
+    let encoded_arguments = hex::encode(words_to_bytes(&words));
+    let options = DecodingOptions {
+        pins,
+        ..Default::default()
+    };
+    let result = decode_arguments(&encoded_arguments, &options).unwrap();
+    assert_eq!(result.tokens, expected_tokens);
+}
+
+fn test_rejected_pins(pins_and_error: (Vec<Pin>, DecodingError)) {
+    let (pins, error) = pins_and_error;
+    let encoded_arguments = hex::encode(words_to_bytes(&[1, 2]));
+    let options = DecodingOptions {
+        pins,
+        ..Default::default()
+    };
+    assert_eq!(decode_arguments(&encoded_arguments, &options), Err(error));
+}
+
+fn test_invalid_hex(arguments_and_position: (&str, usize)) {
+    let (encoded_arguments, position) = arguments_and_position;
+    for strict in [false, true] {
+        let options = DecodingOptions {
+            strict,
+            ..Default::default()
+        };
+        assert_eq!(
+            decode_arguments(encoded_arguments, &options),
+            Err(DecodingError::InvalidHex(position))
+        );
+    }
+}
+
+fn test_parse_pin(pin_and_expected: (&str, Result<Pin, PinError>)) {
+    let (pin, expected) = pin_and_expected;
+    assert_eq!(pin.parse::<Pin>(), expected);
+    if let Ok(parsed) = expected {
+        assert_eq!(parsed.to_string(), pin);
+    }
+}
+
+fn test_single_element_tuples(arguments_wrapper_and_unwrapped: (Vec<Token>, bool, Vec<Token>)) {
+    let (arguments, wrapper_tuple, unwrapped_tokens) = arguments_wrapper_and_unwrapped;
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert_eq!(result.tokens, arguments);
+    assert!(result.alternatives.is_empty());
+    assert_eq!(result.wrapper_tuple, wrapper_tuple);
+    assert_eq!(result.unwrapped_tokens(), unwrapped_tokens);
+}
+
+fn test_fixed_arrays(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    // The decoded tokens keep the groups as tuples, the fixed size arrays are the first alternative
+    let mut fixed_array = false;
+    visit::visit_tokens(&result.tokens, &mut |_: &[usize], token: &Token| {
+        fixed_array |= matches!(token, Token::FixedArray(_));
+        !fixed_array
+    });
+    assert!(!fixed_array);
+    assert!(result.round_trip);
+    if result.tokens == arguments {
+        assert!(result.alternatives.is_empty());
+    } else {
+        assert_eq!(result.alternatives.first(), Some(&arguments));
+    }
+}
+
+#[test]
+fn test_struct_of_repeating_types_is_wrapper_tuple() {
+    let fields = vec![
+        bytes_token_from_string("abcdef"),
+        bytes_token_from_string("0123"),
+    ];
+    let encoded_arguments = hex::encode(ethabi::encode(&[Token::Tuple(fields.clone())]));
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert_eq!(result.tokens, vec![Token::Tuple(fields.clone())]);
+    assert!(result.wrapper_tuple);
+    assert_eq!(result.unwrapped_tokens(), fields);
+    assert_eq!(result.alternatives, vec![vec![Token::FixedArray(fields)]]);
+}
+
+fn test_empty_values(arguments_and_types: (Vec<Token>, &str)) {
+    let (arguments, types) = arguments_and_types;
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert_eq!(result.tokens, arguments);
+    assert_eq!(result.types().to_string(), types);
+    assert_eq!(types.parse::<TypeHints>().unwrap().to_string(), types);
+}
+
+type DecodedValueCase = (Vec<Token>, Vec<(usize, usize)>, Vec<WordType>, Vec<Token>);
+
+fn test_decoded_values(arguments_spans_preferred_types_and_resolved: DecodedValueCase) {
+    let (arguments, spans, preferred_types, resolved) =
+        arguments_spans_preferred_types_and_resolved;
+    let data = ethabi::encode(&arguments);
+    let result = decode_arguments(&hex::encode(&data), &DecodingOptions::default()).unwrap();
+    let values = DecodedValue::from_tokens(&result.tokens, &data);
+    assert_eq!(
+        values
+            .iter()
+            .map(|value| (value.span().start, value.span().end))
+            .collect::<Vec<_>>(),
+        spans
+    );
+    let to_tokens = |resolution: &Resolution| -> Vec<Token> {
+        values
+            .iter()
+            .map(|value| value.to_token(resolution))
+            .collect()
+    };
+    assert_eq!(to_tokens(&Resolution::default()), result.tokens);
+    assert_eq!(to_tokens(&Resolution::prefer(preferred_types)), resolved);
+}
+
+fn test_visit(tokens_descend_into_arrays_and_paths: (Vec<Token>, bool, Vec<Vec<usize>>)) {
+    let (tokens, descend_into_arrays, expected_paths) = tokens_descend_into_arrays_and_paths;
+    let mut paths = Vec::new();
+    visit::visit_tokens(&tokens, &mut |path: &[usize], token: &Token| {
+        paths.push(path.to_vec());
+        descend_into_arrays || !matches!(token, Token::Array(_))
+    });
+    assert_eq!(paths, expected_paths);
+}
+
+fn test_fold(tokens_and_folded: (Vec<Token>, Vec<Token>)) {
+    let (tokens, expected_tokens) = tokens_and_folded;
+    let folded = visit::fold_tokens(tokens, &mut |path: &[usize], token: Token| match token {
+        Token::Uint(_) => Token::Uint(U256::from(path.len())),
+        Token::Tuple(mut tokens) if tokens.len() == 1 => tokens.remove(0),
+        token => token,
+    });
+    assert_eq!(folded, expected_tokens);
+}
+
+fn test_query(query_and_paths: (&str, Vec<Vec<usize>>)) {
+    let (query, expected_paths) = query_and_paths;
+    let tokens = vec![
+        Token::Uint(U256::from(5)),
+        Token::Array(vec![
+            Token::Tuple(vec![
+                address_token_from_string("0xabcdef0123456789abcdef0123456789abcdef01"),
+                Token::Array(vec![Token::Uint(U256::from(6))]),
+            ]),
+            Token::Tuple(vec![
+                Token::Uint(U256::from(7)),
+                bytes_token_from_string("abcdef"),
+            ]),
+        ]),
+        Token::Array(vec![]),
+    ];
+    let query: Query = query.parse().unwrap();
+    let selected = query.select(&tokens);
+    let paths: Vec<Vec<usize>> = selected.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(paths, expected_paths);
+    for (path, token) in selected {
+        assert_eq!(get_token(&tokens, &path), Some(token));
+    }
+    assert_eq!(query.to_string().parse::<Query>().unwrap(), query);
+}
+
+fn test_invalid_query(query_and_error: (&str, QueryError)) {
+    let (query, error) = query_and_error;
+    assert_eq!(query.parse::<Query>(), Err(error));
+}
+
+fn test_decodes_large_input_quickly(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let start = std::time::Instant::now();
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    let elapsed = start.elapsed();
+    println!(
+        "Decoded {} words in {:?}",
+        encoded_arguments.len() / 64,
+        elapsed
+    );
+    assert_eq!(reencode_with_trailing_data(&result), encoded_arguments);
+    // Without memoization these inputs take minutes to decode
+    assert!(elapsed < std::time::Duration::from_secs(5));
+}
+
+// Words that could be offsets or lengths reaching past a shorter prefix mean that the whole data
+// is one encoding, even if the layout found for it is not the one it was encoded from
+fn test_does_not_cut_off_complete_encoding(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let result = decode_arguments(&encoded_arguments, &DecodingOptions::default()).unwrap();
+    assert_eq!(result.trailing_data, None);
+    assert!(result.round_trip);
+    assert_eq!(reencode_with_trailing_data(&result), encoded_arguments);
+}
+
+fn test_finds_end_of_large_input_quickly(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let data = format!("{}{}", encoded_arguments, "01".repeat(32));
+    let start = std::time::Instant::now();
+    let result = decode_arguments(&data, &DecodingOptions::default()).unwrap();
+    let elapsed = start.elapsed();
+    assert_eq!(reencode_with_trailing_data(&result), data);
+    let trailing_data = result.trailing_data.expect("Trailing data not detected");
+    assert_eq!(trailing_data.data, vec![1; 32]);
+    // Decoding every prefix from scratch takes minutes
+    assert!(elapsed < std::time::Duration::from_secs(5));
+}
+
+// Ending the search at the first failed rerun has to find the same layout as trying every
+// combination of invalid markers
+fn test_pruned_reruns_find_same_layout(arguments: Vec<Token>) {
+    let words = to_words(&ethabi::encode(&arguments));
+    let exhaustive = try_decode_words(
+        &words,
+        &DecodingOptions::default(),
+        &mut Budget::with_exhaustive_reruns(usize::MAX),
+    );
+    let pruned = try_decode_words(
+        &words,
+        &DecodingOptions::default(),
+        &mut Budget::with_exhaustive_reruns(0),
+    );
+    assert!(exhaustive.is_some());
+    assert_eq!(pruned, exhaustive);
+}
+
+fn test_solver_decodes_wide_head_quickly(arguments: Vec<Token>) {
+    let encoded_arguments = hex::encode(ethabi::encode(&arguments));
+    let solver = constraint_solver::ConstraintSolver::default();
+    let start = std::time::Instant::now();
+    let result =
+        decode_arguments_with_engine(&encoded_arguments, &DecodingOptions::default(), &solver)
+            .unwrap();
+    let elapsed = start.elapsed();
+    println!("Decoded in {:?}", elapsed);
+    assert!(!result.partial);
+    assert_eq!(reencode_with_trailing_data(&result), encoded_arguments);
+    // Enumerating every combination of offsets in the head takes minutes
+    assert!(elapsed < std::time::Duration::from_secs(5));
+}
+
+// Head of 20 numbers that could all be offsets into the content of the bytes following them
+fn wide_head(content: Vec<u8>) -> Vec<Token> {
+    (0..20)
+        .map(|i| Token::Uint(U256::from(32 * (22 + i))))
+        .chain([Token::Bytes(content)])
+        .collect()
+}
+
+// Resembles the orders passed to Seaport's fulfillAvailableAdvancedOrders
+fn seaport_order(i: u64) -> Token {
+    let address = |j: u64| Token::Address(ethereum_types::H160::from_low_u64_be(0xabcdef0123 + j));
+    let uint = |j: u64| Token::Uint(U256::from(j));
+    let offer = Token::Tuple(vec![
+        uint(2),
+        address(i + 2),
+        uint(1000 + i),
+        uint(1),
+        uint(1),
+    ]);
+    let consideration = (0..3)
+        .map(|j| {
+            Token::Tuple(vec![
+                uint(0),
+                address(0),
+                uint(0),
+                uint(10000 + j),
+                uint(10000 + j),
+                address(i + j + 3),
+            ])
+        })
+        .collect();
+    let parameters = Token::Tuple(vec![
+        address(i),
+        address(i + 1),
+        Token::Array(vec![offer]),
+        Token::Array(consideration),
+        uint(0),
+        uint(1670000000),
+        uint(1680000000),
+        Token::FixedBytes(vec![0; 32]),
+        uint(123456789 + i),
+        Token::FixedBytes(vec![0x0a; 32]),
+        uint(3),
+    ]);
+    Token::Tuple(vec![
+        parameters,
+        uint(1),
+        uint(1),
+        Token::Bytes(vec![0xab; 65]),
+        Token::Bytes(vec![]),
+    ])
 }
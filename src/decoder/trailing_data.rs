@@ -0,0 +1,319 @@
+use ethabi::token::Token;
+
+use super::annotation::{annotate_words, WordRole};
+use super::is_canonical_encoding;
+use super::layout_engine::LayoutEngine;
+use super::options::DecodingOptions;
+use super::parse_context::Budget;
+use super::parse_token::{contains_dynamic_type, get_param_type};
+use super::pointer_graph::PointerGraph;
+use super::word::{words_to_bytes, Word};
+
+/// Seaport integrators tag their orders by appending 4 bytes derived from their domain
+const KNOWN_DOMAIN_TAGS: [([u8; 4], &str); 1] = [([0x36, 0x0c, 0x6e, 0xbe], "opensea.io")];
+
+/// Data that was appended after the abi encoded arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailingData {
+    /// Raw bytes following the encoded arguments
+    pub data: Vec<u8>,
+    /// Seaport style domain tag at the end of the trailing data (if present)
+    pub domain_tag: Option<DomainTag>,
+}
+
+/// 4 byte tag appended to calldata to mark the frontend / domain that created it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainTag {
+    /// The raw tag bytes
+    pub tag: [u8; 4],
+    /// Domain the tag belongs to if it is a known one
+    pub domain: Option<&'static str>,
+}
+
+impl TrailingData {
+    /// Parses hex encoded trailing data, returns `None` if there is none
+    pub fn from_hex(trailing_data: &str) -> Option<TrailingData> {
+        if trailing_data.is_empty() {
+            return None;
+        }
+        // Unaligned data might end in half a byte which we pad with a zero
+        let mut trailing_data = trailing_data.to_string();
+        if trailing_data.len() % 2 == 1 {
+            trailing_data.push('0');
+        }
+        let data = hex::decode(trailing_data).ok()?;
+        let domain_tag = get_domain_tag(&data);
+        Some(TrailingData { data, domain_tag })
+    }
+}
+
+// The tag is the last 4 bytes, everything before it (if anything) has to be zero padding
+fn get_domain_tag(data: &[u8]) -> Option<DomainTag> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (padding, tag) = data.split_at(data.len() - 4);
+    if padding.iter().any(|byte| *byte != 0) || tag.iter().all(|byte| *byte == 0) {
+        return None;
+    }
+    let tag: [u8; 4] = tag.try_into().ok()?;
+    let domain = KNOWN_DOMAIN_TAGS
+        .iter()
+        .find(|(known_tag, _)| *known_tag == tag)
+        .map(|(_, domain)| *domain);
+    Some(DomainTag { tag, domain })
+}
+
+/// Decodes the complete words of the data and, if that does not yield a structured decoding,
+/// searches for the longest prefix that does (leaving the zeros before a domain tag out of it if
+/// possible). Returns the number of words the decoding consumed and the layouts of these words
+/// found by the engine, best first.
+///
+/// Data appended to the arguments is not referenced by them, so only the ends of values whose
+/// length is one of the words are considered, and a prefix is skipped if any of its words could
+/// be an offset or a length reaching into the words after it (see `references_dropped_words`).
+/// All attempts share the budget, which bounds the whole search and keeps the results of parsing
+/// ranges of the data that every prefix contains.
+pub fn decode_without_trailing_data(
+    words: &[Word],
+    options: &DecodingOptions,
+    engine: &dyn LayoutEngine,
+    budget: &mut Budget,
+) -> Option<(usize, Vec<Vec<Token>>)> {
+    let layouts = decode_fully_consumed(words, options, engine, budget);
+    if !layouts.is_empty() {
+        return Some((words.len(), layouts));
+    }
+    let graph = PointerGraph::from_words(words);
+    let padding_start = get_tag_padding_start(words);
+    for length in get_candidate_ends(words, &graph, padding_start) {
+        // Prefixes only decode completely by cutting through values that were left undecoded, so
+        // once a limit is reached the data is decoded without looking for its end
+        if budget.limit_reached() || !budget.check(options) {
+            return None;
+        }
+        // Zeros before a domain tag are its padding rather than values, so lengths may only reach
+        // into them if the layout reads the zeros as values
+        let values_end = if length >= padding_start {
+            words.len() - 1
+        } else {
+            length
+        };
+        if continues_past(&graph, length) {
+            continue;
+        }
+        let prefix = &words[..length];
+        let layouts: Vec<_> = decode_fully_consumed(prefix, options, engine, budget)
+            .into_iter()
+            .filter(|tokens| !references_dropped_words(tokens, prefix, values_end, &graph))
+            .collect();
+        if !layouts.is_empty() {
+            return Some((length, layouts));
+        }
+    }
+    None
+}
+
+// Start of the zero words before a domain tag in the last word, or the number of words if the
+// data does not end in a tag
+fn get_tag_padding_start(words: &[Word]) -> usize {
+    if words
+        .last()
+        .is_none_or(|word| get_domain_tag(&word.bytes).is_none())
+    {
+        return words.len();
+    }
+    let zeros = words[..words.len() - 1]
+        .iter()
+        .rev()
+        .take_while(|word| word.bytes.iter().all(|byte| *byte == 0))
+        .count();
+    words.len() - 1 - zeros
+}
+
+// Ends before the end of the data of the content of byte strings and of arrays with static
+// elements. The ends in the padding of a domain tag come first, shortest first, as values are
+// rarely followed by zeros, followed by the others, longest first.
+fn get_candidate_ends(words: &[Word], graph: &PointerGraph, padding_start: usize) -> Vec<usize> {
+    let mut ends = Vec::new();
+    for length in graph.lengths() {
+        // The length of a dynamic value is at the position an offset points to
+        if graph.offsets_to(length.index).next().is_none() {
+            continue;
+        }
+        if let Some(bytes) = &length.bytes {
+            ends.push(bytes.end);
+        }
+        // Words after the elements of an array could just as well be part of larger elements as
+        // long as there are at least as many of them as elements
+        match length.elements {
+            Some(0) => ends.push(length.index + 1),
+            Some(elements) => {
+                let size = (words.len() - length.index - 1) / elements;
+                ends.push(length.index + 1 + elements * size);
+            }
+            None => {}
+        }
+    }
+    ends.retain(|end| *end < words.len());
+    ends.sort_unstable_by_key(|end| {
+        if *end >= padding_start {
+            (false, *end)
+        } else {
+            (true, usize::MAX - end)
+        }
+    });
+    ends.dedup();
+    ends
+}
+
+// Whether the words before the end could be the start of a longer encoding because one of the
+// words after it could be an offset relative to a tuple starting before it (i.e. the head of the
+// tuple goes on)
+fn continues_past(graph: &PointerGraph, end: usize) -> bool {
+    graph
+        .offsets()
+        .iter()
+        .any(|offset| offset.from >= end && offset.base < end)
+}
+
+// Whether a word of the layout of the words before the end of the data could point to the words
+// after it, or could be the length of a value reaching into them (up to the end of the values,
+// which only differs from the number of words in the padding of a domain tag). Offsets count if
+// they are relative to the tuples and arrays of the layout (or to those that the numbers in it
+// could point to), lengths wherever an offset could point to them.
+fn references_dropped_words(
+    tokens: &[Token],
+    words: &[Word],
+    values_end: usize,
+    graph: &PointerGraph,
+) -> bool {
+    let end = words.len();
+    let annotations = annotate_words(tokens, &words_to_bytes(words));
+    // Tuples of the layout, i.e. the values its offsets are relative to, and the starts of the
+    // elements of its arrays that are static values
+    let mut bases = vec![0];
+    let mut static_elements = Vec::new();
+    let mut last_tail = 0;
+    for (index, annotation) in annotations.iter().enumerate() {
+        if let WordRole::Offset { target } = annotation.role {
+            let offset = words[index].as_usize().unwrap_or(0) / 32;
+            bases.push(target - offset);
+            match annotations[target].role {
+                // The elements of an array could still be dynamic values if every one of them
+                // could be an offset
+                WordRole::Length { .. } => {
+                    let elements = words[target].as_usize().unwrap_or(0);
+                    let could_be_offsets =
+                        (target + 1..end.min(target + 1 + elements)).all(|index| {
+                            graph
+                                .offsets_from(index)
+                                .iter()
+                                .any(|offset| offset.base == target + 1)
+                        });
+                    if elements > 0 && could_be_offsets {
+                        bases.push(target + 1);
+                    } else {
+                        static_elements.push(target + 1);
+                    }
+                }
+                _ => bases.push(target),
+            }
+            last_tail = last_tail.max(target);
+        }
+    }
+    // Tuples that the numbers in the layout could point to
+    let layout_bases = bases.clone();
+    for (index, annotation) in annotations.iter().enumerate() {
+        if annotation.role == WordRole::HeadValue {
+            bases.extend(
+                graph
+                    .offsets_from(index)
+                    .iter()
+                    .filter(|offset| layout_bases.contains(&offset.base))
+                    .flat_map(|offset| [offset.to, offset.to + 1]),
+            );
+        }
+    }
+    let points_past = graph
+        .offsets()
+        .iter()
+        .any(|offset| offset.from < end && offset.to >= end && bases.contains(&offset.base));
+    // Any word that an offset could point to could be the length of a byte string reaching past
+    // the end, or of an array if the layout does not have static values where the offset would be
+    // relative to. The elements of an array in the last tail could also be larger and fill the data
+    // up to its end, which is weaker evidence (any word is the element of some array of small
+    // numbers), so the layout must not have offsets or lengths there either.
+    let reaches_past = (0..end).any(|index| {
+        let Some(length) = graph.length(index) else {
+            return false;
+        };
+        let longer_bytes = length
+            .bytes
+            .as_ref()
+            .is_some_and(|bytes| bytes.end > values_end);
+        let longer_elements = length
+            .elements
+            .is_some_and(|elements| index + 1 + elements > values_end);
+        let fills_data = index >= last_tail
+            && length.elements.is_some_and(|elements| {
+                elements > 0 && (graph.word_count() - index - 1).is_multiple_of(elements)
+            });
+        graph.offsets_to(index).any(|offset| {
+            let in_layout = bases.contains(&offset.base);
+            let in_static_values = !in_layout && static_elements.contains(&offset.base);
+            let in_pointers = !in_layout
+                && annotations.get(offset.base).is_some_and(|annotation| {
+                    matches!(
+                        annotation.role,
+                        WordRole::Offset { .. } | WordRole::Length { .. }
+                    )
+                });
+            longer_bytes
+                || longer_elements && !in_static_values
+                || fills_data && !in_static_values && !in_pointers
+        })
+    });
+    points_past || reaches_past
+}
+
+// Layouts of the words that are complete abi encodings of them
+fn decode_fully_consumed(
+    words: &[Word],
+    options: &DecodingOptions,
+    engine: &dyn LayoutEngine,
+    budget: &mut Budget,
+) -> Vec<Vec<Token>> {
+    if words.is_empty() {
+        return Vec::new();
+    }
+    engine
+        .decode_layouts(words, options, budget)
+        .into_iter()
+        // Undecoded regions say nothing about where the encoding ends
+        .filter(|(tokens, partial)| !partial && consumes_all_words(tokens, words, options))
+        .map(|(tokens, _)| tokens)
+        .collect()
+}
+
+// A layout is considered a complete abi encoding if it contains dynamic types (whose offsets and
+// lengths determine where the encoding ends) and it re-encodes to exactly the words. Non canonical
+// encodings never re-encode to the same data, so for those it is enough that ethabi decodes the
+// words to the same tokens.
+fn consumes_all_words(tokens: &[Token], words: &[Word], options: &DecodingOptions) -> bool {
+    if !tokens.iter().any(contains_dynamic_type) {
+        return false;
+    }
+    if is_canonical_encoding(tokens, words) {
+        return true;
+    }
+    if !options.allow_non_canonical {
+        return false;
+    }
+    let param_types: Vec<_> = tokens.iter().map(get_param_type).collect();
+    let data = words_to_bytes(words);
+    match ethabi::decode(&param_types, &data) {
+        Ok(decoded_tokens) => decoded_tokens == tokens,
+        _ => false,
+    }
+}
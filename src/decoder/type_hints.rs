@@ -0,0 +1,439 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ethabi::param_type::{ParamType, Reader, Writer};
+use ethabi::token::Token;
+
+use super::empty_values::is_empty_value;
+use super::error::TypeHintsError;
+use super::pins::AllowedRoles;
+use super::word::Word;
+
+/// Known (or unknown) type of one value
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeHint {
+    /// Value of unknown type, which is either a single static word or a value behind an offset
+    Unknown,
+    /// Value of a fully known type
+    Type(ParamType),
+    /// Array with partially known elements
+    Array(Box<TypeHint>),
+    /// Fixed size array with partially known elements, which is expected behind an offset
+    FixedArray(Box<TypeHint>, usize),
+    /// Tuple with partially known elements, which is expected behind an offset
+    Tuple(Vec<TypeHint>),
+}
+
+impl TypeHint {
+    /// Array of the hinted elements, which is fully known if its elements are
+    pub fn array(element: TypeHint) -> Self {
+        match element {
+            TypeHint::Type(param_type) => TypeHint::Type(ParamType::Array(Box::new(param_type))),
+            element => TypeHint::Array(Box::new(element)),
+        }
+    }
+
+    /// Fixed size array of the hinted elements, which is fully known if its elements are
+    pub fn fixed_array(element: TypeHint, length: usize) -> Self {
+        match element {
+            TypeHint::Type(param_type) => {
+                TypeHint::Type(ParamType::FixedArray(Box::new(param_type), length))
+            }
+            element => TypeHint::FixedArray(Box::new(element), length),
+        }
+    }
+
+    /// Tuple of the hinted elements, which is fully known if all of its elements are
+    pub fn tuple(elements: Vec<TypeHint>) -> Self {
+        if elements
+            .iter()
+            .all(|element| matches!(element, TypeHint::Type(_)))
+        {
+            let param_types = elements
+                .into_iter()
+                .map(|element| match element {
+                    TypeHint::Type(param_type) => param_type,
+                    _ => unreachable!(),
+                })
+                .collect();
+            TypeHint::Type(ParamType::Tuple(param_types))
+        } else {
+            TypeHint::Tuple(elements)
+        }
+    }
+}
+
+/// Partially known types of the arguments, which the decoding has to be consistent with
+///
+/// Hints are written like a function signature without name, in which `?` stands for a value of
+/// unknown type: `(address,?,bytes,?[])`. Known static tuples and fixed size arrays take up as
+/// many words as their encoding, every other hint takes up one word in the head. Static values
+/// are decoded as their hinted type and the heuristics choose the types of all others.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::{DecodingOptions, TypeHints};
+/// use ethabi::Token;
+///
+/// // Without hints this is an empty array
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000000";
+/// let options = DecodingOptions {
+///     type_hints: Some("(address,?)".parse::<TypeHints>().unwrap()),
+///     ..Default::default()
+/// };
+/// let result = abi_decoder::decode_arguments_with_options(encoded, &options).unwrap();
+/// assert!(matches!(result.tokens[0], Token::Address(_)));
+/// assert_eq!(result.tokens[1], Token::Uint(0.into()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeHints(pub Vec<TypeHint>);
+
+impl TypeHints {
+    /// Roles each word may take in a decoding of the words consistent with the hints, as far as
+    /// the hints and the offsets they point out determine the layout
+    pub(crate) fn allowed_roles(&self, words: &[Word]) -> Vec<AllowedRoles> {
+        let mut walker = RoleWalker {
+            words,
+            roles: vec![AllowedRoles::default(); words.len()],
+        };
+        walker.walk_tuple(&self.0, 0);
+        walker.roles
+    }
+
+    /// Converts the decoded arguments to the hinted types, returns `None` if they are not
+    /// consistent with the hints
+    pub(crate) fn apply(&self, tokens: &[Token]) -> Option<Vec<Token>> {
+        apply_hints(&self.0, tokens)
+    }
+}
+
+impl fmt::Display for TypeHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeHint::Unknown => write!(f, "?"),
+            TypeHint::Type(param_type) => write!(f, "{}", Writer::write(param_type)),
+            TypeHint::Array(element) => write!(f, "{}[]", element),
+            TypeHint::FixedArray(element, length) => write!(f, "{}[{}]", element, length),
+            TypeHint::Tuple(elements) => write!(f, "{}", TypeHints(elements.clone())),
+        }
+    }
+}
+
+impl fmt::Display for TypeHints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (index, hint) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", hint)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl FromStr for TypeHints {
+    type Err = TypeHintsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let template: String = s.split_whitespace().collect();
+        let mut parser = TemplateParser {
+            template: &template,
+            position: 0,
+        };
+        let hints = parser.parse_tuple()?;
+        if parser.position != template.len() {
+            return Err(TypeHintsError::Syntax(parser.position));
+        }
+        Ok(TypeHints(hints))
+    }
+}
+
+// Follows the hints over the data to find the roles they require of the words
+struct RoleWalker<'a> {
+    words: &'a [Word],
+    roles: Vec<AllowedRoles>,
+}
+
+impl<'a> RoleWalker<'a> {
+    fn walk_tuple(&mut self, hints: &[TypeHint], base: usize) -> Option<()> {
+        let mut head = base;
+        for hint in hints {
+            match hint {
+                TypeHint::Unknown => head += 1,
+                TypeHint::Type(param_type) if !param_type.is_dynamic() => {
+                    for index in head..head + get_static_size(param_type) {
+                        *self.roles.get_mut(index)? = AllowedRoles::VALUE;
+                    }
+                    head += get_static_size(param_type);
+                }
+                hint => {
+                    *self.roles.get_mut(head)? = AllowedRoles::OFFSET;
+                    let offset = self.words[head].as_usize()?;
+                    if offset % 32 != 0 {
+                        return None;
+                    }
+                    self.walk_dynamic(hint, base + offset / 32);
+                    head += 1;
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn walk_dynamic(&mut self, hint: &TypeHint, start: usize) -> Option<()> {
+        match hint {
+            TypeHint::Type(ParamType::Bytes) | TypeHint::Type(ParamType::String) => {
+                *self.roles.get_mut(start)? = AllowedRoles::BYTES_LENGTH;
+                Some(())
+            }
+            TypeHint::Type(ParamType::Array(element)) => {
+                self.walk_array(&TypeHint::Type((**element).clone()), start)
+            }
+            TypeHint::Array(element) => self.walk_array(element, start),
+            TypeHint::Type(ParamType::FixedArray(element, length)) => {
+                let elements = vec![TypeHint::Type((**element).clone()); *length];
+                self.walk_tuple_tail(&elements, start)
+            }
+            TypeHint::FixedArray(element, length) => {
+                let elements = vec![(**element).clone(); *length];
+                self.walk_tuple_tail(&elements, start)
+            }
+            TypeHint::Type(ParamType::Tuple(param_types)) => {
+                let elements: Vec<TypeHint> =
+                    param_types.iter().cloned().map(TypeHint::Type).collect();
+                self.walk_tuple_tail(&elements, start)
+            }
+            TypeHint::Tuple(elements) => self.walk_tuple_tail(elements, start),
+            _ => None,
+        }
+    }
+
+    fn walk_array(&mut self, element: &TypeHint, start: usize) -> Option<()> {
+        *self.roles.get_mut(start)? = AllowedRoles::ARRAY_LENGTH;
+        let length = self.words[start].as_usize()?;
+        if length > self.words.len() {
+            return None;
+        }
+        self.walk_tuple(&vec![element.clone(); length], start + 1)
+    }
+
+    fn walk_tuple_tail(&mut self, elements: &[TypeHint], start: usize) -> Option<()> {
+        *self.roles.get_mut(start)? = AllowedRoles::TUPLE_HEAD;
+        self.walk_tuple(elements, start)
+    }
+}
+
+struct TemplateParser<'a> {
+    template: &'a str,
+    position: usize,
+}
+
+impl<'a> TemplateParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.template.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), TypeHintsError> {
+        if self.peek() != Some(byte) {
+            return Err(TypeHintsError::Syntax(self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Takes the characters up to the next delimiter
+    fn take_name(&mut self) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| !b"?,()[]".contains(&byte)) {
+            self.position += 1;
+        }
+        &self.template[start..self.position]
+    }
+
+    fn parse_tuple(&mut self) -> Result<Vec<TypeHint>, TypeHintsError> {
+        self.expect(b'(')?;
+        let mut hints = Vec::new();
+        if self.peek() == Some(b')') {
+            self.position += 1;
+            return Ok(hints);
+        }
+        loop {
+            hints.push(self.parse_hint()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b')') => {
+                    self.position += 1;
+                    return Ok(hints);
+                }
+                _ => return Err(TypeHintsError::Syntax(self.position)),
+            }
+        }
+    }
+
+    fn parse_hint(&mut self) -> Result<TypeHint, TypeHintsError> {
+        let mut hint = match self.peek() {
+            Some(b'?') => {
+                self.position += 1;
+                TypeHint::Unknown
+            }
+            Some(b'(') => TypeHint::tuple(self.parse_tuple()?),
+            _ => {
+                let start = self.position;
+                let name = self.take_name();
+                if name.is_empty() {
+                    return Err(TypeHintsError::Syntax(start));
+                }
+                TypeHint::Type(
+                    read_type_name(name)
+                        .ok_or_else(|| TypeHintsError::UnknownType(name.to_string()))?,
+                )
+            }
+        };
+        while self.peek() == Some(b'[') {
+            self.position += 1;
+            let start = self.position;
+            let length = self.take_name();
+            self.expect(b']')?;
+            hint = if length.is_empty() {
+                TypeHint::array(hint)
+            } else {
+                let length = length.parse().map_err(|_| TypeHintsError::Syntax(start))?;
+                TypeHint::fixed_array(hint, length)
+            };
+        }
+        Ok(hint)
+    }
+}
+
+// Elementary type with the name, which the reader of ethabi reads for any name (as uint8 if it
+// does not know it)
+fn read_type_name(name: &str) -> Option<ParamType> {
+    let is_size = |size: &str, valid: fn(usize) -> bool| {
+        size.parse().is_ok_and(valid) && !size.starts_with('0')
+    };
+    let is_valid = match name {
+        "address" | "bool" | "string" | "bytes" | "uint" | "int" => true,
+        _ => {
+            if let Some(size) = name.strip_prefix("bytes") {
+                is_size(size, |size| (1..=32).contains(&size))
+            } else if let Some(size) = name.strip_prefix("uint").or(name.strip_prefix("int")) {
+                is_size(size, |size| (8..=256).contains(&size) && size % 8 == 0)
+            } else {
+                false
+            }
+        }
+    };
+    is_valid.then(|| Reader::read(name).ok()).flatten()
+}
+
+// Static tuples and fixed size arrays are decoded as their individual words, so a known static
+// hint consumes as many tokens as it takes to fill its encoding
+fn apply_hints(hints: &[TypeHint], tokens: &[Token]) -> Option<Vec<Token>> {
+    let mut index = 0;
+    let mut hinted_tokens = Vec::new();
+    for hint in hints {
+        match hint {
+            TypeHint::Type(param_type) if !param_type.is_dynamic() => {
+                let size = get_static_size(param_type) * 32;
+                let mut data = Vec::new();
+                while data.len() < size {
+                    let token = tokens.get(index)?;
+                    if token.is_dynamic() {
+                        return None;
+                    }
+                    data.extend(ethabi::encode(std::slice::from_ref(token)));
+                    index += 1;
+                }
+                hinted_tokens.push(decode_static(param_type, &data)?);
+            }
+            hint => {
+                hinted_tokens.push(apply_hint(hint, tokens.get(index)?)?);
+                index += 1;
+            }
+        }
+    }
+    (index == tokens.len()).then_some(hinted_tokens)
+}
+
+fn apply_hint(hint: &TypeHint, token: &Token) -> Option<Token> {
+    match (hint, token) {
+        (TypeHint::Unknown, _) => Some(token.clone()),
+        (TypeHint::Type(param_type), _) => convert_token(param_type, token),
+        (TypeHint::Array(element), Token::Array(tokens)) => Some(Token::Array(
+            tokens
+                .iter()
+                .map(|token| apply_hint(element, token))
+                .collect::<Option<_>>()?,
+        )),
+        (TypeHint::FixedArray(element, length), Token::Tuple(tokens))
+        | (TypeHint::FixedArray(element, length), Token::FixedArray(tokens)) => {
+            let elements = vec![(**element).clone(); *length];
+            Some(Token::FixedArray(apply_hints(&elements, tokens)?))
+        }
+        (TypeHint::Tuple(elements), Token::Tuple(tokens))
+        | (TypeHint::Tuple(elements), Token::FixedArray(tokens)) => {
+            Some(Token::Tuple(apply_hints(elements, tokens)?))
+        }
+        _ => None,
+    }
+}
+
+// Decodes the value as the given type, which has to encode the same as the decoded token
+fn convert_token(param_type: &ParamType, token: &Token) -> Option<Token> {
+    if !param_type.is_dynamic() {
+        if token.is_dynamic() {
+            return None;
+        }
+        return decode_static(param_type, &ethabi::encode(std::slice::from_ref(token)));
+    }
+    match (param_type, token) {
+        // Empty values are decoded as empty arrays
+        (ParamType::Bytes, _) if is_empty_value(token) => Some(Token::Bytes(Vec::new())),
+        (ParamType::String, _) if is_empty_value(token) => Some(Token::String(String::new())),
+        (ParamType::Array(_), _) if is_empty_value(token) => Some(Token::Array(Vec::new())),
+        (ParamType::Bytes, Token::Bytes(bytes)) => Some(Token::Bytes(bytes.clone())),
+        (ParamType::Bytes, Token::String(string)) => Some(Token::Bytes(string.clone().into())),
+        (ParamType::String, Token::Bytes(bytes)) => {
+            String::from_utf8(bytes.clone()).ok().map(Token::String)
+        }
+        (ParamType::String, Token::String(string)) => Some(Token::String(string.clone())),
+        (ParamType::Array(element), Token::Array(tokens)) => Some(Token::Array(
+            tokens
+                .iter()
+                .map(|token| convert_token(element, token))
+                .collect::<Option<_>>()?,
+        )),
+        (ParamType::FixedArray(element, length), Token::Tuple(tokens))
+        | (ParamType::FixedArray(element, length), Token::FixedArray(tokens)) => {
+            let elements = vec![TypeHint::Type((**element).clone()); *length];
+            Some(Token::FixedArray(apply_hints(&elements, tokens)?))
+        }
+        (ParamType::Tuple(param_types), Token::Tuple(tokens))
+        | (ParamType::Tuple(param_types), Token::FixedArray(tokens)) => {
+            let elements: Vec<TypeHint> = param_types.iter().cloned().map(TypeHint::Type).collect();
+            Some(Token::Tuple(apply_hints(&elements, tokens)?))
+        }
+        _ => None,
+    }
+}
+
+// Static value of the type encoded in the data, if the data is its canonical encoding
+fn decode_static(param_type: &ParamType, data: &[u8]) -> Option<Token> {
+    if data.len() != get_static_size(param_type) * 32 {
+        return None;
+    }
+    let token = ethabi::decode(std::slice::from_ref(param_type), data)
+        .ok()?
+        .remove(0);
+    (ethabi::encode(std::slice::from_ref(&token)) == data).then_some(token)
+}
+
+fn get_static_size(param_type: &ParamType) -> usize {
+    match param_type {
+        ParamType::FixedArray(element, length) => get_static_size(element) * length,
+        ParamType::Tuple(param_types) => param_types.iter().map(get_static_size).sum(),
+        _ => 1,
+    }
+}
@@ -0,0 +1,284 @@
+use ethabi::ParamType;
+
+/// Deviation of the given data from the canonical abi encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingDeviation {
+    /// Index of the (32 byte) word in which the deviation was found
+    pub word_index: usize,
+    /// What is wrong with the word
+    pub kind: DeviationKind,
+}
+
+/// Kinds of deviations from the canonical abi encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviationKind {
+    /// Offset does not point to the word where the canonical encoding places the tail
+    NonMinimalOffset {
+        /// Word index at which the canonical encoding places the tail
+        expected: usize,
+        /// Word index the offset points to
+        actual: usize,
+    },
+    /// Padding after the content of bytes / strings / fixed bytes is not zero
+    DirtyPadding,
+    /// Unused high order bits of an address, bool or small integer are not zero (or not sign
+    /// extended for signed integers)
+    DirtyHighBits,
+    /// Words that are not referenced by any part of the encoding
+    UnreferencedGap {
+        /// Number of consecutive unreferenced words
+        length: usize,
+    },
+    /// Offset or length points outside of the data or the word is incomplete
+    InvalidData,
+}
+
+impl std::fmt::Display for EncodingDeviation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            DeviationKind::NonMinimalOffset { expected, actual } => write!(
+                f,
+                "Word {}: offset points to word {} instead of word {}",
+                self.word_index, actual, expected
+            ),
+            DeviationKind::DirtyPadding => {
+                write!(f, "Word {}: padding is not zero", self.word_index)
+            }
+            DeviationKind::DirtyHighBits => {
+                write!(f, "Word {}: unused high bits are not zero", self.word_index)
+            }
+            DeviationKind::UnreferencedGap { length } => write!(
+                f,
+                "Words {}-{}: not referenced by the encoding",
+                self.word_index,
+                self.word_index + length - 1
+            ),
+            DeviationKind::InvalidData => write!(f, "Word {}: invalid data", self.word_index),
+        }
+    }
+}
+
+/// Walks the layout of the given types over the data and reports every deviation from the
+/// canonical encoding of the same values
+pub fn validate_encoding(param_types: &[ParamType], data: &[u8]) -> Vec<EncodingDeviation> {
+    let mut validator = Validator {
+        words: data.chunks(32).collect(),
+        referenced: vec![false; data.len().div_ceil(32)],
+        deviations: Vec::new(),
+    };
+    validator.validate_tuple(param_types, 0);
+    validator.add_unreferenced_gaps();
+    validator
+        .deviations
+        .sort_by_key(|deviation| deviation.word_index);
+    validator.deviations
+}
+
+struct Validator<'a> {
+    words: Vec<&'a [u8]>,
+    referenced: Vec<bool>,
+    deviations: Vec<EncodingDeviation>,
+}
+
+impl<'a> Validator<'a> {
+    // Validates the head of a tuple starting at `base` and all of its tails. Returns the index of
+    // the word after the last word belonging to the tuple.
+    fn validate_tuple(&mut self, param_types: &[ParamType], base: usize) -> Option<usize> {
+        let head_length: usize = param_types.iter().map(get_head_length).sum();
+        let mut head = base;
+        let mut expected_tail = base + head_length;
+        let mut end = base + head_length;
+        for param_type in param_types {
+            if param_type.is_dynamic() {
+                let offset = self.read_usize(head)?;
+                if offset % 32 != 0 {
+                    self.add_deviation(head, DeviationKind::InvalidData);
+                    return None;
+                }
+                let tail = base + offset / 32;
+                if tail >= self.words.len() {
+                    self.add_deviation(head, DeviationKind::InvalidData);
+                    return None;
+                }
+                if tail != expected_tail {
+                    self.add_deviation(
+                        head,
+                        DeviationKind::NonMinimalOffset {
+                            expected: expected_tail,
+                            actual: tail,
+                        },
+                    );
+                }
+                // Continue from the actual tail so that one misplaced tail is reported once
+                let tail_end = self.validate_dynamic(param_type, tail)?;
+                expected_tail = tail_end;
+                end = end.max(tail_end);
+                head += 1;
+            } else {
+                self.validate_static(param_type, head)?;
+                head += get_head_length(param_type);
+            }
+        }
+        Some(end)
+    }
+
+    fn validate_dynamic(&mut self, param_type: &ParamType, start: usize) -> Option<usize> {
+        match param_type {
+            ParamType::Bytes | ParamType::String => {
+                let length = self.read_length(start)?;
+                let length_words = length.div_ceil(32);
+                if start + length_words >= self.words.len() {
+                    self.add_deviation(start, DeviationKind::InvalidData);
+                    return None;
+                }
+                for i in start + 1..start + 1 + length_words {
+                    self.read_word(i)?;
+                }
+                let padding = length_words * 32 - length;
+                if padding > 0 {
+                    let last_word = start + length_words;
+                    if self.words[last_word][32 - padding..]
+                        .iter()
+                        .any(|byte| *byte != 0)
+                    {
+                        self.add_deviation(last_word, DeviationKind::DirtyPadding);
+                    }
+                }
+                Some(start + 1 + length_words)
+            }
+            ParamType::Array(element_type) => {
+                let length = self.read_length(start)?;
+                let element_types = vec![(**element_type).clone(); length];
+                self.validate_tuple(&element_types, start + 1)
+            }
+            ParamType::FixedArray(element_type, length) => {
+                let element_types = vec![(**element_type).clone(); *length];
+                self.validate_tuple(&element_types, start)
+            }
+            ParamType::Tuple(param_types) => self.validate_tuple(param_types, start),
+            _ => panic!("Static type passed to validate_dynamic"),
+        }
+    }
+
+    fn validate_static(&mut self, param_type: &ParamType, index: usize) -> Option<()> {
+        match param_type {
+            ParamType::Address => {
+                let word = self.read_word(index)?;
+                self.check_zero(index, &word[..12], DeviationKind::DirtyHighBits);
+            }
+            ParamType::Uint(size) => {
+                let word = self.read_word(index)?;
+                self.check_zero(index, &word[..32 - size / 8], DeviationKind::DirtyHighBits);
+            }
+            ParamType::Int(size) => {
+                let word = self.read_word(index)?;
+                let (high_bytes, value) = word.split_at(32 - size / 8);
+                let sign_byte = if value[0] & 0x80 == 0 { 0 } else { 0xff };
+                if high_bytes.iter().any(|byte| *byte != sign_byte) {
+                    self.add_deviation(index, DeviationKind::DirtyHighBits);
+                }
+            }
+            ParamType::Bool => {
+                let word = self.read_word(index)?;
+                if word[..31].iter().any(|byte| *byte != 0) || word[31] > 1 {
+                    self.add_deviation(index, DeviationKind::DirtyHighBits);
+                }
+            }
+            ParamType::FixedBytes(size) => {
+                let word = self.read_word(index)?;
+                self.check_zero(index, &word[*size..], DeviationKind::DirtyPadding);
+            }
+            ParamType::FixedArray(element_type, length) => {
+                let element_length = get_head_length(element_type);
+                for i in 0..*length {
+                    self.validate_static(element_type, index + i * element_length)?;
+                }
+            }
+            ParamType::Tuple(param_types) => {
+                let mut i = index;
+                for param_type in param_types {
+                    self.validate_static(param_type, i)?;
+                    i += get_head_length(param_type);
+                }
+            }
+            _ => panic!("Dynamic type passed to validate_static"),
+        }
+        Some(())
+    }
+
+    fn read_word(&mut self, index: usize) -> Option<&'a [u8]> {
+        match self.words.get(index) {
+            Some(word) if word.len() == 32 => {
+                self.referenced[index] = true;
+                Some(*word)
+            }
+            Some(_) => {
+                self.add_deviation(index, DeviationKind::InvalidData);
+                None
+            }
+            None => {
+                let last_word = self.words.len().saturating_sub(1);
+                self.add_deviation(last_word, DeviationKind::InvalidData);
+                None
+            }
+        }
+    }
+
+    // Reads an offset / length, values that cannot possibly fit into the data are invalid
+    fn read_usize(&mut self, index: usize) -> Option<usize> {
+        let word = self.read_word(index)?;
+        if word[..28].iter().any(|byte| *byte != 0) {
+            self.add_deviation(index, DeviationKind::InvalidData);
+            return None;
+        }
+        let mut value_bytes = [0; 4];
+        value_bytes.copy_from_slice(&word[28..]);
+        Some(u32::from_be_bytes(value_bytes) as usize)
+    }
+
+    fn read_length(&mut self, index: usize) -> Option<usize> {
+        let length = self.read_usize(index)?;
+        if length > self.words.len() * 32 {
+            self.add_deviation(index, DeviationKind::InvalidData);
+            return None;
+        }
+        Some(length)
+    }
+
+    fn check_zero(&mut self, index: usize, bytes: &[u8], kind: DeviationKind) {
+        if bytes.iter().any(|byte| *byte != 0) {
+            self.add_deviation(index, kind);
+        }
+    }
+
+    fn add_deviation(&mut self, word_index: usize, kind: DeviationKind) {
+        self.deviations.push(EncodingDeviation { word_index, kind });
+    }
+
+    fn add_unreferenced_gaps(&mut self) {
+        let mut i = 0;
+        while i < self.referenced.len() {
+            if self.referenced[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < self.referenced.len() && !self.referenced[i] {
+                i += 1;
+            }
+            self.add_deviation(start, DeviationKind::UnreferencedGap { length: i - start });
+        }
+    }
+}
+
+// Number of words a type occupies in the head of its parent
+fn get_head_length(param_type: &ParamType) -> usize {
+    if param_type.is_dynamic() {
+        return 1;
+    }
+    match param_type {
+        ParamType::FixedArray(element_type, length) => get_head_length(element_type) * length,
+        ParamType::Tuple(param_types) => param_types.iter().map(get_head_length).sum(),
+        _ => 1,
+    }
+}
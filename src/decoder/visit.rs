@@ -0,0 +1,182 @@
+use ethabi::token::Token;
+
+// Paths are the indices leading to a token, starting with the index of the argument followed by
+// the indices of the elements and fields (the same paths `annotate_words` and `get_token` use).
+// Paths of a single token start at that token, so the token itself has an empty path.
+
+/// Visits the tokens of decoded trees, parents before their elements and fields
+///
+/// Closures taking the path and the token implement it.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::visit_tokens;
+/// use ethabi::Token;
+///
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000002\
+///                0000000000000000000000000000000000000000000000000000000000001000\
+///                0000000000000000000000000000000000000000000000000000000000002000";
+/// let result = abi_decoder::decode_arguments(encoded).unwrap();
+/// let mut paths = Vec::new();
+/// visit_tokens(&result.tokens, &mut |path: &[usize], token: &Token| {
+///     if matches!(token, Token::Uint(_)) {
+///         paths.push(path.to_vec());
+///     }
+///     true
+/// });
+/// assert_eq!(paths, [[0, 0], [0, 1]]);
+/// ```
+pub trait Visit<'a> {
+    /// Called with the path of every token, returns whether to visit its elements and fields
+    fn visit(&mut self, path: &[usize], token: &'a Token) -> bool;
+}
+
+impl<'a, F: FnMut(&[usize], &'a Token) -> bool> Visit<'a> for F {
+    fn visit(&mut self, path: &[usize], token: &'a Token) -> bool {
+        self(path, token)
+    }
+}
+
+/// Transforms decoded trees, elements and fields before the tokens containing them
+///
+/// Closures taking the path and the token implement it.
+///
+/// Example
+/// ```rust
+/// use abi_decoder::fold_tokens;
+/// use ethabi::Token;
+///
+/// let tokens = vec![Token::Tuple(vec![Token::Bool(true)]), Token::Bool(false)];
+/// let folded = fold_tokens(tokens, &mut |_: &[usize], token: Token| match token {
+///     Token::Tuple(mut fields) if fields.len() == 1 => fields.remove(0),
+///     token => token,
+/// });
+/// assert_eq!(folded, [Token::Bool(true), Token::Bool(false)]);
+/// ```
+pub trait Fold {
+    /// Called with the path of every token after its elements and fields have been folded, returns
+    /// the token replacing it
+    fn fold(&mut self, path: &[usize], token: Token) -> Token;
+}
+
+impl<F: FnMut(&[usize], Token) -> Token> Fold for F {
+    fn fold(&mut self, path: &[usize], token: Token) -> Token {
+        self(path, token)
+    }
+}
+
+/// Visits the decoded arguments and everything nested in them
+pub fn visit_tokens<'a>(tokens: &'a [Token], visitor: &mut impl Visit<'a>) {
+    visit_children(tokens, &mut Vec::new(), visitor);
+}
+
+/// Visits the token and everything nested in it
+pub fn visit_token<'a>(token: &'a Token, visitor: &mut impl Visit<'a>) {
+    visit_with_path(token, &mut Vec::new(), visitor);
+}
+
+/// Folds the decoded arguments and everything nested in them
+pub fn fold_tokens(tokens: Vec<Token>, folder: &mut impl Fold) -> Vec<Token> {
+    fold_children(tokens, &mut Vec::new(), folder)
+}
+
+/// Folds the token and everything nested in it
+pub fn fold_token(token: Token, folder: &mut impl Fold) -> Token {
+    fold_with_path(token, &mut Vec::new(), folder)
+}
+
+// Both traversals keep an explicit stack of the values they are in instead of recursing, so deeply
+// nested tokens can be visited and folded on small stacks (like the ones the decoder runs on).
+
+fn visit_with_path<'a>(token: &'a Token, path: &mut Vec<usize>, visitor: &mut impl Visit<'a>) {
+    if !visitor.visit(path, token) {
+        return;
+    }
+    if let Token::Tuple(tokens) | Token::Array(tokens) | Token::FixedArray(tokens) = token {
+        visit_children(tokens, path, visitor);
+    }
+}
+
+fn visit_children<'a>(tokens: &'a [Token], path: &mut Vec<usize>, visitor: &mut impl Visit<'a>) {
+    let base = path.len();
+    let mut stack = vec![tokens.iter().enumerate()];
+    while let Some(children) = stack.last_mut() {
+        let Some((index, token)) = children.next() else {
+            stack.pop();
+            continue;
+        };
+        path.truncate(base + stack.len() - 1);
+        path.push(index);
+        if !visitor.visit(path, token) {
+            continue;
+        }
+        if let Token::Tuple(tokens) | Token::Array(tokens) | Token::FixedArray(tokens) = token {
+            stack.push(tokens.iter().enumerate());
+        }
+    }
+    path.truncate(base);
+}
+
+// Value whose elements or fields are being folded
+struct FoldFrame {
+    wrap: fn(Vec<Token>) -> Token,
+    folded: Vec<Token>,
+    remaining: std::vec::IntoIter<Token>,
+}
+
+impl FoldFrame {
+    fn new(wrap: fn(Vec<Token>) -> Token, tokens: Vec<Token>) -> Self {
+        FoldFrame {
+            wrap,
+            folded: Vec::with_capacity(tokens.len()),
+            remaining: tokens.into_iter(),
+        }
+    }
+}
+
+fn fold_with_path(token: Token, path: &mut Vec<usize>, folder: &mut impl Fold) -> Token {
+    let token = match token {
+        Token::Tuple(tokens) => Token::Tuple(fold_children(tokens, path, folder)),
+        Token::Array(tokens) => Token::Array(fold_children(tokens, path, folder)),
+        Token::FixedArray(tokens) => Token::FixedArray(fold_children(tokens, path, folder)),
+        token => token,
+    };
+    folder.fold(path, token)
+}
+
+fn fold_children(tokens: Vec<Token>, path: &mut Vec<usize>, folder: &mut impl Fold) -> Vec<Token> {
+    let base = path.len();
+    // The outermost frame collects the folded tokens and is never wrapped
+    let mut stack = vec![FoldFrame::new(Token::Tuple, tokens)];
+    loop {
+        let depth = stack.len();
+        let frame = stack
+            .last_mut()
+            .expect("The outermost frame is only popped at the end");
+        let Some(token) = frame.remaining.next() else {
+            let frame = stack.pop().expect("The stack is not empty");
+            let Some(parent) = stack.last_mut() else {
+                path.truncate(base);
+                return frame.folded;
+            };
+            path.truncate(base + depth - 1);
+            parent
+                .folded
+                .push(folder.fold(path, (frame.wrap)(frame.folded)));
+            continue;
+        };
+        let index = frame.folded.len();
+        path.truncate(base + depth - 1);
+        path.push(index);
+        match token {
+            Token::Tuple(tokens) => stack.push(FoldFrame::new(Token::Tuple, tokens)),
+            Token::Array(tokens) => stack.push(FoldFrame::new(Token::Array, tokens)),
+            Token::FixedArray(tokens) => stack.push(FoldFrame::new(Token::FixedArray, tokens)),
+            token => {
+                let token = folder.fold(path, token);
+                frame.folded.push(token);
+            }
+        }
+    }
+}
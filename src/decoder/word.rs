@@ -0,0 +1,46 @@
+use ethereum_types::U256;
+
+/// A 32 byte word of the encoded data
+///
+/// The decoder interprets every word as a potential offset / length many times, so the numeric
+/// value is computed once when the word is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Word {
+    pub bytes: [u8; 32],
+    // Value of the word if it fits into a usize (which every valid offset and length does)
+    number: Option<usize>,
+}
+
+impl Word {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        let value = U256::from_big_endian(&bytes);
+        let number = if value > U256::from(usize::MAX) {
+            None
+        } else {
+            Some(value.as_usize())
+        };
+        Word { bytes, number }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.number
+    }
+
+    pub fn as_u256(&self) -> U256 {
+        U256::from_big_endian(&self.bytes)
+    }
+}
+
+/// Splits the data into words, the length of the data has to be a multiple of 32
+pub fn to_words(data: &[u8]) -> Vec<Word> {
+    if !data.len().is_multiple_of(32) {
+        panic!("Invalid data length");
+    }
+    data.chunks(32)
+        .map(|chunk| Word::new(chunk.try_into().expect("Chunk is not 32 bytes long")))
+        .collect()
+}
+
+pub fn words_to_bytes(words: &[Word]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.bytes).collect()
+}
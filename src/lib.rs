@@ -21,6 +21,7 @@
 #![warn(missing_docs)]
 use ethabi::token::Token;
 
+mod client;
 mod decoder;
 #[cfg(test)]
 mod test_utils;
@@ -29,7 +30,29 @@ mod tests;
 mod transaction_data;
 mod utils;
 
-use decoder::chunk_and_decode_data;
+pub use client::{DecodedLog, Decoder};
+pub use decoder::annotation::{annotate_words, get_token, WordAnnotation, WordRole};
+pub use decoder::constraint_solver::ConstraintSolver;
+pub use decoder::decoded_value::{DecodedValue, Resolution, WordType};
+pub use decoder::error::{
+    DecodingError, PinError, PriorsError, QueryError, SignatureError, TypeHintsError,
+};
+pub use decoder::explain::{Explanation, Rejection, RejectionReason, Rule, RuleKind, WordTrace};
+pub use decoder::heuristic::{
+    AddressBook, Addresses, EmptyArrays, Gaps, Heuristic, Heuristics, LeftAlignedBytes,
+};
+pub use decoder::layout_engine::{GreedySearch, LayoutEngine};
+pub use decoder::options::DecodingOptions;
+pub use decoder::pins::{Pin, PinnedRole};
+pub use decoder::pointer_graph::{LengthCandidate, OffsetCandidate, PointerGraph};
+pub use decoder::priors::{Priors, Ratio};
+pub use decoder::query::Query;
+pub use decoder::signatures::Signatures;
+pub use decoder::trailing_data::{DomainTag, TrailingData};
+pub use decoder::type_hints::{TypeHint, TypeHints};
+pub use decoder::validation::{validate_encoding, DeviationKind, EncodingDeviation};
+pub use decoder::visit::{fold_token, fold_tokens, visit_token, visit_tokens, Fold, Visit};
+pub use decoder::DecodingResult;
 use transaction_data::get_encoded_arguments;
 #[doc(hidden)]
 pub use utils::print_parse_tree;
@@ -39,6 +62,10 @@ pub use utils::print_parse_tree;
 /// This function gets the calldata from of the given transaction (if it contains any), split off
 /// the first 4 bytes (which is the function signature) and then decodes the remaining data.
 ///
+/// Tuples with a single element are replaced by their element, so a function taking a single
+/// field struct decodes to the field. Use [`decode_transaction`] for the tokens of the layout
+/// itself, which keep these tuples and re-encode to the calldata.
+///
 /// Example
 /// ```rust
 /// tokio_test::block_on(async {
@@ -53,12 +80,153 @@ pub async fn decode_transaction_calldata(
     tx_hash: &str,
     provider_rpc_url: Option<&str>,
 ) -> Vec<Token> {
+    decode_transaction(tx_hash, provider_rpc_url)
+        .await
+        .tokens
+        .into_iter()
+        .map(decoder::parse_token::remove_single_element_tuples)
+        .collect()
+}
+
+/// Decodes the calldata of the given transaction and returns any data appended after the arguments
+///
+/// Works like [`decode_transaction_calldata`] but also reports data that follows the abi encoded
+/// arguments (such as the domain tags appended to Seaport orders) separately.
+///
+/// Example
+/// ```rust
+/// tokio_test::block_on(async {
+///   let tx_hash = "0x53ad65f13d7abec1423e1663e0d2c6852d7a60651248e565471ab722d1da9bed";
+///   let rpc_url = "https://rpc.ankr.com/eth";
+///   let result = abi_decoder::decode_transaction(tx_hash, Some(rpc_url)).await;
+///   assert!(result.trailing_data.is_none());
+/// })
+/// ```
+///
+/// # Panics
+///
+/// Panics if the transaction cannot be fetched. Decoding itself does not fail, as the calldata
+/// the provider returns is always hex.
+pub async fn decode_transaction(tx_hash: &str, provider_rpc_url: Option<&str>) -> DecodingResult {
+    decode_transaction_with_options(tx_hash, provider_rpc_url, &DecodingOptions::default())
+        .await
+        .expect("Calldata from the provider is hex, which the default options always decode")
+}
+
+/// Decodes the calldata of the given transaction with the given options
+pub async fn decode_transaction_with_options(
+    tx_hash: &str,
+    provider_rpc_url: Option<&str>,
+    options: &DecodingOptions,
+) -> Result<DecodingResult, DecodingError> {
+    decode_transaction_with_engine(tx_hash, provider_rpc_url, options, &GreedySearch).await
+}
+
+/// Decodes the calldata of the given transaction with the given options and layout engine
+pub async fn decode_transaction_with_engine(
+    tx_hash: &str,
+    provider_rpc_url: Option<&str>,
+    options: &DecodingOptions,
+    engine: &dyn LayoutEngine,
+) -> Result<DecodingResult, DecodingError> {
     let tx_hash = tx_hash.trim_start_matches("0x");
     let arguments_encoded = get_encoded_arguments(tx_hash, provider_rpc_url).await;
     if arguments_encoded.is_empty() {
-        println!("Not a valid function call");
-        return Vec::new();
+        return Ok(DecodingResult::default());
     }
 
-    chunk_and_decode_data(&arguments_encoded)
+    decode_arguments_with_engine(&arguments_encoded, options, engine)
+}
+
+/// Decodes hex encoded arguments (calldata without the function selector)
+///
+/// Example
+/// ```rust
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000002\
+///                0000000000000000000000000000000000000000000000000000000000000003\
+///                0000000000000000000000000000000000000000000000000000000000000004\
+///                360c6ebe";
+/// let result = abi_decoder::decode_arguments(encoded).unwrap();
+/// assert_eq!(result.tokens.len(), 1);
+/// let trailing_data = result.trailing_data.unwrap();
+/// assert_eq!(trailing_data.domain_tag.unwrap().domain, Some("opensea.io"));
+/// ```
+///
+/// Arguments that are not hex are reported as [`DecodingError::InvalidHex`]:
+/// ```rust
+/// let error = abi_decoder::decode_arguments("zz").unwrap_err();
+/// assert_eq!(error, abi_decoder::DecodingError::InvalidHex(0));
+/// ```
+///
+pub fn decode_arguments(encoded_arguments: &str) -> Result<DecodingResult, DecodingError> {
+    decode_arguments_with_options(encoded_arguments, &DecodingOptions::default())
+}
+
+/// Decodes hex encoded arguments with the given options
+///
+/// Example
+/// ```rust
+/// // Two arrays whose tails are stored in reverse order
+/// let encoded = "00000000000000000000000000000000000000000000000000000000000000a0\
+///                0000000000000000000000000000000000000000000000000000000000000040\
+///                0000000000000000000000000000000000000000000000000000000000000002\
+///                0000000000000000000000000000000000000000000000000000000000000003\
+///                0000000000000000000000000000000000000000000000000000000000000004\
+///                0000000000000000000000000000000000000000000000000000000000000002\
+///                0000000000000000000000000000000000000000000000000000000000000001\
+///                0000000000000000000000000000000000000000000000000000000000000002";
+/// let options = abi_decoder::DecodingOptions {
+///     allow_non_canonical: true,
+///     ..Default::default()
+/// };
+/// let result = abi_decoder::decode_arguments_with_options(encoded, &options).unwrap();
+/// assert_eq!(result.tokens.len(), 2);
+/// assert!(result.non_canonical);
+///
+/// // Strict mode reports the out of order tails as an error instead
+/// let options = abi_decoder::DecodingOptions {
+///     allow_non_canonical: true,
+///     strict: true,
+///     ..Default::default()
+/// };
+/// assert!(abi_decoder::decode_arguments_with_options(encoded, &options).is_err());
+/// ```
+///
+pub fn decode_arguments_with_options(
+    encoded_arguments: &str,
+    options: &DecodingOptions,
+) -> Result<DecodingResult, DecodingError> {
+    decoder::decode_arguments(encoded_arguments.trim_start_matches("0x"), options)
+}
+
+/// Decodes hex encoded arguments with the given options and layout engine
+///
+/// The [`ConstraintSolver`] returns every layout it finds, the best one as the decoded tokens
+/// and the others as alternatives.
+///
+/// Example
+/// ```rust
+/// // Either an array containing an empty array or an array containing the tuple (32, 0)
+/// let encoded = "0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000001\
+///                0000000000000000000000000000000000000000000000000000000000000020\
+///                0000000000000000000000000000000000000000000000000000000000000000";
+/// let solver = abi_decoder::ConstraintSolver::default();
+/// let options = abi_decoder::DecodingOptions::default();
+/// let result = abi_decoder::decode_arguments_with_engine(encoded, &options, &solver).unwrap();
+/// assert_eq!(result.tokens.len(), 1);
+/// assert!(!result.alternatives.is_empty());
+/// ```
+///
+pub fn decode_arguments_with_engine(
+    encoded_arguments: &str,
+    options: &DecodingOptions,
+    engine: &dyn LayoutEngine,
+) -> Result<DecodingResult, DecodingError> {
+    decoder::decode_arguments_with_engine(
+        encoded_arguments.trim_start_matches("0x"),
+        options,
+        engine,
+    )
 }
@@ -1,20 +1,243 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use ethabi::Token;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Engine {
+    /// Greedy search that finds one layout, and alternatives if it does not re-encode to the data
+    Greedy,
+    /// Constraint solver that finds all layouts
+    Constraints,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ExplainFormat {
+    /// One line per word followed by the rejected alternatives
+    Text,
+    /// Array with the trace of every word
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Learn priors from a corpus of calldata with known signatures (one `<signature>
+    /// <calldata>` per line) and save them to a file
+    Train {
+        /// Corpus to learn from
+        corpus: String,
+        /// File to save the priors to
+        output: String,
+    },
+}
 
 /// Decode transaction calldata without abi
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Transaction whose calldata to decode
-    tx: String,
+    #[arg(required = true)]
+    tx: Option<String>,
+    /// Accept offsets pointing to tails out of order or to the same tail
+    #[arg(long)]
+    allow_non_canonical: bool,
+    /// Fail if the calldata is not the canonical encoding of the decoded values
+    #[arg(long)]
+    strict: bool,
+    /// Stop decoding after trying to parse this many nested values
+    #[arg(long)]
+    max_steps: Option<usize>,
+    /// Stop decoding after this many milliseconds
+    #[arg(long)]
+    max_duration_ms: Option<u64>,
+    /// Do not decode values nested deeper than this
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Layout engine used to decode the calldata
+    #[arg(long, value_enum, default_value_t = Engine::Greedy)]
+    engine: Engine,
+    /// Rank the layouts by the likelihood under the priors in this file (see `train`)
+    #[arg(long)]
+    priors: Option<String>,
+    /// Explain why each word was interpreted the way it was
+    #[arg(long, value_enum)]
+    explain: Option<ExplainFormat>,
+    /// Partially known types of the arguments with `?` for unknown types (i.e.
+    /// `(address,?,bytes,?[])`)
+    #[arg(long)]
+    type_hints: Option<String>,
+    /// Force (`<word>=<role>`) or forbid (`<word>!=<role>`) the role of a word, with the roles
+    /// value, offset, bytes-length and array-length
+    #[arg(long = "pin")]
+    pins: Vec<abi_decoder::Pin>,
+    /// Print the fields of a single struct argument as arguments and tuples with a single element
+    /// as the element
+    #[arg(long)]
+    unwrap: bool,
+    /// Only print the values matching the query, one per line (i.e. `$[0][*].1` for the second
+    /// field of every tuple in the first argument or `$..[?address]` for all addresses)
+    #[arg(long)]
+    select: Option<abi_decoder::Query>,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    let tx_hash = args.tx.trim_start_matches("0x");
-    let tokens = abi_decoder::decode_transaction_calldata(tx_hash, None).await;
+    if let Some(Command::Train { corpus, output }) = &args.command {
+        train(corpus, output);
+        return;
+    }
+    let tx = args.tx.expect("Transaction is required without a command");
+    let tx_hash = tx.trim_start_matches("0x");
+    let mut options = abi_decoder::DecodingOptions {
+        allow_non_canonical: args.allow_non_canonical,
+        strict: args.strict,
+        max_steps: args.max_steps,
+        max_duration: args.max_duration_ms.map(Duration::from_millis),
+        max_depth: args.max_depth,
+        explain: args.explain.is_some(),
+        pins: args.pins.clone(),
+        ..Default::default()
+    };
+    if let Some(path) = &args.priors {
+        let priors: abi_decoder::Priors = match read_file(path).parse() {
+            Ok(priors) => priors,
+            Err(error) => {
+                println!("{}: {}", path, error);
+                std::process::exit(1);
+            }
+        };
+        options.heuristics.push(priors);
+    }
+    if let Some(template) = &args.type_hints {
+        match template.parse() {
+            Ok(type_hints) => options.type_hints = Some(type_hints),
+            Err(error) => {
+                println!("{}: {}", template, error);
+                std::process::exit(1);
+            }
+        }
+    }
+    let engine: Box<dyn abi_decoder::LayoutEngine> = match args.engine {
+        Engine::Greedy => Box::new(abi_decoder::GreedySearch),
+        Engine::Constraints => Box::new(abi_decoder::ConstraintSolver::default()),
+    };
+    let result =
+        match abi_decoder::decode_transaction_with_engine(tx_hash, None, &options, engine.as_ref())
+            .await
+        {
+            Ok(result) => result,
+            Err(error) => {
+                println!("{}", error);
+                std::process::exit(1);
+            }
+        };
 
+    let tokens = if args.unwrap {
+        result.unwrapped_tokens()
+    } else {
+        result.tokens.clone()
+    };
+    if let Some(query) = &args.select {
+        for (_, token) in query.select(&tokens) {
+            println!("{}", format_value(token));
+        }
+        return;
+    }
+    // The explanation as JSON is printed on its own so that the output can be parsed
+    if let (Some(ExplainFormat::Json), Some(explanation)) = (args.explain, &result.explanation) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&explanation.to_json())
+                .expect("Failed to serialize explanation")
+        );
+        return;
+    }
     println!("#### Decoded Tokens ####");
     for token in &tokens {
         abi_decoder::print_parse_tree(token, 0);
     }
+    println!("#### Types ####");
+    println!("{}", result.types());
+    for (i, alternative) in result.alternatives.iter().enumerate() {
+        println!("#### Alternative Layout {} ####", i + 1);
+        for token in alternative {
+            abi_decoder::print_parse_tree(token, 0);
+        }
+    }
+    if let (Some(ExplainFormat::Text), Some(explanation)) = (args.explain, &result.explanation) {
+        println!("#### Explanation ####");
+        print!("{}", explanation);
+    }
+    if result.partial {
+        println!("Warning: decoding stopped early, undecoded values are shown as raw bytes");
+    }
+    if result.non_canonical {
+        println!("Warning: calldata is not the canonical encoding of the decoded tokens");
+    }
+    if let Some(trailing_data) = result.trailing_data {
+        println!("#### Trailing Data ####");
+        println!("{}", hex::encode(&trailing_data.data));
+        if let Some(domain_tag) = trailing_data.domain_tag {
+            println!(
+                "Domain tag: {} ({})",
+                hex::encode(domain_tag.tag),
+                domain_tag.domain.unwrap_or("unknown domain")
+            );
+        }
+    }
+}
+
+// Formats the value on a single line, numbers in decimal and bytes in hex
+fn format_value(token: &Token) -> String {
+    let format_values = |tokens: &[Token]| -> String {
+        tokens
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    match token {
+        Token::Address(address) => format!("{:?}", address),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        Token::Uint(value) => value.to_string(),
+        // Negative numbers are stored in two's complement
+        Token::Int(value) if value.bit(255) => {
+            format!("-{}", (!*value).overflowing_add(1.into()).0)
+        }
+        Token::Int(value) => value.to_string(),
+        Token::Bool(value) => value.to_string(),
+        Token::String(string) => format!("{:?}", string),
+        Token::Array(tokens) | Token::FixedArray(tokens) => format!("[{}]", format_values(tokens)),
+        Token::Tuple(tokens) => format!("({})", format_values(tokens)),
+    }
+}
+
+fn train(corpus: &str, output: &str) {
+    let priors = match abi_decoder::Priors::from_corpus(&read_file(corpus)) {
+        Ok(priors) => priors,
+        Err(error) => {
+            println!("{}: {}", corpus, error);
+            std::process::exit(1);
+        }
+    };
+    if let Err(error) = std::fs::write(output, priors.to_string()) {
+        println!("{}: {}", output, error);
+        std::process::exit(1);
+    }
+    println!(
+        "Learned from {} length candidates, saved priors to {}",
+        priors.lengths.total, output
+    );
+}
+
+fn read_file(path: &str) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+            println!("{}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
 }
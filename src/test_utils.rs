@@ -1,4 +1,6 @@
 use crate::decoder;
+use crate::decoder::visit::fold_tokens;
+use crate::DecodingResult;
 use ethabi::Token;
 use ethereum_types::{H160, U256};
 use std::str::FromStr;
@@ -36,44 +38,32 @@ pub fn remove_single_top_level_tuple(tokens: Vec<Token>) -> Vec<Token> {
 }
 
 pub fn replace_zero_value_with_uint(tokens: Vec<Token>) -> Vec<Token> {
-    tokens
-        .iter()
-        .map(|token| replace_zero_value_with_uint_single_token(token.clone()))
-        .collect()
+    fold_tokens(tokens, &mut |_: &[usize], token: Token| match token {
+        Token::Bool(false) => Token::Uint(U256::from(0)),
+        Token::Address(address) if address == H160::zero() => Token::Uint(U256::from(0)),
+        Token::Int(int) if int == U256::zero() => Token::Uint(U256::from(0)),
+        token => token,
+    })
 }
 
-pub fn replace_zero_value_with_uint_single_token(token: Token) -> Token {
-    match token {
-        Token::Bool(false) => Token::Uint(U256::from(0)),
-        Token::Address(address) => {
-            if address == H160::zero() {
-                Token::Uint(U256::from(0))
-            } else {
-                token
-            }
-        }
-        Token::Array(tokens) => Token::Array(replace_zero_value_with_uint(tokens)),
-        Token::Tuple(tokens) => Token::Tuple(replace_zero_value_with_uint(tokens)),
-        Token::Int(int) => {
-            if int == U256::zero() {
-                Token::Uint(U256::from(0))
-            } else {
-                token
-            }
-        }
-        _ => token,
+// Re-encodes the decoded tokens and appends the trailing data to get back the original arguments
+pub fn reencode_with_trailing_data(result: &DecodingResult) -> String {
+    let mut reencoded = hex::encode(ethabi::encode(&result.tokens));
+    if let Some(trailing_data) = &result.trailing_data {
+        reencoded.push_str(&hex::encode(&trailing_data.data));
     }
+    reencoded
 }
 
 pub fn print_chunked_data(label: &str, data: &str) {
     println!("{}", label);
-    let chunks = decoder::preprocessing::chunk_data(data);
-    for (i, chunk) in chunks.iter().enumerate() {
+    let words = decoder::word::to_words(&hex::decode(data).expect("Failed to decode data"));
+    for (i, word) in words.iter().enumerate() {
         println!(
             "{}: {} - {}",
             i,
-            chunk,
-            u64::from_str_radix(chunk.trim_start_matches('0'), 16).unwrap_or(0)
+            hex::encode(word.bytes),
+            word.as_usize().unwrap_or(0)
         );
     }
 }
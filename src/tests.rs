@@ -1,13 +1,14 @@
 use super::*;
 use crate::test_utils::parameterize;
 use decoder::preprocessing::add_padding;
-use ethabi::{Contract, Token};
+use ethabi::{Contract, ParamType, Token};
 use ethers::providers::Middleware;
 use std::env;
 use transaction_data::{get_provider, split_off_encoded_arguments};
 
 mod data;
 
+#[allow(dead_code, clippy::upper_case_acronyms)]
 enum Chain {
     BSC,
     Ethereum,
@@ -42,7 +43,7 @@ parameterize!(
     can_re_encode_single_transaction,
     [
         // These opensea transactions contain extra data appeneded after the encoded_arguments which
-        // will always mess up this algorithm
+        // is returned separately as trailing data
         (
             nft_bulk_transfer,
             "0x32cf9e754e4e2400886bb9119130de3c826132921cd444ad882efe670f29cc23"
@@ -55,12 +56,10 @@ parameterize!(
             opensea_cancel_listing,
             "0xe65afe90ca425074a68231a64c30e743878c46e0bed15307561c31d1acbce297"
         ),
-        // Transaction with huge calldata that gets the algorithm stuck in infinite recursion
-        // TODO: Try to reproduce with smaller example
-        // (
-        //     opensea_fullfill_multiple_orders_long,
-        //     "0x9360601719fa9c412e402dde237a384ff7517e64cb47258b775b237c8d88827f"
-        // ),
+        (
+            opensea_fullfill_multiple_orders_long,
+            "0x9360601719fa9c412e402dde237a384ff7517e64cb47258b775b237c8d88827f"
+        ),
         (
             opensea_fullfill_multiple_orders_shorter,
             "0x7c1531482c3c1d1d42638016e8912ed7d12ba709efdb0db77790308f4af8a531"
@@ -87,6 +86,155 @@ parameterize!(
     ]
 );
 
+parameterize!(
+    decoder_decodes_calldata,
+    [
+        (
+            known_signature,
+            (
+                "a9059cbb\
+                 000000000000000000000000000000000000000000000000000000000000dead\
+                 0000000000000000000000000000000000000000000000000000000000000064",
+                Some("transfer(address,uint256)"),
+                vec![
+                    Token::Address(ethabi::Address::from_low_u64_be(0xdead)),
+                    Token::Uint(100.into())
+                ]
+            )
+        ),
+        (
+            unknown_selector,
+            (
+                "095ea7b3\
+                 000000000000000000000000000000000000000000000000000000000000dead\
+                 0000000000000000000000000000000000000000000000000000000000000064",
+                None,
+                vec![Token::Uint(0xdead.into()), Token::Uint(100.into())]
+            )
+        ),
+        (
+            arguments_not_matching_signature,
+            (
+                "a9059cbb0000000000000000000000000000000000000000000000000000000000000064",
+                None,
+                vec![Token::Uint(100.into())]
+            )
+        ),
+        (not_a_function_call, ("a905", None, vec![])),
+    ]
+);
+
+parameterize!(
+    invalid_signature,
+    [
+        (
+            unclosed_arguments,
+            (
+                "transfer(address",
+                SignatureError::Syntax("transfer(address".to_string())
+            )
+        ),
+        (
+            missing_name,
+            ("(uint256)", SignatureError::Syntax("(uint256)".to_string()))
+        ),
+        (
+            unknown_type,
+            (
+                "mint(uint7)",
+                SignatureError::UnknownType("uint7".to_string())
+            )
+        ),
+        (
+            unknown_argument,
+            ("mint(?)", SignatureError::Syntax("mint(?)".to_string()))
+        ),
+    ]
+);
+
+fn decoder_decodes_calldata(calldata_signature_and_tokens: (&str, Option<&str>, Vec<Token>)) {
+    let (calldata, signature, tokens) = calldata_signature_and_tokens;
+    let signatures = "transfer(address,uint256)\ntransferFrom(address,address,uint256)";
+    let decoder =
+        Decoder::new(DecodingOptions::default()).with_signatures(signatures.parse().unwrap());
+    let result = decoder.decode_calldata(calldata).unwrap();
+    assert_eq!(result.signature.as_deref(), signature);
+    assert_eq!(result.tokens, tokens);
+}
+
+fn invalid_signature(signature_and_error: (&str, SignatureError)) {
+    let (signature, error) = signature_and_error;
+    assert_eq!(signature.parse::<Signatures>(), Err(error));
+}
+
+#[test]
+fn decoder_is_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync + Clone>(_: &T) {}
+    let decoder = Decoder::new(DecodingOptions::default());
+    assert_send_sync(&decoder);
+    let address = "000000000000000000000000000a0b0c0d0e0f000000000000000000000000ff";
+    let mut address_book = AddressBook::default();
+    address_book.insert(address[24..].parse().unwrap(), "Vault");
+    let decoder = decoder.with_address_book(address_book);
+    let clone = decoder.clone();
+    let tokens = std::thread::spawn(move || clone.decode_arguments(address).unwrap().tokens)
+        .join()
+        .unwrap();
+    assert_eq!(tokens, vec![Token::Address(address[24..].parse().unwrap())]);
+    assert_eq!(
+        decoder
+            .address_book()
+            .label(&address[24..].parse().unwrap()),
+        Some("Vault")
+    );
+}
+
+#[test]
+fn decoder_decodes_log() {
+    let event = "Transfer(address,address,uint256)";
+    let decoder = Decoder::new(DecodingOptions::default()).with_signatures(event.parse().unwrap());
+    let from = ethabi::Address::repeat_byte(0x11);
+    let to = ethabi::Address::repeat_byte(0x22);
+    let topics = vec![
+        ethabi::long_signature(
+            "Transfer",
+            &[ParamType::Address, ParamType::Address, ParamType::Uint(256)],
+        ),
+        from.into(),
+        to.into(),
+    ];
+    let data = hex::encode(ethabi::encode(&[Token::Uint(100.into())]));
+    let log = decoder.decode_log(&topics, &data).unwrap();
+    assert_eq!(log.event.as_deref(), Some(event));
+    assert_eq!(log.topics, vec![Token::Address(from), Token::Address(to)]);
+    assert_eq!(log.data.tokens, vec![Token::Uint(100.into())]);
+
+    // Logs of unknown events might be anonymous, so all topics are indexed values
+    let log = Decoder::new(DecodingOptions::default())
+        .decode_log(&topics, &data)
+        .unwrap();
+    assert_eq!(log.event, None);
+    assert_eq!(log.topics.len(), 3);
+}
+
+#[test]
+fn decoder_decodes_revert_data() {
+    let decoder =
+        Decoder::new(DecodingOptions::default()).with_signatures("Error(string)".parse().unwrap());
+    let reason = Token::String("Insufficient balance".to_string());
+    let revert_data = format!(
+        "08c379a0{}",
+        hex::encode(ethabi::encode(std::slice::from_ref(&reason)))
+    );
+    let result = decoder.decode_return_data(&revert_data).unwrap();
+    assert_eq!(result.signature.as_deref(), Some("Error(string)"));
+    assert_eq!(result.tokens, vec![reason]);
+
+    let return_data = hex::encode(ethabi::encode(&[Token::Uint(7.into())]));
+    let result = decoder.decode_return_data(&return_data).unwrap();
+    assert_eq!(result.tokens, vec![Token::Uint(7.into())]);
+}
+
 #[tokio::main]
 async fn produces_expected_result(tx_hash_and_chain_enum: (&str, Chain, Vec<Token>)) {
     let (tx_hash, chain, expected_tokens) = tx_hash_and_chain_enum;
@@ -146,8 +294,11 @@ async fn same_decoding_as_etherscan(tx_hash: &str) {
 #[tokio::main]
 async fn can_re_encode_single_transaction(tx_hash: &str) {
     let tx_hash = tx_hash.trim_start_matches("0x");
-    let arguments_encoded = add_padding(&get_encoded_arguments(tx_hash, None).await);
-    test_utils::print_chunked_data("#### ENCODED ARGUMENTS ####", &arguments_encoded);
+    let arguments_encoded = get_encoded_arguments(tx_hash, None).await;
+    test_utils::print_chunked_data(
+        "#### ENCODED ARGUMENTS ####",
+        &add_padding(&arguments_encoded),
+    );
 
     let expected_tokens =
         test_utils::remove_single_top_level_tuple(decode_tx_via_etherscan(tx_hash).await.unwrap());
@@ -156,28 +307,28 @@ async fn can_re_encode_single_transaction(tx_hash: &str) {
         utils::print_parse_tree(token, 0);
     }
 
-    let tokens = decode_transaction_calldata(tx_hash, None).await;
+    let result = decode_transaction(tx_hash, None).await;
     println!("#### Decoded Tokens ####");
-    for token in &tokens {
+    for token in &result.tokens {
         utils::print_parse_tree(token, 0);
     }
     println!("### DONE ##");
-    let tokens_reencoded = hex::encode(ethabi::encode(&tokens));
+    let tokens_reencoded = test_utils::reencode_with_trailing_data(&result);
     println!("Reencoded tokens length: {}", tokens_reencoded.len());
-    test_utils::print_chunked_data("#### RE-ENCODED ARGUMENTS ####", &tokens_reencoded);
+    test_utils::print_chunked_data(
+        "#### RE-ENCODED ARGUMENTS ####",
+        &add_padding(&tokens_reencoded),
+    );
 
     assert_eq!(tokens_reencoded, arguments_encoded);
 }
 
 // Opensea/Seaport transactions often are troublesome since they contain complex nested
 // data and added data after the encoded arguments. This makes it hard to decode them correctly
+#[allow(dead_code)]
 #[tokio::main]
 async fn can_re_encode_all_transactions_to_seaport() {
     let start_block = 16136002;
-    // TODO: Transactions with very large calldata to seaport methods can cause the algorithm
-    // to get stuck - investigate
-    // Increase this value to find the smallest problematic transaction for debugging
-    let max_calldata_size = 64 * 100;
     let num_blocks = 5;
     let seaport_address = ethereum_types::H160::from_slice(
         &hex::decode("00000000006c3852cbef3e08e8df289169ede581").unwrap(),
@@ -199,24 +350,26 @@ async fn can_re_encode_all_transactions_to_seaport() {
                 println!("Tx index: {}", i);
                 let tx_hash = hex::encode(tx.hash.0);
                 let calldata = hex::encode(&tx.input.0);
-                let encoded_arguments = add_padding(split_off_encoded_arguments(&calldata));
-                test_utils::print_chunked_data("#### ENCODED ARGUMENTS ####", &encoded_arguments);
-                if encoded_arguments.len() > max_calldata_size {
-                    println!("Skipping transaction with huge calldata: {}", tx_hash);
-                    continue;
-                }
+                let encoded_arguments = split_off_encoded_arguments(&calldata);
+                test_utils::print_chunked_data(
+                    "#### ENCODED ARGUMENTS ####",
+                    &add_padding(encoded_arguments),
+                );
                 println!("Encoded arguments length: {}", encoded_arguments.len());
                 println!("Decoding tx: {}", tx_hash);
-                let tokens = decode_transaction_calldata(&tx_hash, None).await;
+                let result = decode_transaction(&tx_hash, None).await;
                 println!();
                 println!("#### Decoded Tokens ####");
-                for token in &tokens {
+                for token in &result.tokens {
                     utils::print_parse_tree(token, 0);
                 }
                 println!("### DONE ##");
-                let tokens_reencoded = hex::encode(ethabi::encode(&tokens));
+                let tokens_reencoded = test_utils::reencode_with_trailing_data(&result);
                 println!("Reencoded tokens length: {}", tokens_reencoded.len());
-                test_utils::print_chunked_data("#### RE-ENCODED ARGUMENTS ####", &tokens_reencoded);
+                test_utils::print_chunked_data(
+                    "#### RE-ENCODED ARGUMENTS ####",
+                    &add_padding(&tokens_reencoded),
+                );
                 assert_eq!(tokens_reencoded, encoded_arguments);
             }
         }
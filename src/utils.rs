@@ -1,5 +1,7 @@
 use ethabi::Token;
 
+use crate::decoder::visit::visit_token;
+
 fn print_with_indentation(indent: usize, s: &str) {
     for _i in 0..indent {
         print!("    ");
@@ -9,25 +11,20 @@ fn print_with_indentation(indent: usize, s: &str) {
 
 /// Recursively prints nested tokens with increasing identation
 pub fn print_parse_tree(parse_tree: &Token, indentation: usize) {
-    match parse_tree {
-        Token::Array(ref elements) => {
-            print_with_indentation(indentation, "Array: ");
-            for item in elements {
-                print_parse_tree(item, indentation + 1);
+    visit_token(parse_tree, &mut |path: &[usize], token: &Token| {
+        let indentation = indentation + path.len();
+        match token {
+            Token::Array(_) => print_with_indentation(indentation, "Array: "),
+            Token::FixedArray(_) => print_with_indentation(indentation, "FixedArray: "),
+            Token::Tuple(_) => print_with_indentation(indentation, "Tuple: "),
+            // Avoid normal bytes debug output which prints a huge array of bytes
+            Token::Bytes(ref bytes) => {
+                print_with_indentation(indentation, &format!("Bytes: {:?}", hex::encode(bytes)));
             }
-        }
-        Token::Tuple(ref elements) => {
-            print_with_indentation(indentation, "Tuple: ");
-            for item in elements {
-                print_parse_tree(item, indentation + 1);
+            token => {
+                print_with_indentation(indentation, &format!("{:?}", token));
             }
         }
-        // Avoid normal bytes debug output which prints a huge array of bytes
-        Token::Bytes(ref bytes) => {
-            print_with_indentation(indentation, &format!("Bytes: {:?}", hex::encode(bytes)));
-        }
-        token => {
-            print_with_indentation(indentation, &format!("{:?}", token));
-        }
-    }
+        true
+    });
 }